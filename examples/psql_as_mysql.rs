@@ -12,6 +12,7 @@ use mysql::prelude::*;
 use mysql::Opts;
 use slab::Slab;
 
+use std::convert::TryFrom;
 use std::io;
 use std::net;
 use std::thread;
@@ -146,9 +147,7 @@ impl<W: io::Read + io::Write> MysqlShim<W> for Postgres {
                         let ct = p2mt(t);
                         Column {
                             table: t.schema().to_owned(),
-                            column: t.name().to_owned(),
-                            coltype: ct,
-                            colflags: ColumnFlags::empty(),
+                            ..Column::new(t.name(), ct)
                         }
                     })
                     .collect();
@@ -160,9 +159,7 @@ impl<W: io::Read + io::Write> MysqlShim<W> for Postgres {
                         let ct = p2mt(t);
                         Column {
                             table: t.schema().to_owned(),
-                            column: c.name().to_owned(),
-                            coltype: ct,
-                            colflags: ColumnFlags::empty(),
+                            ..Column::new(c.name(), ct)
                         }
                     })
                     .collect();
@@ -211,32 +208,34 @@ impl<W: io::Read + io::Write> MysqlShim<W> for Postgres {
                 // *then* take a slice of that vec.
                 let args: Vec<Box<dyn postgres::types::ToSql + Sync>> = ps
                     .into_iter()
-                    .map(|p| match p.coltype {
-                        ColumnType::MYSQL_TYPE_SHORT => {
-                            Box::new(Into::<i16>::into(p.value)) as Box<_>
-                        }
-                        ColumnType::MYSQL_TYPE_LONG => {
-                            Box::new(Into::<i32>::into(p.value)) as Box<_>
-                        }
-                        ColumnType::MYSQL_TYPE_LONGLONG => {
-                            Box::new(Into::<i64>::into(p.value)) as Box<_>
-                        }
-                        ColumnType::MYSQL_TYPE_FLOAT => {
-                            Box::new(Into::<f32>::into(p.value)) as Box<_>
-                        }
-                        ColumnType::MYSQL_TYPE_DOUBLE => {
-                            Box::new(Into::<f64>::into(p.value)) as Box<_>
-                        }
-                        ColumnType::MYSQL_TYPE_STRING => {
-                            Box::new(Into::<&str>::into(p.value)) as Box<_>
-                        }
-                        ct => unimplemented!(
-                            "don't know how to translate PostgreSQL \
+                    .map(|p| {
+                        Ok(match p.coltype {
+                            ColumnType::MYSQL_TYPE_SHORT => {
+                                Box::new(i16::try_from(p.value)?) as Box<_>
+                            }
+                            ColumnType::MYSQL_TYPE_LONG => {
+                                Box::new(i32::try_from(p.value)?) as Box<_>
+                            }
+                            ColumnType::MYSQL_TYPE_LONGLONG => {
+                                Box::new(i64::try_from(p.value)?) as Box<_>
+                            }
+                            ColumnType::MYSQL_TYPE_FLOAT => {
+                                Box::new(f32::try_from(p.value)?) as Box<_>
+                            }
+                            ColumnType::MYSQL_TYPE_DOUBLE => {
+                                Box::new(f64::try_from(p.value)?) as Box<_>
+                            }
+                            ColumnType::MYSQL_TYPE_STRING => {
+                                Box::new(<&str>::try_from(p.value)?) as Box<_>
+                            }
+                            ct => unimplemented!(
+                                "don't know how to translate PostgreSQL \
                              argument type {:?} into MySQL value",
-                            ct
-                        ),
+                                ct
+                            ),
+                        })
                     })
-                    .collect();
+                    .collect::<io::Result<_>>()?;
                 let args: Vec<_> = args.iter().map(|a| &**a).collect();
 
                 // lazy_query unfortunately gets us into all sorts of lifetime trouble it seems...
@@ -279,9 +278,7 @@ fn answer_rows<W: io::Read + io::Write>(
                         let ct = p2mt(t);
                         Column {
                             table: t.schema().to_owned(),
-                            column: c.name().to_owned(),
-                            coltype: ct,
-                            colflags: ColumnFlags::empty(),
+                            ..Column::new(c.name(), ct)
                         }
                     })
                     .collect();
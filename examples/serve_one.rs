@@ -10,8 +10,6 @@ extern crate mysql;
 
 use msql_srv::*;
 use std::io;
-use std::net;
-use std::thread;
 
 struct Backend;
 impl<W: io::Read + io::Write> MysqlShim<W> for Backend {
@@ -36,16 +34,8 @@ impl<W: io::Read + io::Write> MysqlShim<W> for Backend {
 }
 
 fn main() {
-    let mut threads = Vec::new();
-    let listener = net::TcpListener::bind("127.0.0.1:3306").unwrap();
-
-    while let Ok((s, _)) = listener.accept() {
-        threads.push(thread::spawn(move || {
-            MysqlIntermediary::run_on_tcp(Backend, s).unwrap();
-        }));
-    }
-
-    for t in threads {
-        t.join().unwrap();
-    }
+    MysqlServer::bind("127.0.0.1:3306", |_| Backend)
+        .unwrap()
+        .run()
+        .unwrap();
 }
@@ -0,0 +1,741 @@
+use crate::myc::constants::CapabilityFlags;
+use crate::packet::{Packet, PacketConn};
+use crate::server::Stop;
+use crate::{
+    commands, ignore_hang_up, invalid_string, parse_handshake, peer_terminated, reject,
+    say_goodbye, ssl_not_supported, trim_schema, unknown_statement, use_schema, writers,
+    AuthenticationContext, BootstrapQuery, Charset, Column, Connection, ErrorKind, InitWriter,
+    ParamParser, QueryResultWriter, RowWriter, ShutdownHandle, StatementMetaWriter, StatusFlags,
+    SystemVariables, ToMysqlValue, Transport,
+};
+use async_trait::async_trait;
+use futures_core::Stream;
+use futures_io::{AsyncRead, AsyncWrite};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// A connection to a client that is driven by an
+/// [`AsyncMysqlIntermediary`](struct.AsyncMysqlIntermediary.html).
+///
+/// This is the type parameter of the writers that are passed to an
+/// [`AsyncMysqlShim`](trait.AsyncMysqlShim.html). The writers buffer everything that is written
+/// to them, and the buffer is sent to the client when the shim returns. To send what has been
+/// written so far while producing a large response, `.await` the `flush` method of the writer.
+pub struct AsyncStream {
+    stream: Box<dyn RawStream>,
+    input: Vec<u8>,
+    eof: bool,
+    output: Vec<u8>,
+    #[cfg(feature = "tls")]
+    tls: Option<Box<rustls::ServerConnection>>,
+}
+
+/// The stream we talk to the client over.
+trait RawStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> RawStream for T {}
+
+impl AsyncStream {
+    fn new<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(stream: S) -> Self {
+        AsyncStream {
+            stream: Box::new(stream),
+            input: Vec::new(),
+            eof: false,
+            output: Vec::new(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Take in bytes received from the client.
+    fn receive(&mut self, raw: &[u8]) -> io::Result<()> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &mut self.tls {
+            let mut raw = raw;
+            while !raw.is_empty() {
+                tls.read_tls(&mut raw)?;
+                let state = tls
+                    .process_new_packets()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let start = self.input.len();
+                self.input
+                    .resize(start + state.plaintext_bytes_to_read(), 0);
+                tls.reader().read_exact(&mut self.input[start..])?;
+                if state.peer_has_closed() {
+                    self.eof = true;
+                }
+            }
+            return Ok(());
+        }
+
+        self.input.extend_from_slice(raw);
+        Ok(())
+    }
+
+    /// Switch to TLS, starting with the `unread` bytes that have already been received.
+    #[cfg(feature = "tls")]
+    fn start_tls(
+        &mut self,
+        config: std::sync::Arc<rustls::ServerConfig>,
+        unread: &[u8],
+    ) -> io::Result<()> {
+        let mut tls = rustls::ServerConnection::new(config).map_err(io::Error::other)?;
+        // we cannot wait for rustls to make room in the middle of a response
+        tls.set_buffer_limit(None);
+        self.tls = Some(Box::new(tls));
+
+        let mut raw = unread.to_vec();
+        raw.append(&mut self.input);
+        self.receive(&raw)
+    }
+
+    #[cfg(feature = "tls")]
+    fn tls_certs(&self) -> Option<Vec<rustls::Certificate>> {
+        self.tls.as_ref()?.peer_certificates().map(<[_]>::to_vec)
+    }
+
+    /// Read more bytes from the client.
+    async fn fill(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        let n = poll_fn(|cx| Pin::new(&mut self.stream).poll_read(cx, &mut buf)).await?;
+        if n == 0 {
+            self.eof = true;
+        }
+        self.receive(&buf[..n])?;
+        // the TLS handshake may need us to reply
+        #[cfg(feature = "tls")]
+        if matches!(&self.tls, Some(tls) if tls.wants_write()) {
+            self.drain().await?;
+        }
+        Ok(())
+    }
+    async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut self.stream).poll_write(cx, buf)).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    /// Send everything that has been written to the client.
+    async fn drain(&mut self) -> io::Result<()> {
+        let mut output = std::mem::take(&mut self.output);
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &mut self.tls {
+            tls.writer().write_all(&output)?;
+            output.clear();
+            while tls.wants_write() {
+                tls.write_tls(&mut output)?;
+            }
+        }
+
+        let res = self.write_all(&output).await;
+        // hold on to the allocation
+        output.clear();
+        self.output = output;
+        res?;
+        poll_fn(|cx| Pin::new(&mut self.stream).poll_flush(cx)).await
+    }
+}
+
+// `PacketConn` reads and writes through these; reads that would have to wait for the client fail
+// with `WouldBlock` so that the intermediary can `.await` more input and try again.
+impl Read for AsyncStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.input.is_empty() {
+            if self.eof {
+                return Ok(0);
+            }
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let n = buf.len().min(self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for AsyncStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// how much of a streamed resultset we buffer before waiting for the client to take it
+const STREAM_BUFFER: usize = 64 * 1024;
+
+impl<'a> QueryResultWriter<'a, AsyncStream> {
+    /// Send what has been written so far to the client.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.get_mut().drain().await
+    }
+
+    /// Send a resultset that conforms to the given `columns` with rows from an async `Stream`.
+    ///
+    /// Rows are sent to the client as they become available, and the stream is not polled while
+    /// the client is slow to take them. If the stream yields an error, the resultset is ended
+    /// with an `ER_UNKNOWN_ERROR` error packet that carries the error's message.
+    pub async fn stream_rows<S, R, T, E>(self, columns: &'a [Column], rows: S) -> io::Result<()>
+    where
+        S: Stream<Item = Result<R, E>>,
+        R: IntoIterator<Item = T>,
+        T: ToMysqlValue,
+        E: fmt::Display,
+    {
+        let mut rows = std::pin::pin!(rows);
+        let mut w = self.start(columns)?;
+        loop {
+            // send what we have before we wait for more rows
+            let ready = poll_fn(|cx| {
+                Poll::Ready(match rows.as_mut().poll_next(cx) {
+                    Poll::Ready(row) => Some(row),
+                    Poll::Pending => None,
+                })
+            })
+            .await;
+            let row = match ready {
+                Some(row) => row,
+                None => {
+                    w.flush().await?;
+                    poll_fn(|cx| rows.as_mut().poll_next(cx)).await
+                }
+            };
+
+            match row {
+                Some(Ok(row)) => {
+                    w.write_row(row)?;
+                    if w.writer().get_mut().output.len() >= STREAM_BUFFER {
+                        w.flush().await?;
+                    }
+                }
+                Some(Err(e)) => {
+                    return w.finish_error(ErrorKind::ER_UNKNOWN_ERROR, &e.to_string().into_bytes())
+                }
+                None => return w.finish(),
+            }
+        }
+    }
+}
+
+impl<'a> RowWriter<'a, AsyncStream> {
+    /// Send the rows that have been completed so far to the client.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer().get_mut().drain().await
+    }
+}
+
+/// Implementors of this trait can be used to drive a MySQL-compatible database backend from an
+/// async runtime.
+///
+/// This is the async counterpart of [`MysqlShim`](trait.MysqlShim.html); see there for what each
+/// method is for. Implementations use [`async_trait`](https://docs.rs/async-trait), and should
+/// be annotated with `#[async_trait]` as well.
+#[async_trait]
+pub trait AsyncMysqlShim: Send {
+    /// The error type produced by operations on this shim.
+    ///
+    /// Must implement `From<io::Error>` so that transport-level errors can be lifted.
+    type Error: From<io::Error> + Send;
+
+    /// Called when the client issues a request to prepare `query` for later execution.
+    async fn on_prepare(
+        &mut self,
+        query: &str,
+        info: StatementMetaWriter<'_, AsyncStream>,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when the client executes a previously prepared statement.
+    async fn on_execute(
+        &mut self,
+        id: u32,
+        params: ParamParser<'_>,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when the client wishes to deallocate resources associated with a previously prepared
+    /// statement.
+    async fn on_close(&mut self, stmt: u32);
+
+    /// Called when the client issues a query for immediate execution.
+    async fn on_query(
+        &mut self,
+        query: &str,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when the client issues a query for immediate execution, with the query text exactly
+    /// as the client sent it.
+    ///
+    /// See [`MysqlShim::on_query_bytes`](trait.MysqlShim.html#method.on_query_bytes).
+    async fn on_query_bytes(
+        &mut self,
+        query: &[u8],
+        charset: Charset,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> Result<(), Self::Error> {
+        match charset.decode(query) {
+            Some(query) => self.on_query(&query, results).await,
+            None => Ok(invalid_string(charset, |k, m| results.error(k, m))?),
+        }
+    }
+
+    /// Called when client switches database.
+    async fn on_init(
+        &mut self,
+        _: &str,
+        _: InitWriter<'_, AsyncStream>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Provides the system variables for a new connection.
+    ///
+    /// See [`MysqlShim::system_variables`](trait.MysqlShim.html#method.system_variables).
+    fn system_variables(&mut self) -> SystemVariables {
+        SystemVariables::default()
+    }
+
+    /// Decides whether a connector bootstrap query should be answered by the intermediary.
+    ///
+    /// See
+    /// [`MysqlShim::answer_bootstrap_query`](trait.MysqlShim.html#method.answer_bootstrap_query).
+    fn answer_bootstrap_query(&self, _query: BootstrapQuery) -> bool {
+        false
+    }
+
+    /// Decides whether `SET GLOBAL` statements from clients update the global system variables.
+    ///
+    /// See [`MysqlShim::allow_set_global`](trait.MysqlShim.html#method.allow_set_global).
+    fn allow_set_global(&self) -> bool {
+        false
+    }
+
+    /// Decides whether packets with unexpected sequence ids are rejected.
+    ///
+    /// See [`MysqlShim::strict_sequence_ids`](trait.MysqlShim.html#method.strict_sequence_ids).
+    fn strict_sequence_ids(&self) -> bool {
+        true
+    }
+
+    /// Provides a handle that stops the connection when it is shut down.
+    ///
+    /// Clients are sent `ER_SERVER_SHUTDOWN` and disconnected once the handle is shut down, even
+    /// while idle. Note that [`ShutdownHandle::shutdown`](struct.ShutdownHandle.html#method.shutdown)
+    /// blocks until the connections are closed, so call it from outside the executor that runs
+    /// them, such as with `tokio::task::spawn_blocking`.
+    ///
+    /// See [`MysqlShim::shutdown_handle`](trait.MysqlShim.html#method.shutdown_handle).
+    fn shutdown_handle(&self) -> Option<ShutdownHandle> {
+        None
+    }
+
+    /// Provides the TLS configuration, if we want to support TLS.
+    #[cfg(feature = "tls")]
+    fn tls_config(&self) -> Option<std::sync::Arc<rustls::ServerConfig>> {
+        None
+    }
+
+    /// Called after successful authentication (including TLS if applicable).
+    async fn after_authentication(
+        &mut self,
+        _context: &AuthenticationContext<'_>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A server that speaks the MySQL/MariaDB protocol over an async stream, and can delegate client
+/// commands to a backend that implements [`AsyncMysqlShim`](trait.AsyncMysqlShim.html).
+///
+/// The intermediary works with any stream that implements the `futures` I/O traits, and so with
+/// any executor. Streams from tokio can be used through
+/// [`run_on_tokio`](struct.AsyncMysqlIntermediary.html#method.run_on_tokio).
+pub struct AsyncMysqlIntermediary<B> {
+    shim: B,
+    rw: PacketConn<AsyncStream>,
+    conn: Connection,
+    timer: Option<Timer>,
+    shutdown: Option<Shutdown>,
+}
+
+/// Completes after the given duration, which lets us time out clients that stop sending packets.
+type Timer = fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+impl<B: AsyncMysqlShim> AsyncMysqlIntermediary<B> {
+    /// Create a new server over a two-way channel and process client commands until the client
+    /// disconnects or an error occurs.
+    ///
+    /// Without a timer to wake us up, clients that stop sending packets are only timed out once
+    /// they send something again; use
+    /// [`run_on_tokio`](#method.run_on_tokio) to enforce `connect_timeout` and `wait_timeout` on
+    /// idle clients.
+    pub async fn run_on<S>(shim: B, stream: S) -> Result<(), B::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::run_with(shim, stream, Transport::Other, None).await
+    }
+
+    /// Like [`run_on`](#method.run_on), but for a stream that is connected over a Unix domain
+    /// socket, which the shim then sees as [`Transport::Unix`](enum.Transport.html).
+    pub async fn run_on_unix<S>(shim: B, stream: S) -> Result<(), B::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::run_with(shim, stream, Transport::Unix, None).await
+    }
+
+    async fn run_with<S>(
+        mut shim: B,
+        stream: S,
+        transport: Transport,
+        timer: Option<Timer>,
+    ) -> Result<(), B::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut rw = PacketConn::new(AsyncStream::new(stream));
+        rw.set_strict_sequence(shim.strict_sequence_ids());
+        let conn = Connection::new(shim.system_variables(), transport);
+        let shutdown = shim.shutdown_handle().map(|h| Shutdown::register(&h));
+        let mut mi = AsyncMysqlIntermediary {
+            shim,
+            rw,
+            conn,
+            timer,
+            shutdown,
+        };
+        mi.init().await?;
+        mi.run().await
+    }
+
+    /// Read the next packet from the client.
+    async fn next(&mut self) -> io::Result<Option<(u8, Packet)>> {
+        loop {
+            match self.rw.next() {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // we stop listening to the client, as if it had hung up
+                    if self.shutting_down() {
+                        return Ok(None);
+                    }
+                    // if the deadline passes first, the next read fails with a timeout
+                    let timeout = match (self.timer, self.rw.deadline()) {
+                        (Some(timer), Some(deadline)) => {
+                            Some(timer(deadline.saturating_duration_since(Instant::now())))
+                        }
+                        _ => None,
+                    };
+                    fill_until(self.rw.get_mut(), timeout, self.shutdown.as_ref()).await?
+                }
+                Err(e) => {
+                    let e = reject(&mut self.rw, e);
+                    self.rw.get_mut().drain().await?;
+                    return Err(e);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Send everything that has been written to the client.
+    async fn flush(&mut self) -> io::Result<()> {
+        self.rw.flush()?;
+        self.rw.get_mut().drain().await
+    }
+
+    fn shutting_down(&self) -> bool {
+        self.shutdown.as_ref().is_some_and(|s| s.stop.requested())
+    }
+
+    /// Tell the client that we are hanging up on it because we are shutting down.
+    async fn say_goodbye(&mut self) -> io::Result<()> {
+        say_goodbye(&mut self.rw)?;
+        ignore_hang_up(self.rw.get_mut().drain().await)
+    }
+
+    async fn init(&mut self) -> Result<(), B::Error> {
+        // the whole handshake has to fit in `connect_timeout`
+        self.rw
+            .set_deadline(self.conn.deadline("connect_timeout"))?;
+
+        #[cfg(feature = "tls")]
+        let tls_conf = self.shim.tls_config();
+        #[cfg(feature = "tls")]
+        self.conn.greet(&mut self.rw, tls_conf.is_some())?;
+        #[cfg(not(feature = "tls"))]
+        self.conn.greet(&mut self.rw, false)?;
+        self.flush().await?;
+
+        let mut auth_context = AuthenticationContext {
+            transport: self.conn.transport,
+            ..AuthenticationContext::default()
+        };
+        #[cfg(feature = "tls")]
+        let tls_certs;
+        {
+            let (_, handshake) = self.next().await?.ok_or_else(peer_terminated)?;
+            let handshake = parse_handshake(&handshake, false)?;
+
+            auth_context.username = handshake.username.map(|x| x.to_vec());
+            self.conn.accept(&handshake, &mut self.rw);
+
+            #[cfg(not(feature = "tls"))]
+            if handshake.capabilities.contains(CapabilityFlags::CLIENT_SSL) {
+                return Err(ssl_not_supported().into());
+            }
+
+            #[cfg(feature = "tls")]
+            if handshake.capabilities.contains(CapabilityFlags::CLIENT_SSL) {
+                let config = tls_conf.ok_or_else(ssl_not_supported)?;
+
+                let unread = self.rw.take_unread();
+                self.rw.get_mut().start_tls(config, &unread)?;
+
+                let (_, handshake) = self.next().await?.ok_or_else(peer_terminated)?;
+                let handshake = parse_handshake(&handshake, true)?;
+
+                auth_context.username = handshake.username.map(|x| x.to_vec());
+                self.conn.accept(&handshake, &mut self.rw);
+
+                auth_context.tls = true;
+                tls_certs = self.rw.get_mut().tls_certs();
+                auth_context.tls_client_certs = tls_certs.as_deref();
+            }
+        }
+
+        if let Err(e) = self.shim.after_authentication(&auth_context).await {
+            writers::write_err(
+                ErrorKind::ER_ACCESS_DENIED_ERROR,
+                "client authentication failed".as_ref(),
+                &mut self.rw,
+            )?;
+            self.flush().await?;
+            return Err(e);
+        }
+
+        writers::write_ok_packet(&mut self.rw, 0, 0, StatusFlags::empty())?;
+        self.flush().await?;
+        self.conn.apply_max_packet(&mut self.rw);
+
+        Ok(())
+    }
+
+    async fn run(mut self) -> Result<(), B::Error> {
+        use crate::commands::Command;
+
+        let mut stmts = HashMap::new();
+        loop {
+            // every command starts a new sequence
+            self.rw.set_seq(0);
+            if self.shutting_down() {
+                return Ok(self.say_goodbye().await?);
+            }
+            self.rw.set_deadline(self.conn.deadline("wait_timeout"))?;
+            let packet = match self.next().await? {
+                Some((_, p)) => p,
+                None if self.shutting_down() => return Ok(self.say_goodbye().await?),
+                None => break,
+            };
+            let cmd = commands::parse(&packet).unwrap().1;
+            match cmd {
+                Command::Query(q) => {
+                    let (attributes, q) = self.conn.query_attributes(q)?;
+                    let client_charset = self.conn.charset("character_set_client");
+                    if let Some(schema) = use_schema(q) {
+                        match client_charset.decode(schema) {
+                            Some(schema) => {
+                                let schema = trim_schema(&schema);
+                                let w = InitWriter {
+                                    writer: &mut self.rw,
+                                    schema: &mut self.conn.schema,
+                                    requested: schema,
+                                };
+                                self.shim.on_init(schema, w).await?;
+                            }
+                            None => invalid_string(client_charset, |k, m| {
+                                writers::write_err(k, m, &mut self.rw)
+                            })?,
+                        }
+                    } else {
+                        let results_charset = self.conn.charset("character_set_results");
+                        let w = QueryResultWriter::new(
+                            &mut self.rw,
+                            false,
+                            results_charset,
+                            self.conn.time_zone(),
+                            attributes,
+                        );
+                        let w = match client_charset.decode(q) {
+                            Some(text) => {
+                                let shim = &self.shim;
+                                let set_global = shim.allow_set_global();
+                                self.conn.intercept(&text, w, set_global, |b| {
+                                    shim.answer_bootstrap_query(b)
+                                })?
+                            }
+                            None => Some(w),
+                        };
+                        if let Some(w) = w {
+                            self.shim.on_query_bytes(q, client_charset, w).await?;
+                        }
+                    }
+                }
+                Command::Prepare(q) => {
+                    let charset = self.conn.charset("character_set_client");
+                    let w = StatementMetaWriter {
+                        writer: &mut self.rw,
+                        stmts: &mut stmts,
+                    };
+                    match charset.decode(q) {
+                        Some(q) => self.shim.on_prepare(&q, w).await?,
+                        None => invalid_string(charset, |k, m| w.error(k, m))?,
+                    }
+                }
+                Command::Execute {
+                    stmt,
+                    flags,
+                    params,
+                } => {
+                    let state = stmts
+                        .get_mut(&stmt)
+                        .ok_or_else(|| unknown_statement(stmt))?;
+                    {
+                        let (params, attributes) =
+                            self.conn.execute_params(params, flags, state)?;
+                        let charset = self.conn.charset("character_set_results");
+                        let w = QueryResultWriter::new(
+                            &mut self.rw,
+                            true,
+                            charset,
+                            self.conn.time_zone(),
+                            attributes,
+                        );
+                        self.shim.on_execute(stmt, params, w).await?;
+                    }
+                    state.long_data.clear();
+                }
+                Command::Close(stmt) => {
+                    self.shim.on_close(stmt).await;
+                    stmts.remove(&stmt);
+                    // NOTE: spec dictates no response from server
+                }
+                Command::Init(schema) => {
+                    let charset = self.conn.charset("character_set_client");
+                    match charset.decode(schema) {
+                        Some(schema) => {
+                            let w = InitWriter {
+                                writer: &mut self.rw,
+                                schema: &mut self.conn.schema,
+                                requested: &schema,
+                            };
+                            self.shim.on_init(&schema, w).await?;
+                        }
+                        None => {
+                            invalid_string(charset, |k, m| writers::write_err(k, m, &mut self.rw))?
+                        }
+                    }
+                }
+                Command::Quit => {
+                    break;
+                }
+                cmd => crate::handle_plain_command(cmd, &mut self.rw, &mut stmts)?,
+            }
+            self.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Read more bytes from the client, unless `timeout` completes or we are shut down first.
+async fn fill_until(
+    stream: &mut AsyncStream,
+    mut timeout: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    shutdown: Option<&Shutdown>,
+) -> io::Result<()> {
+    let mut fill = std::pin::pin!(stream.fill());
+    poll_fn(|cx| {
+        if let Poll::Ready(res) = fill.as_mut().poll(cx) {
+            return Poll::Ready(res);
+        }
+        if let Some(Poll::Ready(())) = timeout.as_mut().map(|t| t.as_mut().poll(cx)) {
+            return Poll::Ready(Ok(()));
+        }
+        match shutdown {
+            Some(shutdown) => shutdown.poll(cx).map(Ok),
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// Tells an intermediary to hang up, and wakes it if it is waiting for its client.
+struct Shutdown {
+    stop: Stop,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Shutdown {
+    fn register(handle: &ShutdownHandle) -> Self {
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let wake = Arc::clone(&waker);
+        let stop = handle.register(Box::new(move |_| {
+            if let Some(waker) = wake.lock().unwrap().take() {
+                waker.wake();
+            }
+        }));
+        Shutdown { stop, waker }
+    }
+
+    /// Completes once the handle is shut down.
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.stop.requested() {
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            // the handle may have looked for our waker before we stored it
+            if !self.stop.requested() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<B: AsyncMysqlShim> AsyncMysqlIntermediary<B> {
+    /// Create a new server over a tokio stream and process client commands until the client
+    /// disconnects or an error occurs. See also
+    /// [`AsyncMysqlIntermediary::run_on`](struct.AsyncMysqlIntermediary.html#method.run_on).
+    pub async fn run_on_tokio<S>(shim: B, stream: S) -> Result<(), B::Error>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+        let timer: Timer = |d| Box::pin(tokio::time::sleep(d));
+        AsyncMysqlIntermediary::run_with(shim, stream.compat(), Transport::Other, Some(timer)).await
+    }
+
+    /// Like [`run_on_tokio`](#method.run_on_tokio), but for a stream that is connected over a
+    /// Unix domain socket, such as a `tokio::net::UnixStream`.
+    pub async fn run_on_tokio_unix<S>(shim: B, stream: S) -> Result<(), B::Error>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+        let timer: Timer = |d| Box::pin(tokio::time::sleep(d));
+        AsyncMysqlIntermediary::run_with(shim, stream.compat(), Transport::Unix, Some(timer)).await
+    }
+}
@@ -0,0 +1,241 @@
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::resultset::QueryResultWriter;
+use crate::sql::{Parser, Token};
+use crate::vars;
+use crate::Column;
+use std::io::{self, Read, Write};
+
+/// A boilerplate query that connectors commonly issue right after connecting, and that the
+/// intermediary can answer on the shim's behalf.
+///
+/// See [`MysqlShim::answer_bootstrap_query`](trait.MysqlShim.html#method.answer_bootstrap_query).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapQuery {
+    /// `SET NAMES <charset> [COLLATE <collation>]`, possibly combined with other system variable
+    /// assignments.
+    SetNames,
+    /// A `SET` statement that only assigns system variables, like `SET autocommit=1`.
+    SetVariables,
+    /// `SHOW WARNINGS`.
+    ShowWarnings,
+    /// `SELECT DATABASE()` or `SELECT SCHEMA()`.
+    Database,
+    /// `SELECT CONNECTION_ID()`.
+    ConnectionId,
+    /// `SELECT VERSION()`.
+    Version,
+}
+
+/// Session state used to answer bootstrap queries.
+pub(crate) struct Session<'a> {
+    pub(crate) schema: Option<&'a str>,
+    pub(crate) connection_id: u32,
+    pub(crate) vars: &'a vars::SystemVariables,
+}
+
+/// A recognized bootstrap query, along with how its result should be presented.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Recognized {
+    pub(crate) query: BootstrapQuery,
+    column: String,
+    limit: Option<u64>,
+}
+
+impl Recognized {
+    fn new(query: BootstrapQuery, column: String, limit: Option<u64>) -> Self {
+        Recognized {
+            query,
+            column,
+            limit,
+        }
+    }
+}
+
+/// Recognize a connector bootstrap query.
+pub(crate) fn parse(q: &str) -> Option<Recognized> {
+    let mut p = Parser::new(q)?;
+    if p.keyword("SET") {
+        let query = if p.keyword("NAMES") {
+            BootstrapQuery::SetNames
+        } else {
+            BootstrapQuery::SetVariables
+        };
+        return match vars::parse(q) {
+            Some(vars::VarsQuery::Set(_)) => Some(Recognized::new(query, String::new(), None)),
+            _ => None,
+        };
+    }
+
+    if p.keyword("SHOW") {
+        if !p.keyword("WARNINGS") {
+            return None;
+        }
+        let limit = limit(&mut p)?;
+        return if p.at_end() {
+            Some(Recognized::new(
+                BootstrapQuery::ShowWarnings,
+                String::new(),
+                limit,
+            ))
+        } else {
+            None
+        };
+    }
+
+    if !p.keyword("SELECT") {
+        return None;
+    }
+    let (f, query) = match p.next()? {
+        Token::Word(f)
+            if f.eq_ignore_ascii_case("DATABASE") || f.eq_ignore_ascii_case("SCHEMA") =>
+        {
+            (f, BootstrapQuery::Database)
+        }
+        Token::Word(f) if f.eq_ignore_ascii_case("CONNECTION_ID") => {
+            (f, BootstrapQuery::ConnectionId)
+        }
+        Token::Word(f) if f.eq_ignore_ascii_case("VERSION") => (f, BootstrapQuery::Version),
+        _ => return None,
+    };
+    if !p.punct('(') || !p.punct(')') {
+        return None;
+    }
+    let column = if p.keyword("AS") {
+        match p.next()? {
+            Token::Word(w) => w.to_owned(),
+            Token::Ident(s) | Token::Str(s) => s,
+            _ => return None,
+        }
+    } else {
+        match p.peek() {
+            Some(Token::Word(w)) if !w.eq_ignore_ascii_case("LIMIT") => p.ident()?,
+            Some(Token::Ident(_)) => p.ident()?,
+            _ => format!("{}()", f),
+        }
+    };
+    let limit = limit(&mut p)?;
+    if p.at_end() {
+        Some(Recognized::new(query, column, limit))
+    } else {
+        None
+    }
+}
+
+fn limit(p: &mut Parser<'_>) -> Option<Option<u64>> {
+    if p.keyword("LIMIT") {
+        match p.next()? {
+            Token::Number(n) => Some(Some(n.parse().ok()?)),
+            _ => None,
+        }
+    } else {
+        Some(None)
+    }
+}
+
+/// Answer a bootstrap query recognized by [`parse`].
+pub(crate) fn answer<W: Read + Write>(
+    q: Recognized,
+    session: Session<'_>,
+    results: QueryResultWriter<'_, W>,
+) -> io::Result<()> {
+    let Recognized {
+        query,
+        column,
+        limit,
+    } = q;
+    match query {
+        BootstrapQuery::SetNames | BootstrapQuery::SetVariables => results.completed(0, 0),
+        BootstrapQuery::ShowWarnings => {
+            let cols = [
+                Column {
+                    colflags: ColumnFlags::NOT_NULL_FLAG,
+                    ..Column::new("Level", ColumnType::MYSQL_TYPE_VAR_STRING)
+                },
+                Column {
+                    colflags: ColumnFlags::NOT_NULL_FLAG | ColumnFlags::UNSIGNED_FLAG,
+                    ..Column::new("Code", ColumnType::MYSQL_TYPE_LONG)
+                },
+                Column {
+                    colflags: ColumnFlags::NOT_NULL_FLAG,
+                    ..Column::new("Message", ColumnType::MYSQL_TYPE_VAR_STRING)
+                },
+            ];
+            let w = results.start(&cols)?;
+            w.finish()
+        }
+        BootstrapQuery::Database => {
+            let cols = [Column::new(column, ColumnType::MYSQL_TYPE_VAR_STRING)];
+            let mut w = results.start(&cols)?;
+            if limit != Some(0) {
+                w.write_col(session.schema)?;
+                w.end_row()?;
+            }
+            w.finish()
+        }
+        BootstrapQuery::ConnectionId => {
+            let cols = [Column {
+                colflags: ColumnFlags::NOT_NULL_FLAG | ColumnFlags::UNSIGNED_FLAG,
+                ..Column::new(column, ColumnType::MYSQL_TYPE_LONGLONG)
+            }];
+            let mut w = results.start(&cols)?;
+            if limit != Some(0) {
+                w.write_col(u64::from(session.connection_id))?;
+                w.end_row()?;
+            }
+            w.finish()
+        }
+        BootstrapQuery::Version => {
+            let cols = [Column::new(column, ColumnType::MYSQL_TYPE_VAR_STRING)];
+            let mut w = results.start(&cols)?;
+            if limit != Some(0) {
+                w.write_col(session.vars.get("version"))?;
+                w.end_row()?;
+            }
+            w.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_recognizes_bootstrap_queries() {
+        assert_eq!(
+            parse("SET NAMES utf8mb4").map(|r| r.query),
+            Some(BootstrapQuery::SetNames)
+        );
+        assert_eq!(
+            parse("set autocommit=1").map(|r| r.query),
+            Some(BootstrapQuery::SetVariables)
+        );
+        assert_eq!(
+            parse("/* ping */ SHOW WARNINGS LIMIT 10"),
+            Some(Recognized::new(
+                BootstrapQuery::ShowWarnings,
+                String::new(),
+                Some(10)
+            ))
+        );
+        assert_eq!(
+            parse("select database() limit 1;"),
+            Some(Recognized::new(
+                BootstrapQuery::Database,
+                "database()".to_owned(),
+                Some(1)
+            ))
+        );
+        assert_eq!(
+            parse("SELECT CONNECTION_ID() AS id"),
+            Some(Recognized::new(
+                BootstrapQuery::ConnectionId,
+                "id".to_owned(),
+                None
+            ))
+        );
+        assert_eq!(parse("SELECT DATABASE(), 1"), None);
+        assert_eq!(parse("SET @a = 1"), None);
+        assert_eq!(parse("SHOW TABLES"), None);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::myc::constants::{ColumnFlags, ColumnType, UTF8_GENERAL_CI};
+use crate::myc::io::WriteMysqlExt;
+use crate::Column;
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// A character set that the intermediary can transcode query text and results to and from.
+///
+/// The client's character set is negotiated during the handshake, and can later be changed with
+/// `SET NAMES` or by assigning `character_set_client` and `character_set_results`. Character sets
+/// that are not listed here are treated as UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    /// `utf8mb4`, `utf8mb3`, and `utf8`.
+    #[default]
+    Utf8,
+    /// `latin1`, which MySQL defines as Windows-1252.
+    Latin1,
+    /// `cp1251` (Windows Cyrillic).
+    Cp1251,
+    /// `ascii`.
+    Ascii,
+    /// `binary`; text is passed through as bytes.
+    Binary,
+}
+
+// 0x80..=0x9F; the five bytes that Windows-1252 leaves undefined map to the C1 controls like
+// MySQL does.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+// 0x80..=0xBF; 0xC0..=0xFF map to U+0410..=U+044F.
+const CP1251_HIGH: [char; 64] = [
+    '\u{0402}', '\u{0403}', '\u{201A}', '\u{0453}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{20AC}', '\u{2030}', '\u{0409}', '\u{2039}', '\u{040A}', '\u{040C}', '\u{040B}', '\u{040F}',
+    '\u{0452}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{0098}', '\u{2122}', '\u{0459}', '\u{203A}', '\u{045A}', '\u{045C}', '\u{045B}', '\u{045F}',
+    '\u{00A0}', '\u{040E}', '\u{045E}', '\u{0408}', '\u{00A4}', '\u{0490}', '\u{00A6}', '\u{00A7}',
+    '\u{0401}', '\u{00A9}', '\u{0404}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{0407}',
+    '\u{00B0}', '\u{00B1}', '\u{0406}', '\u{0456}', '\u{0491}', '\u{00B5}', '\u{00B6}', '\u{00B7}',
+    '\u{0451}', '\u{2116}', '\u{0454}', '\u{00BB}', '\u{0458}', '\u{0405}', '\u{0455}', '\u{0457}',
+];
+
+impl Charset {
+    /// Look up a character set by its MySQL name (case-insensitively).
+    pub fn from_name(name: &str) -> Option<Charset> {
+        match &*name.to_ascii_lowercase() {
+            "utf8" | "utf8mb3" | "utf8mb4" => Some(Charset::Utf8),
+            "latin1" => Some(Charset::Latin1),
+            "cp1251" => Some(Charset::Cp1251),
+            "ascii" => Some(Charset::Ascii),
+            "binary" => Some(Charset::Binary),
+            _ => None,
+        }
+    }
+
+    /// The MySQL name of this character set.
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf8mb4",
+            Charset::Latin1 => "latin1",
+            Charset::Cp1251 => "cp1251",
+            Charset::Ascii => "ascii",
+            Charset::Binary => "binary",
+        }
+    }
+
+    /// The collation id reported in column definitions for text in this character set.
+    pub(crate) fn collation(self) -> u16 {
+        match self {
+            Charset::Utf8 => UTF8_GENERAL_CI,
+            Charset::Latin1 => 8,
+            Charset::Cp1251 => 51,
+            Charset::Ascii => 11,
+            Charset::Binary => 63,
+        }
+    }
+
+    /// Decode `bytes` in this character set.
+    ///
+    /// Returns `None` if `bytes` is not valid in this character set. `binary` text is decoded as
+    /// UTF-8.
+    pub fn decode(self, bytes: &[u8]) -> Option<Cow<'_, str>> {
+        match self {
+            Charset::Utf8 | Charset::Binary => std::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+            Charset::Ascii if bytes.is_ascii() => {
+                Some(Cow::Borrowed(std::str::from_utf8(bytes).unwrap()))
+            }
+            Charset::Ascii => None,
+            _ if bytes.is_ascii() => Some(Cow::Borrowed(std::str::from_utf8(bytes).unwrap())),
+            Charset::Latin1 => Some(Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| match b {
+                        0x80..=0x9F => CP1252_HIGH[usize::from(b - 0x80)],
+                        _ => char::from(b),
+                    })
+                    .collect(),
+            )),
+            Charset::Cp1251 => Some(Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| match b {
+                        0x80..=0xBF => CP1251_HIGH[usize::from(b - 0x80)],
+                        0xC0..=0xFF => char::from_u32(0x0410 + u32::from(b - 0xC0)).unwrap(),
+                        _ => char::from(b),
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Encode `s` in this character set, replacing characters that cannot be represented with
+    /// `?` like MySQL does.
+    pub fn encode(self, s: &str) -> Cow<'_, [u8]> {
+        if self == Charset::Utf8 || self == Charset::Binary || s.is_ascii() {
+            return Cow::Borrowed(s.as_bytes());
+        }
+        Cow::Owned(
+            s.chars()
+                .map(|c| match self {
+                    Charset::Latin1 => match c {
+                        '\0'..='\u{7F}' | '\u{A0}'..='\u{FF}' => c as u8,
+                        _ => CP1252_HIGH
+                            .iter()
+                            .position(|&h| h == c)
+                            .map(|i| 0x80 + i as u8)
+                            .unwrap_or(b'?'),
+                    },
+                    Charset::Cp1251 => match c {
+                        '\0'..='\u{7F}' => c as u8,
+                        '\u{0410}'..='\u{044F}' => (c as u32 - 0x0410 + 0xC0) as u8,
+                        _ => CP1251_HIGH
+                            .iter()
+                            .position(|&h| h == c)
+                            .map(|i| 0x80 + i as u8)
+                            .unwrap_or(b'?'),
+                    },
+                    _ if c.is_ascii() => c as u8,
+                    _ => b'?',
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The MySQL name of the character set of the collation with the given id, if we know it.
+pub(crate) fn collation_charset_name(collation: u16) -> Option<&'static str> {
+    Some(match collation {
+        5 | 8 | 15 | 31 | 47 | 48 | 49 | 94 => "latin1",
+        14 | 23 | 50 | 51 | 52 => "cp1251",
+        11 | 65 => "ascii",
+        63 => "binary",
+        33 | 76 | 83 | 192..=215 | 223 => "utf8",
+        45 | 46 | 224..=247 | 255..=323 => "utf8mb4",
+        _ => return None,
+    })
+}
+
+/// True for columns whose values are transcoded to the results character set.
+///
+/// BLOB-typed columns are left alone since they may well hold binary data, as are `BINARY` and
+/// `VARBINARY` columns (string columns with the `BINARY_FLAG`).
+pub(crate) fn is_text_column(c: &Column) -> bool {
+    matches!(
+        c.coltype,
+        ColumnType::MYSQL_TYPE_STRING
+            | ColumnType::MYSQL_TYPE_VAR_STRING
+            | ColumnType::MYSQL_TYPE_VARCHAR
+            | ColumnType::MYSQL_TYPE_ENUM
+            | ColumnType::MYSQL_TYPE_SET
+    ) && !c.colflags.contains(ColumnFlags::BINARY_FLAG)
+}
+
+/// Re-encode a length-encoded UTF-8 string (as written by a `ToMysqlValue` impl) in `charset`.
+///
+/// Text-protocol `NULL`s and strings that are not valid UTF-8 are written out unchanged.
+pub(crate) fn transcode_lenenc<W: Write>(
+    charset: Charset,
+    encoded: &[u8],
+    w: &mut W,
+) -> io::Result<()> {
+    use crate::myc::io::ReadMysqlExt;
+
+    if encoded.first() == Some(&0xFB) {
+        return w.write_all(encoded);
+    }
+    let mut rest = encoded;
+    match rest.read_lenenc_str().map(String::from_utf8) {
+        Ok(Ok(s)) if rest.is_empty() => w.write_lenenc_str(&charset.encode(&s)).map(|_| ()),
+        _ => w.write_all(encoded),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_roundtrips_latin1() {
+        let s = "caf\u{e9} \u{20ac}5";
+        let encoded = Charset::Latin1.encode(s);
+        assert_eq!(&*encoded, b"caf\xe9 \x805");
+        assert_eq!(Charset::Latin1.decode(&encoded).unwrap(), s);
+        assert_eq!(&*Charset::Latin1.encode("\u{444}"), b"?");
+    }
+
+    #[test]
+    fn it_roundtrips_cp1251() {
+        let s = "\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442} \u{451}";
+        let encoded = Charset::Cp1251.encode(s);
+        assert_eq!(&*encoded, b"\xcf\xf0\xe8\xe2\xe5\xf2 \xb8");
+        assert_eq!(Charset::Cp1251.decode(&encoded).unwrap(), s);
+    }
+
+    #[test]
+    fn it_rejects_invalid_text() {
+        assert_eq!(Charset::Utf8.decode(b"_binary'\xff'"), None);
+        assert_eq!(Charset::Ascii.decode(b"\xe9"), None);
+    }
+
+    #[test]
+    fn it_maps_collations() {
+        assert_eq!(collation_charset_name(8), Some("latin1"));
+        assert_eq!(collation_charset_name(45), Some("utf8mb4"));
+        assert_eq!(collation_charset_name(1000), None);
+        assert_eq!(Charset::from_name("UTF8MB4"), Some(Charset::Utf8));
+    }
+
+    #[test]
+    fn it_transcodes_lenenc_strings() {
+        let mut out = Vec::new();
+        transcode_lenenc(Charset::Latin1, b"\x02\xc3\xa9", &mut out).unwrap();
+        assert_eq!(out, b"\x01\xe9");
+        out.clear();
+        transcode_lenenc(Charset::Latin1, b"\xfb", &mut out).unwrap();
+        assert_eq!(out, b"\xfb");
+    }
+}
@@ -1,11 +1,18 @@
 use crate::myc::constants::{CapabilityFlags, Command as CommandByte};
 
+/// MariaDB extended capability: the client understands progress report packets.
+pub(crate) const MARIADB_CLIENT_PROGRESS: u32 = 1;
+/// MariaDB extended capability: the client understands extended type information in column
+/// definitions.
+pub(crate) const MARIADB_CLIENT_EXTENDED_TYPE_INFO: u32 = 1 << 3;
+
 #[derive(Debug)]
-#[allow(dead_code)] // The fields here are read, but only in tests. This keeps clippy quiet.
 pub struct ClientHandshake<'a> {
     pub capabilities: CapabilityFlags,
-    maxps: u32,
-    collation: u16,
+    pub(crate) maxps: u32,
+    pub(crate) collation: u16,
+    /// The MariaDB extended capabilities; always empty for MySQL clients.
+    pub(crate) mariadb_capabilities: u32,
     pub(crate) username: Option<&'a [u8]>,
 }
 
@@ -25,7 +32,15 @@ pub fn client_handshake(i: &[u8], after_tls: bool) -> nom::IResult<&[u8], Client
 
         let (i, maxps) = nom::number::complete::le_u32(i)?;
         let (i, collation) = nom::bytes::complete::take(1u8)(i)?;
-        let (i, _) = nom::bytes::complete::take(23u8)(i)?;
+        let (i, _) = nom::bytes::complete::take(19u8)(i)?;
+        // MariaDB clients put their extended capabilities in the last four filler bytes, unless
+        // they set CLIENT_LONG_PASSWORD (which MariaDB calls CLIENT_MYSQL).
+        let (i, mariadb_capabilities) = nom::number::complete::le_u32(i)?;
+        let mariadb_capabilities = if capabilities.contains(CapabilityFlags::CLIENT_LONG_PASSWORD) {
+            0
+        } else {
+            mariadb_capabilities
+        };
 
         let (i, username) = if after_tls || !capabilities.contains(CapabilityFlags::CLIENT_SSL) {
             let (i, user) = nom::bytes::complete::take_until(&b"\0"[..])(i)?;
@@ -41,6 +56,7 @@ pub fn client_handshake(i: &[u8], after_tls: bool) -> nom::IResult<&[u8], Client
                 capabilities,
                 maxps,
                 collation: u16::from(collation[0]),
+                mariadb_capabilities,
                 username,
             },
         ))
@@ -57,6 +73,7 @@ pub fn client_handshake(i: &[u8], after_tls: bool) -> nom::IResult<&[u8], Client
                 capabilities: CapabilityFlags::from_bits_truncate(cap as u32),
                 maxps,
                 collation: 0,
+                mariadb_capabilities: 0,
                 username: Some(username),
             },
         ))
@@ -72,6 +89,7 @@ pub enum Command<'a> {
     Init(&'a [u8]),
     Execute {
         stmt: u32,
+        flags: u8,
         params: &'a [u8],
     },
     SendLongData {
@@ -85,9 +103,16 @@ pub enum Command<'a> {
 
 pub fn execute(i: &[u8]) -> nom::IResult<&[u8], Command<'_>> {
     let (i, stmt) = nom::number::complete::le_u32(i)?;
-    let (i, _flags) = nom::bytes::complete::take(1u8)(i)?;
+    let (i, flags) = nom::number::complete::le_u8(i)?;
     let (i, _iterations) = nom::number::complete::le_u32(i)?;
-    Ok((&[], Command::Execute { stmt, params: i }))
+    Ok((
+        &[],
+        Command::Execute {
+            stmt,
+            flags,
+            params: i,
+        },
+    ))
 }
 
 pub fn send_long_data(i: &[u8]) -> nom::IResult<&[u8], Command<'_>> {
@@ -158,6 +183,8 @@ mod tests {
         .to_vec();
         let r = Cursor::new(data);
         let mut pr = PacketConn::new(r);
+        // the handshake response follows the server's greeting
+        pr.set_seq(1);
         let (_, p) = pr.next().unwrap().unwrap();
         let (_, handshake) = client_handshake(&p, false).unwrap();
         println!("{:?}", handshake);
@@ -176,6 +203,28 @@ mod tests {
         assert_eq!(handshake.collation, UTF8_GENERAL_CI);
         assert_eq!(handshake.username.unwrap(), &b"jon"[..]);
         assert_eq!(handshake.maxps, 16777216);
+        assert_eq!(handshake.mariadb_capabilities, 0);
+    }
+
+    #[test]
+    fn it_parses_mariadb_handshake() {
+        let data = [
+            0x25, 0x00, 0x00, 0x01, 0x84, 0xa6, 0x3f, 0x20, 0x00, 0x00, 0x00, 0x01, 0x21, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x6a, 0x6f, 0x6e, 0x00, 0x00,
+        ]
+        .to_vec();
+        let r = Cursor::new(data);
+        let mut pr = PacketConn::new(r);
+        // the handshake response follows the server's greeting
+        pr.set_seq(1);
+        let (_, p) = pr.next().unwrap().unwrap();
+        let (_, handshake) = client_handshake(&p, false).unwrap();
+        assert_eq!(
+            handshake.mariadb_capabilities,
+            MARIADB_CLIENT_PROGRESS | MARIADB_CLIENT_EXTENDED_TYPE_INFO
+        );
+        assert_eq!(handshake.username.unwrap(), &b"jon"[..]);
     }
 
     #[test]
@@ -188,6 +237,8 @@ mod tests {
         .to_vec();
         let r = Cursor::new(data);
         let mut pr = PacketConn::new(r);
+        // the handshake response follows the server's greeting
+        pr.set_seq(1);
         let (_, p) = pr.next().unwrap().unwrap();
         let (_, handshake) = client_handshake(&p, false).unwrap();
         println!("{:?}", handshake);
@@ -219,6 +270,8 @@ mod tests {
         .to_vec();
         let r = Cursor::new(data);
         let mut pr = PacketConn::new(r);
+        // the handshake response follows the server's greeting
+        pr.set_seq(1);
         let (_, p) = pr.next().unwrap().unwrap();
         let (_, handshake) = client_handshake(&p, true).unwrap();
         println!("{:?}", handshake);
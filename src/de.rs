@@ -0,0 +1,655 @@
+//! Deserializing the parameters of an `EXECUTE` command with serde.
+
+use crate::{FromValueError, OwnedValue, ParamParser, ParamValue, Value, ValueInner};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::vec;
+
+/// An error deserializing the parameters of an `EXECUTE` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeError {
+    index: Option<usize>,
+    name: Option<String>,
+    message: String,
+}
+
+impl DeserializeError {
+    /// The position of the parameter that could not be deserialized, if the error is about one.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The name of the parameter that could not be deserialized, if the client named it.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn at(mut self, index: usize, name: Option<&str>) -> Self {
+        if self.index.is_none() {
+            self.index = Some(index);
+            self.name = name.map(str::to_owned);
+        }
+        self
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.index, &self.name) {
+            (Some(i), Some(name)) => write!(f, "parameter {} (`{}`): {}", i, name, self.message),
+            (Some(i), None) => write!(f, "parameter {}: {}", i, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeserializeError {
+            index: None,
+            name: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl From<FromValueError> for DeserializeError {
+    fn from(e: FromValueError) -> Self {
+        de::Error::custom(e)
+    }
+}
+
+impl From<DeserializeError> for io::Error {
+    fn from(e: DeserializeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+impl<'a> ParamParser<'a> {
+    /// Deserialize the parameters into a tuple or a struct.
+    ///
+    /// Parameters are mapped onto the fields of `T` in order, or by name if the client named
+    /// them, which clients that send query attributes may do. Parameters are converted into each
+    /// field's type the same way as with `TryFrom<Value>`, so for example integers must fit, and
+    /// numbers can be parsed from strings. Dates and times can be read as strings, in the format
+    /// MySQL uses for them.
+    ///
+    /// ```no_run
+    /// # use msql_srv::ParamParser;
+    /// #[derive(serde::Deserialize)]
+    /// struct NewUser<'a> {
+    ///     id: u32,
+    ///     name: &'a str,
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// # fn f(params: ParamParser<'_>) -> Result<(), msql_srv::DeserializeError> {
+    /// let user: NewUser<'_> = params.deserialize()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deserialize<T: de::Deserialize<'a>>(self) -> Result<T, DeserializeError> {
+        let names = self.names.clone();
+        let params: Vec<_> = self.into_iter().collect();
+        let names = names
+            .iter()
+            .map(|name| std::str::from_utf8(name).ok().filter(|n| !n.is_empty()))
+            .collect::<Option<Vec<_>>>()
+            .filter(|names| names.len() == params.len());
+        T::deserialize(ParamsDeserializer { params, names })
+    }
+}
+
+struct ParamsDeserializer<'a> {
+    params: Vec<ParamValue<'a>>,
+    names: Option<Vec<&'a str>>,
+}
+
+struct ParamAccess<'a> {
+    params: std::iter::Enumerate<vec::IntoIter<ParamValue<'a>>>,
+    names: vec::IntoIter<&'a str>,
+    len: usize,
+    // the parameter whose name was just read as a map key
+    value: Option<(usize, Option<&'a str>, Value<'a>)>,
+}
+
+impl<'a> ParamAccess<'a> {
+    fn new(de: ParamsDeserializer<'a>) -> Self {
+        ParamAccess {
+            len: de.params.len(),
+            params: de.params.into_iter().enumerate(),
+            names: de.names.unwrap_or_default().into_iter(),
+            value: None,
+        }
+    }
+
+    fn next(&mut self) -> Option<(usize, Option<&'a str>, Value<'a>)> {
+        self.params
+            .next()
+            .map(|(i, p)| (i, self.names.next(), p.value))
+    }
+
+    fn end(mut self) -> Result<(), DeserializeError> {
+        match self.params.next() {
+            Some((i, _)) => Err(de::Error::custom(format!(
+                "got {} parameters, but expected only {}",
+                self.len, i
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ParamsDeserializer<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut params = ParamAccess::new(self);
+        let v = visitor.visit_seq(&mut params)?;
+        params.end()?;
+        Ok(v)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.names.is_none() {
+            return Err(de::Error::custom("the parameters have no names"));
+        }
+        visitor.visit_map(ParamAccess::new(self))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.names.is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct enum identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for ParamAccess<'de> {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.next() {
+            Some((i, name, value)) => seed
+                .deserialize(ValueDeserializer(value))
+                .map(Some)
+                .map_err(|e| e.at(i, name)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.params.len())
+    }
+}
+
+impl<'de> MapAccess<'de> for ParamAccess<'de> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        self.value = self.next();
+        match self.value {
+            Some((_, Some(name), _)) => seed
+                .deserialize(BorrowedStrDeserializer::new(name))
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (i, name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <DeserializeError as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|e| e.at(i, name))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.params.len())
+    }
+}
+
+/// Deserializes a single parameter.
+struct ValueDeserializer<'a>(Value<'a>);
+
+impl<'a> ValueDeserializer<'a> {
+    /// The text of a parameter that is not a string, such as a number or a date.
+    fn to_text(&self) -> Option<String> {
+        match self.0.into_inner() {
+            ValueInner::Int(i) => Some(i.to_string()),
+            ValueInner::UInt(u) => Some(u.to_string()),
+            ValueInner::Double(f) => Some(f.to_string()),
+            ValueInner::Date(_) => match self.0.to_owned() {
+                OwnedValue::Date(y, mo, d, ..) => Some(format!("{:04}-{:02}-{:02}", y, mo, d)),
+                _ => None,
+            },
+            ValueInner::Datetime(_) => match self.0.to_owned() {
+                OwnedValue::Date(y, mo, d, h, mi, s, us) => {
+                    let mut t = format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, mo, d, h, mi, s);
+                    if us != 0 {
+                        t.push_str(&format!(".{:06}", us));
+                    }
+                    Some(t)
+                }
+                _ => None,
+            },
+            ValueInner::Time(_) => crate::Duration::try_from(self.0)
+                .ok()
+                .map(|d| d.to_string()),
+            ValueInner::NULL | ValueInner::Bytes(_) => None,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0.into_inner() {
+            ValueInner::NULL => visitor.visit_none(),
+            ValueInner::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            ValueInner::Int(i) => visitor.visit_i64(i),
+            ValueInner::UInt(u) => visitor.visit_u64(u),
+            ValueInner::Double(f) => visitor.visit_f64(f),
+            _ => self.deserialize_string(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let b = match self.0.into_inner() {
+            ValueInner::Bytes(b) if b.eq_ignore_ascii_case(b"true") => Some(true),
+            ValueInner::Bytes(b) if b.eq_ignore_ascii_case(b"false") => Some(false),
+            _ => match i64::try_from(self.0) {
+                Ok(0) => Some(false),
+                Ok(1) => Some(true),
+                _ => None,
+            },
+        };
+        match b {
+            Some(b) => visitor.visit_bool(b),
+            None => Err(FromValueError::new(self.0.into_inner(), "bool").into()),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_i8(i8::try_from(self.0)?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_i16(i16::try_from(self.0)?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_i32(i32::try_from(self.0)?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_i64(i64::try_from(self.0)?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u8(u8::try_from(self.0)?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u16(u16::try_from(self.0)?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u32(u32::try_from(self.0)?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(u64::try_from(self.0)?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f32(f32::try_from(self.0)?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f64(f64::try_from(self.0)?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let s = <&str>::try_from(self.0).map_err(|e| e.for_target("char"))?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(FromValueError::new(self.0.into_inner(), "char")
+                .because("not a single character")
+                .into()),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.to_text() {
+            Some(s) => visitor.visit_string(s),
+            None => visitor.visit_borrowed_str(<&str>::try_from(self.0)?),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_bytes(<&[u8]>::try_from(self.0)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_null() {
+            visitor.visit_unit()
+        } else {
+            Err(FromValueError::new(self.0.into_inner(), "()").into())
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Enums can be read from strings naming one of their unit variants.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.to_text() {
+            Some(s) => visitor.visit_enum(s.into_deserializer()),
+            None => visitor.visit_enum(BorrowedStrDeserializer::new(<&str>::try_from(self.0)?)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 seq tuple tuple_struct map struct identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc;
+    use crate::myc::constants::ColumnType;
+    use crate::StatementData;
+    use chrono::FixedOffset;
+    use myc::io::WriteMysqlExt;
+    use myc::proto::MySerialize;
+    use serde::Deserialize;
+
+    /// Build an `EXECUTE` payload for `params`, which are named if any of them has a name, and
+    /// deserialize it into a `T`.
+    fn deserialize<T>(params: &[(&str, myc::value::Value)]) -> Result<T, DeserializeError>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let named = params.iter().any(|(name, _)| !name.is_empty());
+        let mut payload = vec![0; (params.len() + 7) / 8];
+        payload.push(1);
+        let mut values = Vec::new();
+        for (i, (name, v)) in params.iter().enumerate() {
+            let (ct, unsigned) = match v {
+                myc::value::Value::NULL => (ColumnType::MYSQL_TYPE_NULL, false),
+                myc::value::Value::Bytes(_) => (ColumnType::MYSQL_TYPE_VAR_STRING, false),
+                myc::value::Value::Int(_) => (ColumnType::MYSQL_TYPE_LONGLONG, false),
+                myc::value::Value::UInt(_) => (ColumnType::MYSQL_TYPE_LONGLONG, true),
+                myc::value::Value::Float(_) => (ColumnType::MYSQL_TYPE_FLOAT, false),
+                myc::value::Value::Double(_) => (ColumnType::MYSQL_TYPE_DOUBLE, false),
+                myc::value::Value::Date(..) => (ColumnType::MYSQL_TYPE_DATETIME, false),
+                myc::value::Value::Time(..) => (ColumnType::MYSQL_TYPE_TIME, false),
+            };
+            payload.push(ct as u8);
+            payload.push(if unsigned { 0x80 } else { 0 });
+            if named {
+                payload.write_lenenc_str(name.as_bytes()).unwrap();
+            }
+            if let myc::value::Value::NULL = v {
+                payload[i / 8] |= 1 << (i % 8);
+            } else {
+                v.serialize(&mut values);
+            }
+        }
+        payload.extend(values);
+
+        let mut stmt = StatementData {
+            params: params.len() as u16,
+            ..Default::default()
+        };
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let (params, _) = ParamParser::new(&payload, 0, named, &mut stmt, tz).unwrap();
+        params.deserialize()
+    }
+
+    fn bytes(s: &str) -> myc::value::Value {
+        myc::value::Value::Bytes(s.as_bytes().to_vec())
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        id: u32,
+        name: String,
+        email: Option<String>,
+    }
+
+    #[test]
+    fn maps_tuples_in_order() {
+        let v: (u8, String, Option<i64>, Option<u8>, f64) = deserialize(&[
+            ("", myc::value::Value::Int(7)),
+            ("", bytes("seven")),
+            ("", myc::value::Value::UInt(7)),
+            ("", myc::value::Value::NULL),
+            ("", myc::value::Value::Double(0.5)),
+        ])
+        .unwrap();
+        assert_eq!(v, (7, "seven".to_owned(), Some(7), None, 0.5));
+    }
+
+    #[test]
+    fn maps_structs_in_order() {
+        let user: User = deserialize(&[
+            ("", myc::value::Value::Int(1)),
+            ("", bytes("jane")),
+            ("", myc::value::Value::NULL),
+        ])
+        .unwrap();
+        assert_eq!(
+            user,
+            User {
+                id: 1,
+                name: "jane".to_owned(),
+                email: None,
+            }
+        );
+    }
+
+    #[test]
+    fn maps_structs_by_name() {
+        let user: User = deserialize(&[
+            ("email", bytes("jane@example.com")),
+            ("name", bytes("jane")),
+            ("id", myc::value::Value::Int(1)),
+        ])
+        .unwrap();
+        assert_eq!(user.id, 1);
+        assert_eq!(user.name, "jane");
+        assert_eq!(user.email.as_deref(), Some("jane@example.com"));
+
+        let e = deserialize::<User>(&[("name", bytes("jane")), ("id", myc::value::Value::Int(-1))])
+            .unwrap_err();
+        assert_eq!((e.index(), e.name()), (Some(1), Some("id")));
+        assert_eq!(
+            e.to_string(),
+            "parameter 1 (`id`): cannot convert Int(-1) to u32: out of range"
+        );
+    }
+
+    #[test]
+    fn coerces_types() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Color {
+            Red,
+            Green,
+        }
+
+        let v: (i32, String, bool, bool, Color, String, String) = deserialize(&[
+            ("", bytes(" 42 ")),
+            ("", myc::value::Value::Int(-3)),
+            ("", myc::value::Value::Int(1)),
+            ("", bytes("false")),
+            ("", bytes("green")),
+            ("", myc::value::Value::Date(2024, 2, 29, 13, 14, 15, 16)),
+            ("", myc::value::Value::Time(true, 1, 2, 3, 4, 0)),
+        ])
+        .unwrap();
+        assert_eq!(
+            v,
+            (
+                42,
+                "-3".to_owned(),
+                true,
+                false,
+                Color::Green,
+                "2024-02-29 13:14:15.000016".to_owned(),
+                "-26:03:04".to_owned(),
+            )
+        );
+        assert!(deserialize::<(Color,)>(&[("", bytes("blue"))]).is_err());
+        assert!(deserialize::<(Color,)>(&[("", bytes("Red"))]).is_err());
+    }
+
+    #[test]
+    fn borrows_from_the_packet() {
+        #[derive(Deserialize)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            data: &'a [u8],
+        }
+
+        let payload = b"\x00\x01\xfd\x00\xfc\x00\x04jane\x02\xff\x00";
+        let mut stmt = StatementData {
+            params: 2,
+            ..Default::default()
+        };
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let (params, _) = ParamParser::new(&payload[..], 0, false, &mut stmt, tz).unwrap();
+        let v: Borrowed<'_> = params.deserialize().unwrap();
+        assert_eq!(v.name, "jane");
+        assert_eq!(v.data, b"\xff\x00");
+    }
+
+    #[test]
+    fn reports_the_failing_parameter() {
+        let e = deserialize::<(u8, u8)>(&[("", myc::value::Value::Int(1)), ("", bytes("300"))])
+            .unwrap_err();
+        assert_eq!((e.index(), e.name()), (Some(1), None));
+        assert!(e
+            .to_string()
+            .starts_with("parameter 1: cannot convert Bytes(\"300\") to u8: "));
+
+        let e = deserialize::<(String,)>(&[("", myc::value::Value::NULL)]).unwrap_err();
+        assert_eq!(e.to_string(), "parameter 0: cannot convert NULL to &str");
+
+        let e = deserialize::<(u8,)>(&[
+            ("", myc::value::Value::Int(1)),
+            ("", myc::value::Value::Int(2)),
+        ])
+        .unwrap_err();
+        assert_eq!(e.index(), None);
+        assert_eq!(e.to_string(), "got 2 parameters, but expected only 1");
+        assert!(deserialize::<(u8, u8)>(&[("", myc::value::Value::Int(1))]).is_err());
+        assert!(deserialize::<std::collections::HashMap<String, u8>>(&[(
+            "",
+            myc::value::Value::Int(1)
+        )])
+        .is_err());
+    }
+}
@@ -45,15 +45,11 @@
 //!         let cols = [
 //!             Column {
 //!                 table: "foo".to_string(),
-//!                 column: "a".to_string(),
-//!                 coltype: ColumnType::MYSQL_TYPE_LONGLONG,
-//!                 colflags: ColumnFlags::empty(),
+//!                 ..Column::new("a", ColumnType::MYSQL_TYPE_LONGLONG)
 //!             },
 //!             Column {
 //!                 table: "foo".to_string(),
-//!                 column: "b".to_string(),
-//!                 coltype: ColumnType::MYSQL_TYPE_STRING,
-//!                 colflags: ColumnFlags::empty(),
+//!                 ..Column::new("b", ColumnType::MYSQL_TYPE_STRING)
 //!             },
 //!         ];
 //!
@@ -81,6 +77,24 @@
 //!     jh.join().unwrap();
 //! }
 //! ```
+//!
+//! With the `async` feature enabled, backends that are themselves async can instead implement
+//! `AsyncMysqlShim`, and serve clients with `AsyncMysqlIntermediary` on any stream that implements
+//! the `futures` `AsyncRead` and `AsyncWrite` traits, without dedicating a thread to each
+//! connection. The `tokio` feature adds support for tokio's streams.
+//!
+//! The `rust_decimal` and `bigdecimal` features let exact decimals be written to `DECIMAL`
+//! columns, rounded to the column's [`decimals`](Column::decimals), and decoded from parameters
+//! with `TryFrom<Value>`. Similarly, the `serde_json` feature adds support for `JSON` columns
+//! and parameters, either as `serde_json::Value`s or, through the `Json` wrapper, as any type
+//! that implements `Serialize` or `Deserialize`. With the `uuid` feature, `Uuid`s are written as
+//! 16 raw bytes to `BINARY` columns and as text otherwise, and decoded from either form. The
+//! `time` feature adds the `time` crate's dates, times and durations next to `chrono`'s.
+//!
+//! With the `serde` feature, the parameters of a prepared statement can be deserialized into a
+//! tuple or a struct in one go with `ParamParser::deserialize`. Going the other way, structs and
+//! tuples can be written as resultset rows with `RowWriter::serialize`, and `columns_for` describes
+//! the columns for a struct type.
 #![deny(missing_docs)]
 #![deny(rust_2018_idioms)]
 
@@ -96,26 +110,55 @@
 
 extern crate mysql_common as myc;
 
+use chrono::FixedOffset;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io;
 use std::io::prelude::*;
-use std::iter;
 use std::net;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
 
 use myc::constants::CapabilityFlags;
 
 pub use crate::myc::constants::{ColumnFlags, ColumnType, StatusFlags};
 
+#[cfg(feature = "async")]
+mod asynchronous;
+mod bootstrap;
+mod charset;
 mod commands;
+#[cfg(feature = "serde")]
+mod de;
 mod errorcodes;
 mod packet;
 mod params;
 mod resultset;
+#[cfg(feature = "serde")]
+mod ser;
+mod server;
+mod sql;
 #[cfg(feature = "tls")]
 mod tls;
 mod value;
+mod vars;
 mod writers;
 
+// 5.1.10 because that's what Ruby's ActiveRecord requires
+pub(crate) const SERVER_VERSION: &str = "5.1.10-alpha-msql-proxy";
+
+// MariaDB extended capabilities we advertise to clients
+const SERVER_MARIADB_CAPABILITIES: u32 =
+    commands::MARIADB_CLIENT_PROGRESS | commands::MARIADB_CLIENT_EXTENDED_TYPE_INFO;
+
+/// The range that `max_allowed_packet` is clamped to, as in MySQL.
+const MIN_MAX_PACKET: usize = 1024;
+const MAX_MAX_PACKET: usize = 1024 * 1024 * 1024;
+
+static NEXT_CONNECTION_ID: AtomicU32 = AtomicU32::new(1);
+
 /// Meta-information abot a single column, used either to describe a prepared statement parameter
 /// or an output column.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -134,12 +177,78 @@ pub struct Column {
     ///
     /// Of particular interest are `ColumnFlags::UNSIGNED_FLAG` and `ColumnFlags::NOT_NULL_FLAG`.
     pub colflags: ColumnFlags,
+    /// The MariaDB extended type name of this column, like `uuid` or `inet6`.
+    ///
+    /// Only sent to MariaDB clients that support extended type information.
+    pub extended_type_name: Option<String>,
+    /// The MariaDB extended format of this column, like `json`.
+    ///
+    /// Only sent to MariaDB clients that support extended type information.
+    pub extended_format: Option<String>,
+    /// The number of decimals of a `DECIMAL` column, i.e., its scale.
+    ///
+    /// Exact decimal values written to the column are rounded or padded to this many decimals.
+    /// `None` leaves values as they are, and reports no decimals to the client.
+    pub decimals: Option<u8>,
+}
+
+impl Column {
+    /// A column called `name` of type `coltype`, with no table, flags or extended type
+    /// information.
+    ///
+    /// The remaining fields can be set afterwards, or with struct update syntax instead:
+    ///
+    /// ```
+    /// # use msql_srv::{Column, ColumnFlags, ColumnType};
+    /// let id = Column {
+    ///     table: "users".to_owned(),
+    ///     colflags: ColumnFlags::NOT_NULL_FLAG,
+    ///     ..Column::new("id", ColumnType::MYSQL_TYPE_LONGLONG)
+    /// };
+    /// ```
+    pub fn new<S: Into<String>>(name: S, coltype: ColumnType) -> Self {
+        Column {
+            column: name.into(),
+            coltype,
+            ..Default::default()
+        }
+    }
+}
+
+/// An unnamed `MYSQL_TYPE_VAR_STRING` column, with no table, flags or extended type information.
+impl Default for Column {
+    fn default() -> Self {
+        Column {
+            table: String::new(),
+            column: String::new(),
+            coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
+            colflags: ColumnFlags::empty(),
+            extended_type_name: None,
+            extended_format: None,
+            decimals: None,
+        }
+    }
 }
 
+#[cfg(feature = "async")]
+pub use crate::asynchronous::{AsyncMysqlIntermediary, AsyncMysqlShim, AsyncStream};
+pub use crate::bootstrap::BootstrapQuery;
+pub use crate::charset::Charset;
+#[cfg(feature = "serde")]
+pub use crate::de::DeserializeError;
 pub use crate::errorcodes::ErrorKind;
-pub use crate::params::{ParamParser, ParamValue, Params};
+pub use crate::params::{ParamParser, ParamValue, Params, QueryAttributes};
 pub use crate::resultset::{InitWriter, QueryResultWriter, RowWriter, StatementMetaWriter};
-pub use crate::value::{ToMysqlValue, Value, ValueInner};
+#[cfg(feature = "serde")]
+pub use crate::ser::columns_for;
+use crate::server::{Close, Stop};
+pub use crate::server::{Listener, MysqlServer, ShimFactory, ShutdownHandle};
+#[cfg(feature = "serde_json")]
+pub use crate::value::Json;
+pub use crate::value::{
+    Duration, FromValueError, OwnedValue, ToMysqlValue, Value, ValueInner, WriteContext,
+};
+pub use crate::vars::{SystemVariables, VariableScope, VariableValue};
 
 /// Implementors of this trait can be used to drive a MySQL-compatible database backend.
 pub trait MysqlShim<W: Read + Write> {
@@ -185,11 +294,106 @@ pub trait MysqlShim<W: Read + Write> {
         results: QueryResultWriter<'_, W>,
     ) -> Result<(), Self::Error>;
 
+    /// Called when the client issues a query for immediate execution, with the query text exactly
+    /// as the client sent it.
+    ///
+    /// `charset` is the character set the client has said its queries are encoded in. The default
+    /// implementation decodes the query and passes it on to
+    /// [`on_query`](trait.MysqlShim.html#tymethod.on_query), or replies with
+    /// `ER_INVALID_CHARACTER_STRING` if the query is not valid in `charset`. Override this to
+    /// handle encodings yourself, for example to support `_binary` literals that are not valid
+    /// UTF-8.
+    fn on_query_bytes(
+        &mut self,
+        query: &[u8],
+        charset: Charset,
+        results: QueryResultWriter<'_, W>,
+    ) -> Result<(), Self::Error> {
+        match charset.decode(query) {
+            Some(query) => self.on_query(&query, results),
+            None => Ok(invalid_string(charset, |k, m| results.error(k, m))?),
+        }
+    }
+
     /// Called when client switches database.
     fn on_init(&mut self, _: &str, _: InitWriter<'_, W>) -> Result<(), Self::Error> {
         Ok(())
     }
 
+    /// Provides the system variables for a new connection.
+    ///
+    /// `SELECT @@var` and `SHOW VARIABLES` queries that only involve known variables are answered
+    /// from this store without calling [`on_query`](trait.MysqlShim.html#tymethod.on_query);
+    /// selecting an unknown variable passes the query on to `on_query` as usual. `SET` statements
+    /// that assign system variables update the store before being passed on to `on_query`, except
+    /// for `SET GLOBAL` unless
+    /// [`allow_set_global`](trait.MysqlShim.html#method.allow_set_global) says otherwise. A new
+    /// connection starts out with a copy of the global values in its session scope.
+    /// `max_allowed_packet` limits the size of the packets accepted from and sent to the client;
+    /// larger packets from the client are rejected with `ER_NET_PACKET_TOO_LARGE`. It is read
+    /// once the client is authenticated, and clients cannot change it for their session.
+    /// On connections that support read timeouts, a client must complete the handshake within
+    /// `connect_timeout` seconds and is disconnected with `ER_CLIENT_INTERACTION_TIMEOUT` once it
+    /// has been idle for `wait_timeout` seconds, or `interactive_timeout` seconds if it connected
+    /// with `CLIENT_INTERACTIVE`. A client that stalls in the middle of a packet is disconnected
+    /// with `ER_NET_READ_INTERRUPTED` instead. Zoned date and time values are written to and read from the client
+    /// in the fixed offset named by `time_zone` (or by `system_time_zone` if it is `SYSTEM`).
+    /// Override this to change the defaults, or to share global variables between connections
+    /// using
+    /// [`SystemVariables::new_session`](struct.SystemVariables.html#method.new_session).
+    fn system_variables(&mut self) -> SystemVariables {
+        SystemVariables::default()
+    }
+
+    /// Decides whether a connector bootstrap query should be answered by the intermediary instead
+    /// of being passed to [`on_query`](trait.MysqlShim.html#tymethod.on_query).
+    ///
+    /// Connectors send a handful of boilerplate queries when they connect (`SET NAMES utf8mb4`,
+    /// `SHOW WARNINGS`, `SELECT DATABASE()`, and so on). Return `true` for the kinds of
+    /// [`BootstrapQuery`](enum.BootstrapQuery.html) you want answered from the connection's
+    /// session state. By default, all queries are passed to `on_query`.
+    fn answer_bootstrap_query(&self, _query: BootstrapQuery) -> bool {
+        false
+    }
+
+    /// Decides whether `SET GLOBAL` statements from clients update the global system variables.
+    ///
+    /// Global values may be shared with other connections through
+    /// [`SystemVariables::new_session`](struct.SystemVariables.html#method.new_session), so by
+    /// default clients cannot change them: the statement is still passed on to
+    /// [`on_query`](trait.MysqlShim.html#tymethod.on_query), which decides how to reply, but the
+    /// store is left alone. Return `true` to apply such assignments, which then affect the
+    /// connections made afterwards.
+    fn allow_set_global(&self) -> bool {
+        false
+    }
+
+    /// Decides whether packets with unexpected sequence ids are rejected.
+    ///
+    /// By default, the intermediary checks that every command starts a new sequence and that the
+    /// parts of a split packet are numbered consecutively, and replies with
+    /// `ER_NET_PACKETS_OUT_OF_ORDER` before closing the connection otherwise. Return `false` to
+    /// accept whatever sequence id the client sends and continue from there instead, which may
+    /// be necessary behind proxies that renumber packets.
+    fn strict_sequence_ids(&self) -> bool {
+        true
+    }
+
+    /// Provides a handle that stops the connection when it is shut down.
+    ///
+    /// An intermediary that is run on its own, rather than by a
+    /// [`MysqlServer`](struct.MysqlServer.html), serves its client until the client disconnects.
+    /// Return a clone of a [`ShutdownHandle`](struct.ShutdownHandle.html) to have it hang up with
+    /// `ER_SERVER_SHUTDOWN` once the handle is shut down instead. Clients connected through
+    /// [`run_on_tcp`](struct.MysqlIntermediary.html#method.run_on_tcp) or `run_on_unix` are
+    /// disconnected even while idle; clients on other streams when they send their next command.
+    ///
+    /// Connections served by a `MysqlServer` are stopped by the server's own handle, so this is
+    /// not called for them.
+    fn shutdown_handle(&self) -> Option<ShutdownHandle> {
+        None
+    }
+
     /// Provides the TLS configuration, if we want to support TLS.
     #[cfg(feature = "tls")]
     fn tls_config(&self) -> Option<std::sync::Arc<rustls::ServerConfig>> {
@@ -206,11 +410,29 @@ pub trait MysqlShim<W: Read + Write> {
     }
 }
 
+/// How a client is connected to the intermediary.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// A TCP connection.
+    Tcp,
+    /// A Unix domain socket connection.
+    Unix,
+    /// A stream that was handed to
+    /// [`MysqlIntermediary::run_on`](struct.MysqlIntermediary.html#method.run_on), whose transport
+    /// we do not know.
+    #[default]
+    Other,
+}
+
 /// Information about an authenticated user
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct AuthenticationContext<'a> {
     /// The username exactly as passed by the client,
     pub username: Option<Vec<u8>>,
+    /// How the client is connected.
+    pub transport: Transport,
+    /// Whether the client switched the connection to TLS.
+    pub tls: bool,
     #[cfg(feature = "tls")]
     /// The TLS certificate chain presented by the client.
     pub tls_client_certs: Option<&'a [rustls::Certificate]>,
@@ -218,11 +440,26 @@ pub struct AuthenticationContext<'a> {
     _pd: Option<&'a std::marker::PhantomData<()>>,
 }
 
+impl AuthenticationContext<'_> {
+    /// Whether the client is connected over a transport that keeps what it sends private, namely
+    /// TLS or a Unix domain socket.
+    ///
+    /// Check this before accepting credentials that are sent in the clear, such as those of
+    /// authentication methods that require a secure transport.
+    pub fn is_secure(&self) -> bool {
+        self.tls || self.transport == Transport::Unix
+    }
+}
+
 /// A server that speaks the MySQL/MariaDB protocol, and can delegate client commands to a backend
 /// that implements [`MysqlShim`](trait.MysqlShim.html).
 pub struct MysqlIntermediary<B, RW: Read + Write> {
     shim: B,
     rw: packet::PacketConn<RW>,
+    conn: Connection,
+    // tells us when the server is shutting down, at which point we hang up before the next
+    // command
+    shutdown: Option<Stop>,
 }
 
 impl<B: MysqlShim<net::TcpStream>> MysqlIntermediary<B, net::TcpStream> {
@@ -230,7 +467,21 @@ impl<B: MysqlShim<net::TcpStream>> MysqlIntermediary<B, net::TcpStream> {
     /// disconnects or an error occurs. See also
     /// [`MysqlIntermediary::run_on`](struct.MysqlIntermediary.html#method.run_on).
     pub fn run_on_tcp(shim: B, stream: net::TcpStream) -> Result<(), B::Error> {
-        MysqlIntermediary::run_on(shim, stream)
+        let timeouts = net::TcpStream::set_read_timeout;
+        let stop = register(&shim, || server::closer::<net::TcpListener>(&stream));
+        MysqlIntermediary::run_with(shim, stream, Transport::Tcp, Some(timeouts), stop)
+    }
+}
+
+#[cfg(unix)]
+impl<B: MysqlShim<UnixStream>> MysqlIntermediary<B, UnixStream> {
+    /// Create a new server over a Unix domain socket stream and process client commands until the
+    /// client disconnects or an error occurs. See also
+    /// [`MysqlIntermediary::run_on`](struct.MysqlIntermediary.html#method.run_on).
+    pub fn run_on_unix(shim: B, stream: UnixStream) -> Result<(), B::Error> {
+        let timeouts = UnixStream::set_read_timeout;
+        let stop = register(&shim, || server::closer::<UnixListener>(&stream));
+        MysqlIntermediary::run_with(shim, stream, Transport::Unix, Some(timeouts), stop)
     }
 }
 
@@ -250,142 +501,459 @@ struct StatementData {
     params: u16,
 }
 
+/// The state of a client connection that does not depend on how we talk to the client.
+pub(crate) struct Connection {
+    vars: SystemVariables,
+    schema: Option<String>,
+    connection_id: u32,
+    capabilities: CapabilityFlags,
+    client_max_packet: u32,
+    transport: Transport,
+}
+
+impl Connection {
+    fn new(vars: SystemVariables, transport: Transport) -> Self {
+        vars.start_session();
+        Connection {
+            vars,
+            transport,
+            schema: None,
+            connection_id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            capabilities: CapabilityFlags::empty(),
+            client_max_packet: 0,
+        }
+    }
+
+    /// Write the server's greeting, which starts the handshake.
+    fn greet<RW: Read + Write>(
+        &self,
+        rw: &mut packet::PacketConn<RW>,
+        tls: bool,
+    ) -> io::Result<()> {
+        rw.write_all(&[10])?; // protocol 10
+
+        rw.write_all(SERVER_VERSION.as_bytes())?;
+        rw.write_all(&[0x00])?;
+
+        rw.write_all(&self.connection_id.to_le_bytes())?;
+        rw.write_all(&b";X,po_k}\0"[..])?; // auth seed
+        let capabilities = &mut [0x00, 0x42]; // 4.1 proto
+        if tls {
+            capabilities[1] |= 0x08; // SSL support flag
+        }
+        rw.write_all(capabilities)?;
+        rw.write_all(&[0x21])?; // UTF8_GENERAL_CI
+        rw.write_all(&[0x00, 0x00])?; // status flags
+        rw.write_all(&[0x00, 0x08])?; // extended capabilities: CLIENT_QUERY_ATTRIBUTES
+        rw.write_all(&[0x00])?; // no plugins
+        rw.write_all(&[0x00; 6][..])?; // filler
+        rw.write_all(&SERVER_MARIADB_CAPABILITIES.to_le_bytes())?; // MariaDB capabilities
+        rw.write_all(&b">o6^Wz!/kM}N\0"[..])?; // 4.1+ servers must extend salt
+        rw.flush()
+    }
+
+    /// Take note of what the client told us in its handshake response.
+    fn accept<RW: Read + Write>(
+        &mut self,
+        handshake: &commands::ClientHandshake<'_>,
+        rw: &mut packet::PacketConn<RW>,
+    ) {
+        self.capabilities = handshake.capabilities;
+        self.client_max_packet = handshake.maxps;
+        if handshake
+            .capabilities
+            .contains(CapabilityFlags::CLIENT_INTERACTIVE)
+        {
+            // like MySQL, let interactive clients idle for as long as `interactive_timeout`
+            if let Some(timeout) = self.vars.get("interactive_timeout") {
+                self.vars
+                    .set(VariableScope::Session, "wait_timeout", timeout);
+            }
+        }
+        rw.set_mariadb_capabilities(handshake.mariadb_capabilities & SERVER_MARIADB_CAPABILITIES);
+        if let Some(name) = charset::collation_charset_name(handshake.collation) {
+            for var in &[
+                "character_set_client",
+                "character_set_connection",
+                "character_set_results",
+            ] {
+                self.vars.set(VariableScope::Session, var, name);
+            }
+        }
+    }
+
+    /// Limit packet sizes to the `max_allowed_packet` system variable, and to what the client said
+    /// it can receive.
+    ///
+    /// Like in MySQL, the limit is clamped to 1 KiB..1 GiB, and is fixed for the rest of the
+    /// connection once the client has been authenticated.
+    fn apply_max_packet<RW: Read + Write>(&self, rw: &mut packet::PacketConn<RW>) {
+        let max = match self.vars.get("max_allowed_packet") {
+            Some(VariableValue::Int(n)) => usize::try_from(n).unwrap_or(0),
+            Some(VariableValue::UInt(n)) => usize::try_from(n).unwrap_or(usize::MAX),
+            _ => packet::DEFAULT_MAX_PAYLOAD,
+        }
+        .clamp(MIN_MAX_PACKET, MAX_MAX_PACKET);
+        self.vars
+            .set(VariableScope::Session, "max_allowed_packet", max as i64);
+        let write = match self.client_max_packet {
+            0 => max,
+            client => max.min(client as usize),
+        };
+        rw.set_max_payload(max, write);
+    }
+
+    /// The time by which the client must have sent its next packet, if `var` sets a timeout.
+    ///
+    /// Timeouts are read from the session, which holds a copy of the global values taken when the
+    /// connection started, so other clients changing the global values do not affect them.
+    fn deadline(&self, var: &str) -> Option<Instant> {
+        let secs = match self.vars.get(var)? {
+            VariableValue::Int(n) if n > 0 => n as u64,
+            VariableValue::UInt(n) if n > 0 => n,
+            _ => return None,
+        };
+        Instant::now().checked_add(std::time::Duration::from_secs(secs))
+    }
+
+    /// The character set named by the session variable `var`.
+    fn charset(&self, var: &str) -> Charset {
+        match self.vars.get(var) {
+            Some(VariableValue::Str(name)) => Charset::from_name(&name).unwrap_or_default(),
+            _ => Charset::Utf8,
+        }
+    }
+
+    /// The offset of the session `time_zone`, where `SYSTEM` means `system_time_zone`.
+    ///
+    /// Time zones that are not fixed offsets are treated as UTC.
+    fn time_zone(&self) -> FixedOffset {
+        let name = |var| match self.vars.get(var) {
+            Some(VariableValue::Str(name)) => Some(name),
+            _ => None,
+        };
+        let tz = match name("time_zone") {
+            Some(tz) if !tz.eq_ignore_ascii_case("SYSTEM") => Some(tz),
+            _ => name("system_time_zone"),
+        };
+        tz.and_then(|tz| vars::time_zone_offset(&tz))
+            .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+    }
+
+    /// Split the query attributes off the front of a `COM_QUERY`, if the client sends them.
+    fn query_attributes<'a>(&self, q: &'a [u8]) -> io::Result<(QueryAttributes<'a>, &'a [u8])> {
+        if self
+            .capabilities
+            .contains(CapabilityFlags::CLIENT_QUERY_ATTRIBUTES)
+        {
+            params::query_attributes(q)
+        } else {
+            Ok((QueryAttributes::default(), q))
+        }
+    }
+
+    /// Parse the parameters of a `COM_STMT_EXECUTE` for the prepared statement `state`.
+    fn execute_params<'a>(
+        &self,
+        params: &'a [u8],
+        flags: u8,
+        state: &'a mut StatementData,
+    ) -> io::Result<(ParamParser<'a>, QueryAttributes<'a>)> {
+        let query_attributes = self
+            .capabilities
+            .contains(CapabilityFlags::CLIENT_QUERY_ATTRIBUTES);
+        params::ParamParser::new(params, flags, query_attributes, state, self.time_zone())
+    }
+
+    /// Answer the queries about system variables and the connector bootstrap queries that we can
+    /// answer ourselves.
+    ///
+    /// Returns the writer back if the query should be passed on to the shim. `SET GLOBAL` only
+    /// changes global values if `set_global` is set.
+    fn intercept<'a, W, F>(
+        &mut self,
+        text: &str,
+        w: QueryResultWriter<'a, W>,
+        set_global: bool,
+        answer_bootstrap_query: F,
+    ) -> io::Result<Option<QueryResultWriter<'a, W>>>
+    where
+        W: Read + Write,
+        F: FnOnce(BootstrapQuery) -> bool,
+    {
+        let w = match vars::parse(text) {
+            Some(vars::VarsQuery::Select { items, limit }) => {
+                self.vars.answer_select(&items, limit, w)?
+            }
+            Some(vars::VarsQuery::Show { scope, like }) => {
+                self.vars.answer_show(scope, like.as_deref(), w)?;
+                None
+            }
+            Some(vars::VarsQuery::Set(assignments)) => {
+                self.vars.apply(assignments, set_global);
+                Some(w)
+            }
+            None => Some(w),
+        };
+        match (w, bootstrap::parse(text)) {
+            (Some(w), Some(b)) if answer_bootstrap_query(b.query) => {
+                let session = bootstrap::Session {
+                    schema: self.schema.as_deref(),
+                    connection_id: self.connection_id,
+                    vars: &self.vars,
+                };
+                bootstrap::answer(b, session, w)?;
+                Ok(None)
+            }
+            (w, _) => Ok(w),
+        }
+    }
+}
+
+/// Parse a client's handshake response.
+fn parse_handshake(packet: &[u8], after_tls: bool) -> io::Result<commands::ClientHandshake<'_>> {
+    commands::client_handshake(packet, after_tls)
+        .map(|(_, handshake)| handshake)
+        .map_err(|e| match e {
+            nom::Err::Incomplete(_) => io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "client sent incomplete handshake",
+            ),
+            nom::Err::Failure(nom_error) | nom::Err::Error(nom_error) => {
+                if let nom::error::ErrorKind::Eof = nom_error.code {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!(
+                            "client did not complete handshake; got {:?}",
+                            nom_error.input
+                        ),
+                    )
+                } else {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "bad client handshake; got {:?} ({:?})",
+                            nom_error.input, nom_error.code
+                        ),
+                    )
+                }
+            }
+        })
+}
+
+fn peer_terminated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "peer terminated connection",
+    )
+}
+
+fn ssl_not_supported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "client requested SSL despite us not advertising support for it",
+    )
+}
+
+fn unknown_statement(stmt: u32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("asked to execute unknown statement {}", stmt),
+    )
+}
+
+/// The schema named by a `USE <db>` query, if `q` is one.
+fn use_schema(q: &[u8]) -> Option<&[u8]> {
+    if q.starts_with(b"USE ") || q.starts_with(b"use ") {
+        Some(&q[b"USE ".len()..])
+    } else {
+        None
+    }
+}
+
+fn trim_schema(schema: &str) -> &str {
+    schema.trim().trim_end_matches(';').trim_matches('`')
+}
+
+/// Tell the client about a protocol violation before giving up on the connection, since the rest
+/// of the offending packet may still be in flight.
+fn reject<RW: Read + Write>(rw: &mut packet::PacketConn<RW>, e: io::Error) -> io::Error {
+    if let Some(pe) = packet::packet_error(&e) {
+        let res = writers::write_err(pe.error_kind(), pe.message().as_bytes(), rw)
+            .and_then(|()| rw.flush());
+        if let Err(e) = res {
+            return e;
+        }
+    }
+    e
+}
+
+/// Count a connection as open with the shim's shutdown handle, if it has one. `close` makes a
+/// pending read of the connection return.
+fn register<B, RW>(shim: &B, close: impl FnOnce() -> io::Result<Close>) -> Option<Stop>
+where
+    B: MysqlShim<RW>,
+    RW: Read + Write,
+{
+    let handle = shim.shutdown_handle()?;
+    // without a way to wake it, the connection notices the shutdown at the next command
+    let close = close().unwrap_or_else(|_| Box::new(|_| {}));
+    Some(handle.register(close))
+}
+
+/// Tell a client that we are hanging up on it because the server is shutting down.
+fn say_goodbye<RW: Read + Write>(rw: &mut packet::PacketConn<RW>) -> io::Result<()> {
+    writers::write_err(
+        ErrorKind::ER_SERVER_SHUTDOWN,
+        b"Server shutdown in progress",
+        rw,
+    )?;
+    ignore_hang_up(rw.flush())
+}
+
+/// Treat sending our goodbye to a client that has hung up already as a success.
+fn ignore_hang_up(res: io::Result<()>) -> io::Result<()> {
+    match res {
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+            ) =>
+        {
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// Handle the commands that do not involve the shim.
+fn handle_plain_command<RW: Read + Write>(
+    cmd: commands::Command<'_>,
+    rw: &mut packet::PacketConn<RW>,
+    stmts: &mut HashMap<u32, StatementData>,
+) -> io::Result<()> {
+    use crate::commands::Command;
+
+    match cmd {
+        Command::SendLongData { stmt, param, data } => {
+            stmts
+                .get_mut(&stmt)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("got long data packet for unknown statement {}", stmt),
+                    )
+                })?
+                .long_data
+                .entry(param)
+                .or_insert_with(Vec::new)
+                .extend(data);
+        }
+        Command::ListFields(_) => {
+            let cols = &[Column {
+                colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
+                ..Column::new(
+                    "not implemented",
+                    myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+                )
+            }];
+            writers::write_column_definitions(cols, rw, true, true, Charset::Utf8)?;
+        }
+        Command::Ping => {
+            writers::write_ok_packet(rw, 0, 0, StatusFlags::empty())?;
+        }
+        _ => unreachable!("command is handled by the intermediary"),
+    }
+    Ok(())
+}
+
 impl<B: MysqlShim<RW>, RW: Read + Write> MysqlIntermediary<B, RW> {
     /// Create a new server over a two-way channel and process client commands until the client
     /// disconnects or an error occurs.
+    ///
+    /// Since `rw` may not support read timeouts, clients that stop sending packets are only timed
+    /// out once they send something again; use
+    /// [`run_on_tcp`](struct.MysqlIntermediary.html#method.run_on_tcp) or `run_on_unix` to enforce
+    /// `connect_timeout` and `wait_timeout` on idle clients.
     pub fn run_on(shim: B, rw: RW) -> Result<(), B::Error> {
-        let rw = packet::PacketConn::new(rw);
-        let mut mi = MysqlIntermediary { shim, rw };
+        // we have no way to interrupt a read from `rw`
+        let stop = register(&shim, || Ok(Box::new(|_| {})));
+        MysqlIntermediary::run_with(shim, rw, Transport::Other, None, stop)
+    }
+
+    pub(crate) fn run_with(
+        mut shim: B,
+        rw: RW,
+        transport: Transport,
+        timeouts: Option<packet::SetReadTimeout<RW>>,
+        shutdown: Option<Stop>,
+    ) -> Result<(), B::Error> {
+        let mut rw = packet::PacketConn::new(rw);
+        rw.set_strict_sequence(shim.strict_sequence_ids());
+        if let Some(timeouts) = timeouts {
+            rw.set_read_timeouts(timeouts);
+        }
+        let conn = Connection::new(shim.system_variables(), transport);
+        let mut mi = MysqlIntermediary {
+            shim,
+            rw,
+            conn,
+            shutdown,
+        };
         mi.init()?;
         mi.run()
     }
 
     fn init(&mut self) -> Result<(), B::Error> {
+        // the whole handshake has to fit in `connect_timeout`
+        self.rw
+            .set_deadline(self.conn.deadline("connect_timeout"))?;
+
         #[cfg(feature = "tls")]
         let tls_conf = self.shim.tls_config();
-
-        self.rw.write_all(&[10])?; // protocol 10
-
-        // 5.1.10 because that's what Ruby's ActiveRecord requires
-        self.rw.write_all(&b"5.1.10-alpha-msql-proxy\0"[..])?;
-
-        self.rw.write_all(&[0x08, 0x00, 0x00, 0x00])?; // TODO: connection ID
-        self.rw.write_all(&b";X,po_k}\0"[..])?; // auth seed
-        let capabilities = &mut [0x00, 0x42]; // 4.1 proto
         #[cfg(feature = "tls")]
-        if tls_conf.is_some() {
-            capabilities[1] |= 0x08; // SSL support flag
-        }
-        self.rw.write_all(capabilities)?;
-        self.rw.write_all(&[0x21])?; // UTF8_GENERAL_CI
-        self.rw.write_all(&[0x00, 0x00])?; // status flags
-        self.rw.write_all(&[0x00, 0x00])?; // extended capabilities
-        self.rw.write_all(&[0x00])?; // no plugins
-        self.rw.write_all(&[0x00; 6][..])?; // filler
-        self.rw.write_all(&[0x00; 4][..])?; // filler
-        self.rw.write_all(&b">o6^Wz!/kM}N\0"[..])?; // 4.1+ servers must extend salt
-        self.rw.flush()?;
+        self.conn.greet(&mut self.rw, tls_conf.is_some())?;
+        #[cfg(not(feature = "tls"))]
+        self.conn.greet(&mut self.rw, false)?;
 
-        let mut auth_context = AuthenticationContext::default();
+        let mut auth_context = AuthenticationContext {
+            transport: self.conn.transport,
+            ..AuthenticationContext::default()
+        };
 
         {
-            let (seq, handshake) = self.rw.next()?.ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::ConnectionAborted,
-                    "peer terminated connection",
-                )
-            })?;
-            let handshake = commands::client_handshake(&handshake, false)
-                .map_err(|e| match e {
-                    nom::Err::Incomplete(_) => io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "client sent incomplete handshake",
-                    ),
-                    nom::Err::Failure(nom_error) | nom::Err::Error(nom_error) => {
-                        if let nom::error::ErrorKind::Eof = nom_error.code {
-                            io::Error::new(
-                                io::ErrorKind::UnexpectedEof,
-                                format!(
-                                    "client did not complete handshake; got {:?}",
-                                    nom_error.input
-                                ),
-                            )
-                        } else {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!(
-                                    "bad client handshake; got {:?} ({:?})",
-                                    nom_error.input, nom_error.code
-                                ),
-                            )
-                        }
-                    }
-                })?
-                .1;
+            let (_, handshake) = self
+                .rw
+                .next()
+                .map_err(|e| reject(&mut self.rw, e))?
+                .ok_or_else(peer_terminated)?;
+            let handshake = parse_handshake(&handshake, false)?;
 
             auth_context.username = handshake.username.map(|x| x.to_vec());
-
-            self.rw.set_seq(seq + 1);
+            self.conn.accept(&handshake, &mut self.rw);
 
             #[cfg(not(feature = "tls"))]
             if handshake.capabilities.contains(CapabilityFlags::CLIENT_SSL) {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "client requested SSL despite us not advertising support for it",
-                )
-                .into());
+                return Err(ssl_not_supported().into());
             }
 
             #[cfg(feature = "tls")]
             if handshake.capabilities.contains(CapabilityFlags::CLIENT_SSL) {
-                let config = tls_conf.ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "client requested SSL despite us not advertising support for it",
-                    )
-                })?;
+                let config = tls_conf.ok_or_else(ssl_not_supported)?;
 
                 self.rw.switch_to_tls(config)?;
 
-                let (seq, handshake) = self.rw.next()?.ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::ConnectionAborted,
-                        "peer terminated connection",
-                    )
-                })?;
-
-                let handshake = commands::client_handshake(&handshake, true)
-                    .map_err(|e| match e {
-                        nom::Err::Incomplete(_) => io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "client sent incomplete handshake",
-                        ),
-                        nom::Err::Failure(nom_error) | nom::Err::Error(nom_error) => {
-                            if let nom::error::ErrorKind::Eof = nom_error.code {
-                                io::Error::new(
-                                    io::ErrorKind::UnexpectedEof,
-                                    format!(
-                                        "client did not complete handshake; got {:?}",
-                                        nom_error.input
-                                    ),
-                                )
-                            } else {
-                                io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!(
-                                        "bad client handshake; got {:?} ({:?})",
-                                        nom_error.input, nom_error.code
-                                    ),
-                                )
-                            }
-                        }
-                    })?
-                    .1;
+                let (_, handshake) = self
+                    .rw
+                    .next()
+                    .map_err(|e| reject(&mut self.rw, e))?
+                    .ok_or_else(peer_terminated)?;
+                let handshake = parse_handshake(&handshake, true)?;
 
                 auth_context.username = handshake.username.map(|x| x.to_vec());
+                self.conn.accept(&handshake, &mut self.rw);
 
-                self.rw.set_seq(seq + 1);
-
+                auth_context.tls = true;
                 auth_context.tls_client_certs = self.rw.tls_certs();
             }
 
@@ -402,128 +970,148 @@ impl<B: MysqlShim<RW>, RW: Read + Write> MysqlIntermediary<B, RW> {
 
         writers::write_ok_packet(&mut self.rw, 0, 0, StatusFlags::empty())?;
         self.rw.flush()?;
+        self.conn.apply_max_packet(&mut self.rw);
 
         Ok(())
     }
 
+    fn shutting_down(&self) -> bool {
+        self.shutdown.as_ref().is_some_and(Stop::requested)
+    }
+
     fn run(mut self) -> Result<(), B::Error> {
         use crate::commands::Command;
 
         let mut stmts: HashMap<u32, _> = HashMap::new();
-        while let Some((seq, packet)) = self.rw.next()? {
-            self.rw.set_seq(seq + 1);
+        loop {
+            // every command starts a new sequence
+            self.rw.set_seq(0);
+            if self.shutting_down() {
+                return Ok(say_goodbye(&mut self.rw)?);
+            }
+            self.rw.set_deadline(self.conn.deadline("wait_timeout"))?;
+            let packet = match self.rw.next() {
+                Ok(Some((_, p))) => p,
+                // the server stops reading from clients to wake them up when it shuts down, which
+                // TLS reports as an error
+                Ok(None) | Err(_) if self.shutting_down() => return Ok(say_goodbye(&mut self.rw)?),
+                Ok(None) => break,
+                Err(e) => return Err(reject(&mut self.rw, e).into()),
+            };
             let cmd = commands::parse(&packet).unwrap().1;
             match cmd {
                 Command::Query(q) => {
-                    if q.starts_with(b"SELECT @@") || q.starts_with(b"select @@") {
-                        let w = QueryResultWriter::new(&mut self.rw, false);
-                        let var = &q[b"SELECT @@".len()..];
-                        match var {
-                            b"max_allowed_packet" => {
-                                let cols = &[Column {
-                                    table: String::new(),
-                                    column: "@@max_allowed_packet".to_owned(),
-                                    coltype: myc::constants::ColumnType::MYSQL_TYPE_LONG,
-                                    colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
-                                }];
-                                let mut w = w.start(cols)?;
-                                w.write_row(iter::once(67108864u32))?;
-                                w.finish()?;
-                            }
-                            _ => {
-                                w.completed(0, 0)?;
+                    let (attributes, q) = self.conn.query_attributes(q)?;
+                    let client_charset = self.conn.charset("character_set_client");
+                    if let Some(schema) = use_schema(q) {
+                        match client_charset.decode(schema) {
+                            Some(schema) => {
+                                let schema = trim_schema(&schema);
+                                let w = InitWriter {
+                                    writer: &mut self.rw,
+                                    schema: &mut self.conn.schema,
+                                    requested: schema,
+                                };
+                                self.shim.on_init(schema, w)?;
                             }
+                            None => invalid_string(client_charset, |k, m| {
+                                writers::write_err(k, m, &mut self.rw)
+                            })?,
                         }
-                    } else if q.starts_with(b"USE ") || q.starts_with(b"use ") {
-                        let w = InitWriter {
-                            writer: &mut self.rw,
-                        };
-                        let schema = ::std::str::from_utf8(&q[b"USE ".len()..])
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                        let schema = schema.trim().trim_end_matches(';').trim_matches('`');
-                        self.shim.on_init(schema, w)?;
                     } else {
-                        let w = QueryResultWriter::new(&mut self.rw, false);
-                        self.shim.on_query(
-                            ::std::str::from_utf8(q)
-                                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                            w,
-                        )?;
+                        let results_charset = self.conn.charset("character_set_results");
+                        let w = QueryResultWriter::new(
+                            &mut self.rw,
+                            false,
+                            results_charset,
+                            self.conn.time_zone(),
+                            attributes,
+                        );
+                        let w = match client_charset.decode(q) {
+                            Some(text) => {
+                                let shim = &self.shim;
+                                let set_global = shim.allow_set_global();
+                                self.conn.intercept(&text, w, set_global, |b| {
+                                    shim.answer_bootstrap_query(b)
+                                })?
+                            }
+                            None => Some(w),
+                        };
+                        if let Some(w) = w {
+                            self.shim.on_query_bytes(q, client_charset, w)?;
+                        }
                     }
                 }
                 Command::Prepare(q) => {
+                    let charset = self.conn.charset("character_set_client");
                     let w = StatementMetaWriter {
                         writer: &mut self.rw,
                         stmts: &mut stmts,
                     };
-
-                    self.shim.on_prepare(
-                        ::std::str::from_utf8(q)
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                        w,
-                    )?;
+                    match charset.decode(q) {
+                        Some(q) => self.shim.on_prepare(&q, w)?,
+                        None => invalid_string(charset, |k, m| w.error(k, m))?,
+                    }
                 }
-                Command::Execute { stmt, params } => {
-                    let state = stmts.get_mut(&stmt).ok_or_else(|| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("asked to execute unknown statement {}", stmt),
-                        )
-                    })?;
+                Command::Execute {
+                    stmt,
+                    flags,
+                    params,
+                } => {
+                    let state = stmts
+                        .get_mut(&stmt)
+                        .ok_or_else(|| unknown_statement(stmt))?;
                     {
-                        let params = params::ParamParser::new(params, state);
-                        let w = QueryResultWriter::new(&mut self.rw, true);
+                        let (params, attributes) =
+                            self.conn.execute_params(params, flags, state)?;
+                        let charset = self.conn.charset("character_set_results");
+                        let w = QueryResultWriter::new(
+                            &mut self.rw,
+                            true,
+                            charset,
+                            self.conn.time_zone(),
+                            attributes,
+                        );
                         self.shim.on_execute(stmt, params, w)?;
                     }
                     state.long_data.clear();
                 }
-                Command::SendLongData { stmt, param, data } => {
-                    stmts
-                        .get_mut(&stmt)
-                        .ok_or_else(|| {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("got long data packet for unknown statement {}", stmt),
-                            )
-                        })?
-                        .long_data
-                        .entry(param)
-                        .or_insert_with(Vec::new)
-                        .extend(data);
-                }
                 Command::Close(stmt) => {
                     self.shim.on_close(stmt);
                     stmts.remove(&stmt);
                     // NOTE: spec dictates no response from server
                 }
-                Command::ListFields(_) => {
-                    let cols = &[Column {
-                        table: String::new(),
-                        column: "not implemented".to_owned(),
-                        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                        colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
-                    }];
-                    writers::write_column_definitions(cols, &mut self.rw, true, true)?;
-                }
                 Command::Init(schema) => {
-                    let w = InitWriter {
-                        writer: &mut self.rw,
-                    };
-                    self.shim.on_init(
-                        ::std::str::from_utf8(schema)
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                        w,
-                    )?;
-                }
-                Command::Ping => {
-                    writers::write_ok_packet(&mut self.rw, 0, 0, StatusFlags::empty())?;
+                    let charset = self.conn.charset("character_set_client");
+                    match charset.decode(schema) {
+                        Some(schema) => {
+                            let w = InitWriter {
+                                writer: &mut self.rw,
+                                schema: &mut self.conn.schema,
+                                requested: &schema,
+                            };
+                            self.shim.on_init(&schema, w)?;
+                        }
+                        None => {
+                            invalid_string(charset, |k, m| writers::write_err(k, m, &mut self.rw))?
+                        }
+                    }
                 }
                 Command::Quit => {
                     break;
                 }
+                cmd => handle_plain_command(cmd, &mut self.rw, &mut stmts)?,
             }
             self.rw.flush()?;
         }
         Ok(())
     }
 }
+
+fn invalid_string<F>(charset: Charset, reply: F) -> io::Result<()>
+where
+    F: FnOnce(ErrorKind, &[u8]) -> io::Result<()>,
+{
+    let msg = format!("Invalid {} character string", charset.name());
+    reply(ErrorKind::ER_INVALID_CHARACTER_STRING, msg.as_bytes())
+}
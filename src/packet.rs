@@ -1,11 +1,80 @@
+use crate::ErrorKind;
 use byteorder::{ByteOrder, LittleEndian};
 #[cfg(feature = "tls")]
 use rustls::{Certificate, ServerConfig};
 use std::io;
 use std::io::prelude::*;
+use std::time::{Duration, Instant};
 
 const U24_MAX: usize = 16_777_215;
 
+/// The `max_allowed_packet` that applies until the intermediary configures one.
+pub(crate) const DEFAULT_MAX_PAYLOAD: usize = 64 * 1024 * 1024;
+
+/// A protocol violation by the client, carried by the `io::Error`s that `PacketConn` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PacketError {
+    /// The payload is larger than the configured maximum.
+    TooLarge,
+    /// A packet arrived with an unexpected sequence id.
+    OutOfOrder,
+    /// The client did not finish sending a packet in time.
+    TimedOut,
+    /// The client did not start sending a packet in time.
+    Idle,
+}
+
+impl PacketError {
+    /// The error to report to the client.
+    pub(crate) fn error_kind(self) -> ErrorKind {
+        match self {
+            PacketError::TooLarge => ErrorKind::ER_NET_PACKET_TOO_LARGE,
+            PacketError::OutOfOrder => ErrorKind::ER_NET_PACKETS_OUT_OF_ORDER,
+            PacketError::TimedOut => ErrorKind::ER_NET_READ_INTERRUPTED,
+            PacketError::Idle => ErrorKind::ER_CLIENT_INTERACTION_TIMEOUT,
+        }
+    }
+
+    /// The message to report to the client.
+    pub(crate) fn message(self) -> &'static str {
+        match self {
+            PacketError::TooLarge => "Got a packet bigger than 'max_allowed_packet' bytes",
+            PacketError::OutOfOrder => "Got packets out of order",
+            PacketError::TimedOut => "Got timeout reading communication packets",
+            PacketError::Idle => {
+                "The client was disconnected by the server because of inactivity. \
+                 See wait_timeout and interactive_timeout for configuring this behavior."
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+impl From<PacketError> for io::Error {
+    fn from(e: PacketError) -> Self {
+        let kind = match e {
+            PacketError::TimedOut | PacketError::Idle => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+/// Returns the protocol violation that caused `e`, if any.
+pub(crate) fn packet_error(e: &io::Error) -> Option<PacketError> {
+    e.get_ref()?.downcast_ref::<PacketError>().copied()
+}
+
+/// Sets the read timeout of a connection that supports one, like `TcpStream::set_read_timeout`.
+pub(crate) type SetReadTimeout<RW> = fn(&RW, Option<Duration>) -> io::Result<()>;
+
 pub struct PacketConn<RW: Read + Write> {
     #[cfg(feature = "tls")]
     rw: tls::SwitchableConn<RW>,
@@ -17,19 +86,54 @@ pub struct PacketConn<RW: Read + Write> {
     start: usize,
     remaining: usize,
 
+    max_read: usize,
+    strict_seq: bool,
+    // the time by which the packet being read must have arrived
+    deadline: Option<Instant>,
+    set_read_timeout: Option<SetReadTimeout<RW>>,
+
     // write variables
     to_write: Vec<u8>,
     seq: u8,
+    max_write: usize,
+    // length of the payload being written, including parts already sent as full packets
+    payload: usize,
+    // the last packet sent was a full one, so the payload must be terminated by another packet
+    // even if it is empty
+    continued: bool,
+    // part of a payload that then turned out to be too large was sent, so the client can no
+    // longer make sense of anything we send
+    desynced: bool,
+
+    // MariaDB extended capabilities negotiated with the client
+    mariadb_capabilities: u32,
 }
 
 impl<W: Read + Write> Write for PacketConn<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         use std::cmp::min;
-        let left = min(buf.len(), U24_MAX - self.to_write.len());
+        if self.desynced {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "connection abandoned after sending part of an oversized packet",
+            ));
+        }
+        if self.payload + buf.len() > self.max_write {
+            // drop what we have of this payload rather than send a truncated packet, which is
+            // only too late if a full packet of it already went out
+            self.to_write.truncate(4);
+            self.payload = 0;
+            self.desynced = self.continued;
+            self.continued = false;
+            return Err(PacketError::TooLarge.into());
+        }
+
+        let left = min(buf.len(), U24_MAX + 4 - self.to_write.len());
         self.to_write.extend(&buf[..left]);
+        self.payload += left;
 
-        if self.to_write.len() == U24_MAX {
-            self.end_packet()?;
+        if self.to_write.len() == U24_MAX + 4 {
+            self.send_packet()?;
         }
         Ok(left)
     }
@@ -50,23 +154,44 @@ impl<RW: Read + Write> PacketConn<RW> {
             start: 0,
             remaining: 0,
 
+            max_read: DEFAULT_MAX_PAYLOAD,
+            strict_seq: true,
+            deadline: None,
+            set_read_timeout: None,
+
             to_write: vec![0, 0, 0, 0],
             seq: 0,
+            max_write: DEFAULT_MAX_PAYLOAD,
+            payload: 0,
+            continued: false,
+            desynced: false,
+            mariadb_capabilities: 0,
             rw,
         }
     }
 }
 
 impl<W: Read + Write> PacketConn<W> {
-    fn maybe_end_packet(&mut self) -> io::Result<()> {
+    fn send_packet(&mut self) -> io::Result<()> {
         let len = self.to_write.len() - 4;
-        if len != 0 {
-            LittleEndian::write_u24(&mut self.to_write[0..3], len as u32);
-            self.to_write[3] = self.seq;
-            self.seq = self.seq.wrapping_add(1);
+        LittleEndian::write_u24(&mut self.to_write[0..3], len as u32);
+        self.to_write[3] = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+
+        self.rw.write_all(&self.to_write[..])?;
+        self.to_write.truncate(4); // back to just header
 
-            self.rw.write_all(&self.to_write[..])?;
-            self.to_write.truncate(4); // back to just header
+        // a payload that is an exact multiple of U24_MAX is terminated by an empty packet
+        self.continued = len == U24_MAX;
+        if !self.continued {
+            self.payload = 0;
+        }
+        Ok(())
+    }
+
+    fn maybe_end_packet(&mut self) -> io::Result<()> {
+        if self.to_write.len() > 4 || self.continued {
+            self.send_packet()?;
         }
         Ok(())
     }
@@ -75,6 +200,40 @@ impl<W: Read + Write> PacketConn<W> {
         self.maybe_end_packet()
     }
 
+    /// Take the bytes that have been received but not yet read as packets.
+    #[cfg(all(feature = "async", feature = "tls"))]
+    pub fn take_unread(&mut self) -> Vec<u8> {
+        let unread = self.bytes[self.bytes.len() - self.remaining..].to_vec();
+        self.remaining = 0;
+        unread
+    }
+
+    /// The underlying connection.
+    #[cfg(feature = "tls")]
+    fn get_ref(&self) -> &W {
+        self.rw.get_ref()
+    }
+
+    /// The underlying connection.
+    #[cfg(not(feature = "tls"))]
+    fn get_ref(&self) -> &W {
+        &self.rw
+    }
+
+    /// The underlying connection.
+    ///
+    /// Note that this bypasses TLS if the connection has been switched to it.
+    #[cfg(all(feature = "async", feature = "tls"))]
+    pub fn get_mut(&mut self) -> &mut W {
+        self.rw.get_mut()
+    }
+
+    /// The underlying connection.
+    #[cfg(all(feature = "async", not(feature = "tls")))]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.rw
+    }
+
     #[cfg(feature = "tls")]
     pub fn switch_to_tls(&mut self, config: std::sync::Arc<ServerConfig>) -> io::Result<()> {
         let res = self
@@ -97,6 +256,51 @@ impl<W: Read + Write> PacketConn<W> {
     pub fn set_seq(&mut self, seq: u8) {
         self.seq = seq;
     }
+
+    /// Decide whether packets that do not carry the expected sequence id are rejected, or whether
+    /// we adopt the client's numbering.
+    pub fn set_strict_sequence(&mut self, strict: bool) {
+        self.strict_seq = strict;
+    }
+
+    /// Limit the size of the payloads we accept from and send to the client.
+    pub fn set_max_payload(&mut self, read: usize, write: usize) {
+        self.max_read = read;
+        self.max_write = write;
+    }
+
+    /// Enforce read deadlines by setting read timeouts on the underlying connection with `set`.
+    ///
+    /// Without this, deadlines are only checked before reading, which suits connections that do
+    /// not block.
+    pub fn set_read_timeouts(&mut self, set: SetReadTimeout<W>) {
+        self.set_read_timeout = Some(set);
+    }
+
+    /// Fail reads once `deadline` has passed without a whole packet from the client, or never time
+    /// out if it is `None`. The error is `PacketError::Idle` if the client had not started sending
+    /// the packet, and `PacketError::TimedOut` if it had.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) -> io::Result<()> {
+        self.deadline = deadline;
+        match self.set_read_timeout {
+            Some(set) if deadline.is_none() => set(self.get_ref(), None),
+            _ => Ok(()),
+        }
+    }
+
+    /// The time by which the packet being read must have arrived, if any.
+    #[cfg(feature = "async")]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn set_mariadb_capabilities(&mut self, capabilities: u32) {
+        self.mariadb_capabilities = capabilities;
+    }
+
+    pub fn has_mariadb_capability(&self, capability: u32) -> bool {
+        self.mariadb_capabilities & capability == capability
+    }
 }
 
 impl<R: Read + Write> PacketConn<R> {
@@ -105,8 +309,18 @@ impl<R: Read + Write> PacketConn<R> {
 
         loop {
             if self.remaining != 0 {
-                match packet(&self.bytes[self.start..]) {
+                if declared_len(&self.bytes[self.start..]) > self.max_read {
+                    return Err(PacketError::TooLarge.into());
+                }
+                let available = &self.bytes[self.start..];
+                match packet(available) {
                     Ok((rest, p)) => {
+                        let parts = &available[..available.len() - rest.len()];
+                        if self.strict_seq && !in_sequence(parts, self.seq) {
+                            return Err(PacketError::OutOfOrder.into());
+                        }
+                        // we reply with the sequence id following the client's last
+                        self.seq = p.0.wrapping_add(1);
                         self.remaining = rest.len();
                         return Ok(Some(p));
                     }
@@ -124,12 +338,33 @@ impl<R: Read + Write> PacketConn<R> {
             self.bytes.drain(0..self.start);
             self.start = 0;
             let end = self.bytes.len();
+            if let Some(deadline) = self.deadline {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(timed_out(end).into());
+                }
+                if let Some(set) = self.set_read_timeout {
+                    set(self.get_ref(), Some(left))?;
+                }
+            }
             self.bytes.resize(std::cmp::max(4096, end * 2), 0);
-            let read = {
-                let buf = &mut self.bytes[end..];
-                self.rw.read(buf)?
+            let read = self.rw.read(&mut self.bytes[end..]);
+            // keep what we have so far if the read fails; it may be retried (e.g., on `WouldBlock`)
+            self.bytes.truncate(end + *read.as_ref().unwrap_or(&0));
+            let read = match read {
+                // the read timeout we set ran out
+                Err(e)
+                    if self.deadline.is_some()
+                        && self.set_read_timeout.is_some()
+                        && matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                {
+                    return Err(timed_out(end).into());
+                }
+                read => read?,
             };
-            self.bytes.truncate(end + read);
             self.remaining = self.bytes.len();
 
             if read == 0 {
@@ -146,6 +381,42 @@ impl<R: Read + Write> PacketConn<R> {
     }
 }
 
+/// The error for a read that timed out with `buffered` bytes of the next packet received.
+fn timed_out(buffered: usize) -> PacketError {
+    if buffered == 0 {
+        PacketError::Idle
+    } else {
+        PacketError::TimedOut
+    }
+}
+
+/// The payload length of the (possibly split) packet at the start of `i`, as far as its headers
+/// have been received.
+fn declared_len(mut i: &[u8]) -> usize {
+    let mut total = 0;
+    while i.len() >= 4 {
+        let len = LittleEndian::read_u24(i) as usize;
+        total += len;
+        if len < U24_MAX || i.len() < 4 + len {
+            break;
+        }
+        i = &i[4 + len..];
+    }
+    total
+}
+
+/// Returns true if the packets that make up `i` are numbered consecutively from `seq`.
+fn in_sequence(mut i: &[u8], mut seq: u8) -> bool {
+    while i.len() >= 4 {
+        if i[3] != seq {
+            return false;
+        }
+        seq = seq.wrapping_add(1);
+        i = &i[4 + LittleEndian::read_u24(i) as usize..];
+    }
+    true
+}
+
 pub fn fullpacket(i: &[u8]) -> nom::IResult<&[u8], (u8, &[u8])> {
     let (i, _) = nom::bytes::complete::tag(&[0xff, 0xff, 0xff])(i)?;
     let (i, seq) = nom::bytes::complete::take(1u8)(i)?;
@@ -161,7 +432,7 @@ pub fn onepacket(i: &[u8]) -> nom::IResult<&[u8], (u8, &[u8])> {
 }
 
 // Clone because of https://github.com/Geal/nom/issues/1008
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Packet(Vec<u8>);
 
 impl Packet {
@@ -194,9 +465,8 @@ fn packet(i: &[u8]) -> nom::IResult<&[u8], (u8, Packet)> {
             nom::multi::fold_many0(
                 fullpacket,
                 || (0, None),
-                |(seq, pkt): (_, Option<Packet>), (nseq, p)| {
+                |(_, pkt): (_, Option<Packet>), (nseq, p)| {
                     let pkt = if let Some(mut pkt) = pkt {
-                        assert_eq!(nseq, seq + 1);
                         pkt.extend(p);
                         Some(pkt)
                     } else {
@@ -210,7 +480,6 @@ fn packet(i: &[u8]) -> nom::IResult<&[u8], (u8, Packet)> {
         move |(full, last)| {
             let seq = last.0;
             let pkt = if let Some(mut pkt) = full.1 {
-                assert_eq!(last.0, full.0 + 1);
                 pkt.extend(last.1);
                 pkt
             } else {
@@ -224,6 +493,138 @@ fn packet(i: &[u8]) -> nom::IResult<&[u8], (u8, Packet)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Reads from `input` and records everything written into a shared buffer.
+    struct Sink {
+        input: io::Cursor<Vec<u8>>,
+        output: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for Sink {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn conn(input: Vec<u8>) -> (PacketConn<Sink>, Rc<RefCell<Vec<u8>>>) {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let sink = Sink {
+            input: io::Cursor::new(input),
+            output: Rc::clone(&output),
+        };
+        (PacketConn::new(sink), output)
+    }
+
+    fn written(len: usize) -> Vec<u8> {
+        let (mut c, output) = conn(Vec::new());
+        c.write_all(&vec![0x42; len]).unwrap();
+        c.end_packet().unwrap();
+        let out = output.borrow().clone();
+        out
+    }
+
+    #[test]
+    fn test_write_just_below_max() {
+        let out = written(U24_MAX - 1);
+        assert_eq!(out.len(), 4 + U24_MAX - 1);
+        assert_eq!(&out[..4], &[0xfe, 0xff, 0xff, 0]);
+    }
+
+    #[test]
+    fn test_write_exact_max() {
+        let out = written(U24_MAX);
+        assert_eq!(out.len(), 4 + U24_MAX + 4);
+        assert_eq!(&out[..4], &[0xff, 0xff, 0xff, 0]);
+        assert_eq!(&out[4 + U24_MAX..], &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_write_more_than_max() {
+        let out = written(U24_MAX + 1);
+        assert_eq!(out.len(), 4 + U24_MAX + 4 + 1);
+        assert_eq!(&out[..4], &[0xff, 0xff, 0xff, 0]);
+        assert_eq!(&out[4 + U24_MAX..], &[1, 0, 0, 1, 0x42]);
+    }
+
+    #[test]
+    fn test_write_too_large() {
+        let (mut c, output) = conn(Vec::new());
+        c.set_max_payload(DEFAULT_MAX_PAYLOAD, 4);
+        c.write_all(&[1, 2]).unwrap();
+        let e = c.write_all(&[3, 4, 5]).unwrap_err();
+        assert_eq!(packet_error(&e), Some(PacketError::TooLarge));
+        c.write_all(&[6]).unwrap();
+        c.end_packet().unwrap();
+        assert_eq!(&output.borrow()[..], &[1, 0, 0, 0, 6]);
+    }
+
+    #[test]
+    fn test_write_too_large_after_a_full_packet() {
+        let (mut c, output) = conn(Vec::new());
+        c.set_max_payload(DEFAULT_MAX_PAYLOAD, U24_MAX + 10);
+        c.write_all(&vec![0x42; U24_MAX]).unwrap();
+        let e = c.write_all(&[0x42; 20]).unwrap_err();
+        assert_eq!(packet_error(&e), Some(PacketError::TooLarge));
+        assert_eq!(output.borrow().len(), 4 + U24_MAX);
+
+        // the client has seen the start of the payload, so nothing else can follow it
+        assert!(c.write_all(&[1]).is_err());
+        c.end_packet().unwrap();
+        c.flush().unwrap();
+        assert_eq!(output.borrow().len(), 4 + U24_MAX);
+    }
+
+    #[test]
+    fn test_read_max() {
+        let (mut c, _) = conn(vec![4, 0, 0, 0, 1, 2, 3, 4]);
+        c.set_max_payload(4, DEFAULT_MAX_PAYLOAD);
+        let (seq, p) = c.next().unwrap().unwrap();
+        assert_eq!(seq, 0);
+        assert_eq!(&*p, &[1, 2, 3, 4]);
+
+        let (mut c, _) = conn(vec![5, 0, 0, 0, 1, 2, 3, 4, 5]);
+        c.set_max_payload(4, DEFAULT_MAX_PAYLOAD);
+        assert_eq!(
+            packet_error(&c.next().unwrap_err()),
+            Some(PacketError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_read_too_large_before_buffering() {
+        // only the header of a huge packet arrives; we must not wait for (or buffer) the rest
+        let (mut c, _) = conn(vec![0xff, 0xff, 0xff, 0, 1, 2, 3]);
+        c.set_max_payload(1024, DEFAULT_MAX_PAYLOAD);
+        assert_eq!(
+            packet_error(&c.next().unwrap_err()),
+            Some(PacketError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_read_split_exact_max() {
+        let mut data = vec![0xff, 0xff, 0xff, 0];
+        data.extend(&[0; U24_MAX][..]);
+        data.extend(&[0, 0, 0, 1]);
+        let (mut c, _) = conn(data);
+        let (seq, p) = c.next().unwrap().unwrap();
+        assert_eq!(seq, 1);
+        assert_eq!(p.len(), U24_MAX);
+        assert!(c.next().unwrap().is_none());
+    }
 
     #[test]
     fn test_one_ping() {
@@ -281,4 +682,106 @@ mod tests {
         assert_eq!(&p.1[..U24_MAX], &[0; U24_MAX][..]);
         assert_eq!(&p.1[U24_MAX..], &[0x10]);
     }
+
+    #[test]
+    fn test_read_out_of_order() {
+        let (mut c, _) = conn(vec![1, 0, 0, 3, 0x0e]);
+        assert_eq!(
+            packet_error(&c.next().unwrap_err()),
+            Some(PacketError::OutOfOrder)
+        );
+
+        let (mut c, output) = conn(vec![1, 0, 0, 3, 0x0e]);
+        c.set_strict_sequence(false);
+        let (seq, p) = c.next().unwrap().unwrap();
+        assert_eq!((seq, &*p), (3, &[0x0e][..]));
+        c.write_all(&[0x00]).unwrap();
+        c.end_packet().unwrap();
+        assert_eq!(&output.borrow()[..], &[1, 0, 0, 4, 0x00]);
+    }
+
+    #[test]
+    fn test_read_split_out_of_order() {
+        let mut data = vec![0xff, 0xff, 0xff, 0];
+        data.extend(&[0; U24_MAX][..]);
+        data.extend(&[1, 0, 0, 2, 0x10]);
+        let (mut c, _) = conn(data.clone());
+        assert_eq!(
+            packet_error(&c.next().unwrap_err()),
+            Some(PacketError::OutOfOrder)
+        );
+
+        let (mut c, _) = conn(data);
+        c.set_strict_sequence(false);
+        let (seq, p) = c.next().unwrap().unwrap();
+        assert_eq!(seq, 2);
+        assert_eq!(p.len(), U24_MAX + 1);
+    }
+
+    #[test]
+    fn test_read_sequence_wraps() {
+        let (mut c, _) = conn(vec![1, 0, 0, 255, 0x0e]);
+        c.set_seq(255);
+        let (seq, _) = c.next().unwrap().unwrap();
+        assert_eq!(seq, 255);
+        assert_eq!(c.seq, 0);
+    }
+
+    #[test]
+    fn test_read_before_deadline() {
+        let (mut c, _) = conn(vec![1, 0, 0, 0, 0x0e]);
+        c.set_deadline(Some(Instant::now() + Duration::from_secs(60)))
+            .unwrap();
+        let (_, p) = c.next().unwrap().unwrap();
+        assert_eq!(&*p, &[0x0e][..]);
+    }
+
+    #[test]
+    fn test_read_after_deadline() {
+        let (mut c, _) = conn(vec![1, 0, 0, 0, 0x0e]);
+        c.set_deadline(Some(Instant::now())).unwrap();
+        let e = c.next().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(packet_error(&e), Some(PacketError::Idle));
+
+        // nothing was consumed, so the packet can still be read without a deadline
+        c.set_deadline(None).unwrap();
+        assert!(c.next().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_timeout_runs_out() {
+        // sends `.0`, and then nothing more
+        struct Stalled(Vec<u8>);
+        impl Read for Stalled {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                let n = std::cmp::min(buf.len(), self.0.len());
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0.drain(..n);
+                Ok(n)
+            }
+        }
+        impl Write for Stalled {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let stalled = |sent: &[u8]| {
+            let mut c = PacketConn::new(Stalled(sent.to_vec()));
+            c.set_read_timeouts(|_, _| Ok(()));
+            c.set_deadline(Some(Instant::now() + Duration::from_secs(60)))
+                .unwrap();
+            packet_error(&c.next().unwrap_err())
+        };
+        assert_eq!(stalled(&[]), Some(PacketError::Idle));
+        assert_eq!(stalled(&[1, 0]), Some(PacketError::TimedOut));
+        assert_eq!(stalled(&[2, 0, 0, 0, 0x03]), Some(PacketError::TimedOut));
+    }
 }
@@ -1,7 +1,148 @@
 use crate::myc;
+use crate::myc::constants::ColumnType;
+use crate::myc::io::ReadMysqlExt;
 use crate::{StatementData, Value};
+use byteorder::ReadBytesExt;
+use chrono::FixedOffset;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::io;
+
+// COM_STMT_EXECUTE flag set by clients that send a parameter count (for query attributes)
+const PARAMETER_COUNT_AVAILABLE: u8 = 0x08;
+
+/// Query attributes sent by the client along with a query or an `EXECUTE` command.
+///
+/// Clients that support `CLIENT_QUERY_ATTRIBUTES` (MySQL 8.0.23 and later) can attach named
+/// values to each query, which is commonly used to propagate tracing information. The attributes
+/// of the current query are available through
+/// [`QueryResultWriter::attributes`](struct.QueryResultWriter.html#method.attributes).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryAttributes<'a>(Vec<(Cow<'a, str>, Value<'a>)>);
+
+impl<'a> QueryAttributes<'a> {
+    /// Returns the value of the first attribute named `name`.
+    pub fn get(&self, name: &str) -> Option<Value<'a>> {
+        self.0.iter().find(|(n, _)| n == name).map(|&(_, v)| v)
+    }
+
+    /// Iterate over the attributes in the order the client sent them.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Value<'a>)> + '_ {
+        self.0.iter().map(|(n, v)| (&**n, *v))
+    }
+
+    /// The number of attributes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the client sent no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if len > input.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "EOF while reading parameters",
+        ));
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+/// The type of a parameter as bound by the client.
+struct Bound<'a> {
+    coltype: ColumnType,
+    unsigned: bool,
+    // only sent along with query attributes
+    name: &'a [u8],
+}
+
+/// The parameter block shared by `EXECUTE` commands and query attributes: a NULL bitmap, the
+/// parameter types (if the client sent new ones), and the values.
+struct Block<'a> {
+    nullmap: &'a [u8],
+    types: Option<Vec<Bound<'a>>>,
+    values: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    fn parse(mut input: &'a [u8], count: usize, named: bool) -> io::Result<Self> {
+        if count == 0 {
+            return Ok(Block {
+                nullmap: &[],
+                types: None,
+                values: input,
+            });
+        }
+
+        let nullmap = take(&mut input, count.div_ceil(8))?;
+        let mut types = None;
+        if !input.is_empty() && input.read_u8()? != 0x00 {
+            let mut bound = Vec::with_capacity(count);
+            for _ in 0..count {
+                let ct = input.read_u8()?;
+                let flags = input.read_u8()?;
+                let name = if named {
+                    let len = input.read_lenenc_int()?;
+                    take(&mut input, len as usize)?
+                } else {
+                    &[]
+                };
+                bound.push(Bound {
+                    coltype: ColumnType::try_from(ct)
+                        .map_err(|_| invalid(&format!("bad column type 0x{:x}", ct)))?,
+                    unsigned: (flags & 128) != 0,
+                    name,
+                });
+            }
+            types = Some(bound);
+        }
+        Ok(Block {
+            nullmap,
+            types,
+            values: input,
+        })
+    }
+
+    fn is_null(&self, i: usize) -> bool {
+        (self.nullmap[i / 8] & 1u8 << (i % 8)) != 0
+    }
+}
+
+/// Parse the query attributes that prefix a `COM_QUERY` payload when `CLIENT_QUERY_ATTRIBUTES`
+/// has been negotiated, and return them along with the query text.
+pub(crate) fn query_attributes(mut input: &[u8]) -> io::Result<(QueryAttributes<'_>, &[u8])> {
+    let count = input.read_lenenc_int()? as usize;
+    let _sets = input.read_lenenc_int()?;
+    let block = Block::parse(input, count, true)?;
+    let types = match block.types {
+        Some(ref types) => &types[..],
+        None if count == 0 => &[],
+        None => return Err(invalid("query attributes sent without types")),
+    };
+
+    let mut values = block.values;
+    let mut attributes = Vec::with_capacity(count);
+    for (i, t) in types.iter().enumerate() {
+        let value = if block.is_null(i) {
+            Value::null()
+        } else {
+            Value::parse_from(&mut values, t.coltype, t.unsigned)?
+        };
+        attributes.push((String::from_utf8_lossy(t.name), value));
+    }
+    Ok((QueryAttributes(attributes), values))
+}
 
 /// A `ParamParser` decodes query parameters included in a client's `EXECUTE` command given
 /// type information for the expected parameters.
@@ -10,19 +151,92 @@ use std::convert::TryFrom;
 /// provided parameters.
 pub struct ParamParser<'a> {
     pub(crate) params: u16,
+    pub(crate) nullmap: &'a [u8],
     pub(crate) bytes: &'a [u8],
     pub(crate) long_data: &'a HashMap<u16, Vec<u8>>,
-    pub(crate) bound_types: &'a mut Vec<(myc::constants::ColumnType, bool)>,
+    pub(crate) bound_types: &'a [(myc::constants::ColumnType, bool)],
+    pub(crate) time_zone: FixedOffset,
+    // the names of the parameters, if the client sent any along with their types
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub(crate) names: Vec<&'a [u8]>,
 }
 
 impl<'a> ParamParser<'a> {
-    pub(crate) fn new(input: &'a [u8], stmt: &'a mut StatementData) -> Self {
-        ParamParser {
-            params: stmt.params,
-            bytes: input,
-            long_data: &stmt.long_data,
-            bound_types: &mut stmt.bound_types,
+    /// Parse the parameters of an `EXECUTE` command, along with any query attributes that follow
+    /// them.
+    pub(crate) fn new(
+        mut input: &'a [u8],
+        flags: u8,
+        query_attributes: bool,
+        stmt: &'a mut StatementData,
+        time_zone: FixedOffset,
+    ) -> io::Result<(Self, QueryAttributes<'a>)> {
+        let params = usize::from(stmt.params);
+        let count = if query_attributes && flags & PARAMETER_COUNT_AVAILABLE != 0 {
+            input.read_lenenc_int()? as usize
+        } else {
+            params
+        };
+        if count < params {
+            return Err(invalid("EXECUTE sent fewer parameters than were prepared"));
+        }
+
+        let block = Block::parse(input, count, query_attributes)?;
+        let mut names = Vec::new();
+        if let Some(ref types) = block.types {
+            names.extend(types[..params].iter().map(|t| t.name));
+            stmt.bound_types.clear();
+            stmt.bound_types
+                .extend(types[..params].iter().map(|t| (t.coltype, t.unsigned)));
+        }
+        let stmt: &'a StatementData = stmt;
+        if stmt.bound_types.len() < params {
+            return Err(invalid("EXECUTE sent without parameter types"));
+        }
+
+        // parse the parameters once up front, so that iterating over them cannot fail
+        let mut values = block.values;
+        for i in 0..params {
+            if block.is_null(i) || stmt.long_data.contains_key(&(i as u16)) {
+                continue;
+            }
+            let (ct, unsigned) = stmt.bound_types[i];
+            Value::parse_from(&mut values, ct, unsigned)?;
         }
+
+        let mut attributes = Vec::new();
+        if count > params {
+            let types = block
+                .types
+                .as_ref()
+                .ok_or_else(|| invalid("query attributes sent without types"))?;
+            let mut values = block.values;
+            for (i, t) in types.iter().enumerate() {
+                let value = if block.is_null(i) {
+                    Value::null()
+                } else if i < params && stmt.long_data.contains_key(&(i as u16)) {
+                    continue;
+                } else {
+                    Value::parse_from(&mut values, t.coltype, t.unsigned)?
+                };
+                if i >= params {
+                    attributes.push((String::from_utf8_lossy(t.name), value));
+                }
+            }
+        }
+
+        Ok((
+            ParamParser {
+                params: stmt.params,
+                nullmap: block.nullmap,
+                bytes: block.values,
+                long_data: &stmt.long_data,
+                bound_types: &stmt.bound_types,
+                time_zone,
+                names,
+            },
+            QueryAttributes(attributes),
+        ))
     }
 }
 
@@ -33,10 +247,11 @@ impl<'a> IntoIterator for ParamParser<'a> {
         Params {
             params: self.params,
             input: self.bytes,
-            nullmap: None,
+            nullmap: self.nullmap,
             col: 0,
             long_data: self.long_data,
             bound_types: self.bound_types,
+            time_zone: self.time_zone,
         }
     }
 }
@@ -45,10 +260,11 @@ impl<'a> IntoIterator for ParamParser<'a> {
 pub struct Params<'a> {
     params: u16,
     input: &'a [u8],
-    nullmap: Option<&'a [u8]>,
+    nullmap: &'a [u8],
     col: u16,
     long_data: &'a HashMap<u16, Vec<u8>>,
-    bound_types: &'a mut Vec<(myc::constants::ColumnType, bool)>,
+    bound_types: &'a [(myc::constants::ColumnType, bool)],
+    time_zone: FixedOffset,
 }
 
 /// A single parameter value provided by a client when issuing an `EXECUTE` command.
@@ -62,61 +278,106 @@ pub struct ParamValue<'a> {
 impl<'a> Iterator for Params<'a> {
     type Item = ParamValue<'a>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.nullmap.is_none() {
-            let nullmap_len = (self.params as usize + 7) / 8;
-            let (nullmap, rest) = self.input.split_at(nullmap_len);
-            self.nullmap = Some(nullmap);
-            self.input = rest;
-
-            if !rest.is_empty() && rest[0] != 0x00 {
-                let (typmap, rest) = rest[1..].split_at(2 * self.params as usize);
-                self.bound_types.clear();
-                for i in 0..self.params as usize {
-                    self.bound_types.push((
-                        myc::constants::ColumnType::try_from(typmap[2 * i as usize])
-                            .unwrap_or_else(|e| {
-                                panic!("bad column type 0x{:x}: {}", typmap[2 * i as usize], e)
-                            }),
-                        (typmap[2 * i as usize + 1] & 128) != 0,
-                    ));
-                }
-                self.input = rest;
-            }
-        }
-
         if self.col >= self.params {
             return None;
         }
-        let pt = &self.bound_types[self.col as usize];
+        let pt = self.bound_types.get(self.col as usize)?;
 
         // https://web.archive.org/web/20170404144156/https://dev.mysql.com/doc/internals/en/null-bitmap.html
         // NULL-bitmap-byte = ((field-pos + offset) / 8)
         // NULL-bitmap-bit  = ((field-pos + offset) % 8)
-        if let Some(nullmap) = self.nullmap {
-            let byte = self.col as usize / 8;
-            if byte >= nullmap.len() {
-                return None;
-            }
-            if (nullmap[byte] & 1u8 << (self.col % 8)) != 0 {
-                self.col += 1;
-                return Some(ParamValue {
-                    value: Value::null(),
-                    coltype: pt.0,
-                });
-            }
-        } else {
-            unreachable!();
+        let byte = self.col as usize / 8;
+        if byte >= self.nullmap.len() {
+            return None;
+        }
+        if (self.nullmap[byte] & 1u8 << (self.col % 8)) != 0 {
+            self.col += 1;
+            return Some(ParamValue {
+                value: Value::null().in_time_zone(self.time_zone),
+                coltype: pt.0,
+            });
         }
 
         let v = if let Some(data) = self.long_data.get(&self.col) {
             Value::bytes(&data[..])
         } else {
-            Value::parse_from(&mut self.input, pt.0, pt.1).unwrap()
+            Value::parse_from(&mut self.input, pt.0, pt.1).ok()?
         };
         self.col += 1;
         Some(ParamValue {
-            value: v,
+            value: v.in_time_zone(self.time_zone),
             coltype: pt.0,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValueInner;
+
+    #[test]
+    fn it_parses_query_attributes() {
+        let payload = b"\x01\x01\x00\x01\xfe\x00\x0btraceparent\x0600-abcSELECT 1";
+        let (attributes, query) = query_attributes(&payload[..]).unwrap();
+        assert_eq!(query, b"SELECT 1");
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(
+            attributes.get("traceparent").map(Value::into_inner),
+            Some(ValueInner::Bytes(b"00-abc"))
+        );
+
+        let (attributes, query) = query_attributes(&b"\x00\x01SELECT 1"[..]).unwrap();
+        assert!(attributes.is_empty());
+        assert_eq!(query, b"SELECT 1");
+    }
+
+    #[test]
+    fn it_splits_attributes_from_execute_params() {
+        let mut stmt = StatementData {
+            params: 1,
+            ..Default::default()
+        };
+        let payload = b"\x02\x00\x01\x08\x00\x00\xfe\x00\x01x\x2a\x00\x00\x00\x00\x00\x00\x00\x01y";
+        let (params, attributes) = ParamParser::new(
+            &payload[..],
+            PARAMETER_COUNT_AVAILABLE,
+            true,
+            &mut stmt,
+            FixedOffset::east_opt(0).unwrap(),
+        )
+        .unwrap();
+        let params: Vec<_> = params.into_iter().map(|p| p.value.into_inner()).collect();
+        assert_eq!(params, vec![ValueInner::Int(42)]);
+        assert_eq!(
+            attributes.iter().collect::<Vec<_>>(),
+            vec![("x", Value::bytes(b"y"))]
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_execute_params() {
+        let mut stmt = StatementData {
+            params: 1,
+            ..Default::default()
+        };
+        fn new(payload: &[u8], stmt: &mut StatementData) -> io::Result<()> {
+            let tz = FixedOffset::east_opt(0).unwrap();
+            ParamParser::new(payload, 0, false, stmt, tz).map(|_| ())
+        }
+        // a DATE of length 3
+        assert!(new(b"\x00\x01\x0a\x00\x03\xe8\x07\x02", &mut stmt).is_err());
+        // a LONGLONG cut short
+        assert!(new(b"\x00\x01\x08\x00\x2a\x00", &mut stmt).is_err());
+        // no types, neither now nor in an earlier EXECUTE
+        stmt.bound_types.clear();
+        assert!(new(b"\x00\x00", &mut stmt).is_err());
+        // ...which also goes for parameters that are all NULL
+        assert!(new(b"\x01\x00", &mut stmt).is_err());
+        assert!(new(
+            b"\x00\x01\x08\x00\x2a\x00\x00\x00\x00\x00\x00\x00",
+            &mut stmt
+        )
+        .is_ok());
+    }
+}
@@ -1,9 +1,13 @@
+use crate::charset::{self, Charset};
+use crate::commands::MARIADB_CLIENT_PROGRESS;
 use crate::myc::constants::{ColumnFlags, StatusFlags};
 use crate::packet::PacketConn;
-use crate::value::ToMysqlValue;
+use crate::params::QueryAttributes;
+use crate::value::{ToMysqlValue, WriteContext};
 use crate::writers;
 use crate::{Column, ErrorKind, StatementData};
 use byteorder::WriteBytesExt;
+use chrono::FixedOffset;
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::io::{self, Read, Write};
@@ -11,11 +15,14 @@ use std::io::{self, Read, Write};
 /// Convenience type for responding to a client `USE <db>` command.
 pub struct InitWriter<'a, W: Read + Write> {
     pub(crate) writer: &'a mut PacketConn<W>,
+    pub(crate) schema: &'a mut Option<String>,
+    pub(crate) requested: &'a str,
 }
 
 impl<'a, W: Read + Write + 'a> InitWriter<'a, W> {
     /// Tell client that database context has been changed
     pub fn ok(self) -> io::Result<()> {
+        *self.schema = Some(self.requested.to_owned());
         writers::write_ok_packet(self.writer, 0, 0, StatusFlags::empty())
     }
 
@@ -104,18 +111,45 @@ pub struct QueryResultWriter<'a, W: Read + Write> {
     // XXX: specialization instead?
     pub(crate) is_bin: bool,
     pub(crate) writer: &'a mut PacketConn<W>,
+    pub(crate) charset: Charset,
+    time_zone: FixedOffset,
+    attributes: QueryAttributes<'a>,
     last_end: Option<Finalizer>,
 }
 
 impl<'a, W: Read + Write> QueryResultWriter<'a, W> {
-    pub(crate) fn new(writer: &'a mut PacketConn<W>, is_bin: bool) -> Self {
+    pub(crate) fn new(
+        writer: &'a mut PacketConn<W>,
+        is_bin: bool,
+        charset: Charset,
+        time_zone: FixedOffset,
+        attributes: QueryAttributes<'a>,
+    ) -> Self {
         QueryResultWriter {
             is_bin,
             writer,
+            charset,
+            time_zone,
+            attributes,
             last_end: None,
         }
     }
 
+    /// The query attributes the client sent along with this query.
+    ///
+    /// This is always empty for clients that do not support `CLIENT_QUERY_ATTRIBUTES`.
+    pub fn attributes(&self) -> &QueryAttributes<'a> {
+        &self.attributes
+    }
+
+    /// The offset of the client's session `time_zone`.
+    ///
+    /// Zoned date and time values are written in this time zone. It is UTC if the session time
+    /// zone is not a fixed offset.
+    pub fn time_zone(&self) -> FixedOffset {
+        self.time_zone
+    }
+
     fn finalize(&mut self, more_exists: bool) -> io::Result<()> {
         let mut status = StatusFlags::empty();
         if more_exists {
@@ -169,6 +203,33 @@ impl<'a, W: Read + Write> QueryResultWriter<'a, W> {
         writers::write_err(kind, msg.borrow(), self.writer)
     }
 
+    /// Report the progress of a long-running statement to the client.
+    ///
+    /// `stage` counts from 1 up to `max_stage`, and `percent` is the progress within the current
+    /// stage, from 0 to 100. This must be called before the results of the statement are written.
+    /// Only MariaDB clients that ask for progress reports receive them; for all other clients
+    /// this does nothing.
+    pub fn report_progress(
+        &mut self,
+        stage: u8,
+        max_stage: u8,
+        percent: f64,
+        stage_name: &str,
+    ) -> io::Result<()> {
+        if !self.writer.has_mariadb_capability(MARIADB_CLIENT_PROGRESS) {
+            return Ok(());
+        }
+        let progress = (percent.clamp(0.0, 100.0) * 1000.0).round() as u32;
+        writers::write_progress_packet(
+            stage,
+            max_stage.max(stage),
+            progress,
+            stage_name.as_bytes(),
+            self.writer,
+        )?;
+        self.writer.flush()
+    }
+
     /// Send the last bits of the last resultset to the client, and indicate that there are no more
     /// resultsets coming.
     pub fn no_more_results(mut self) -> io::Result<()> {
@@ -199,11 +260,11 @@ pub struct RowWriter<'a, W: Read + Write> {
     result: Option<QueryResultWriter<'a, W>>,
     bitmap_len: usize,
     data: Vec<u8>,
-    columns: &'a [Column],
+    pub(crate) columns: &'a [Column],
 
     // next column to write for the current row
     // NOTE: (ab)used to track number of *rows* for a zero-column resultset
-    col: usize,
+    pub(crate) col: usize,
 
     finished: bool,
 }
@@ -231,10 +292,20 @@ where
         Ok(rw)
     }
 
+    #[cfg(feature = "async")]
+    pub(crate) fn writer(&mut self) -> &mut PacketConn<W> {
+        self.result.as_mut().unwrap().writer
+    }
+
+    fn transcodes(&self, c: &Column) -> bool {
+        self.result.as_ref().unwrap().charset != Charset::Utf8 && charset::is_text_column(c)
+    }
+
     #[inline]
     fn start(&mut self) -> io::Result<()> {
         if !self.columns.is_empty() {
-            writers::column_definitions(self.columns, self.result.as_mut().unwrap().writer)?;
+            let result = self.result.as_mut().unwrap();
+            writers::column_definitions(self.columns, result.writer, result.charset)?;
         }
         Ok(())
     }
@@ -258,6 +329,7 @@ where
             return Ok(());
         }
 
+        let tz = self.result.as_ref().unwrap().time_zone;
         if self.result.as_mut().unwrap().is_bin {
             if self.col == 0 {
                 self.result.as_mut().unwrap().writer.write_u8(0x00)?;
@@ -288,11 +360,29 @@ where
                     // NULL-bitmap-bit  = ((field-pos + offset) % 8)
                     self.data[(self.col + 2) / 8] |= 1u8 << ((self.col + 2) % 8);
                 }
+            } else if self.transcodes(c) {
+                let mut encoded = Vec::new();
+                v.to_mysql_bin_for(&mut encoded, &WriteContext::new(c, tz))?;
+                charset::transcode_lenenc(
+                    self.result.as_ref().unwrap().charset,
+                    &encoded,
+                    &mut self.data,
+                )?;
             } else {
-                v.to_mysql_bin(&mut self.data, c)?;
+                v.to_mysql_bin_for(&mut self.data, &WriteContext::new(c, tz))?;
             }
         } else {
-            v.to_mysql_text(self.result.as_mut().unwrap().writer)?;
+            let columns: &'a [Column] = self.columns;
+            let c = columns.get(self.col);
+            let transcode = matches!(c, Some(c) if self.transcodes(c));
+            let result = self.result.as_mut().unwrap();
+            if transcode {
+                let mut encoded = Vec::new();
+                write_text(&v, &mut encoded, c, tz)?;
+                charset::transcode_lenenc(result.charset, &encoded, result.writer)?;
+            } else {
+                write_text(&v, result.writer, c, tz)?;
+            }
         }
         self.col += 1;
         Ok(())
@@ -405,3 +495,14 @@ impl<'a, W: Read + Write + 'a> Drop for RowWriter<'a, W> {
         self.finish_inner(true).unwrap();
     }
 }
+
+fn write_text<T, W>(v: &T, w: &mut W, c: Option<&Column>, tz: FixedOffset) -> io::Result<()>
+where
+    T: ToMysqlValue,
+    W: Write,
+{
+    match c {
+        Some(c) => v.to_mysql_text_for(w, &WriteContext::new(c, tz)),
+        None => v.to_mysql_text(w),
+    }
+}
@@ -0,0 +1,815 @@
+//! Serializing resultset rows with serde, and describing their columns.
+
+use crate::myc;
+use crate::{Column, ColumnFlags, ColumnType, RowWriter};
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::ser::{self, Impossible, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Carries an `io::Error` through serde, which needs an error type of its own.
+#[derive(Debug)]
+struct Error(io::Error);
+
+impl Error {
+    fn in_field(self, name: &str) -> Self {
+        Error(io::Error::new(
+            self.0.kind(),
+            format!("field `{}`: {}", name, self.0),
+        ))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(io::Error::new(io::ErrorKind::InvalidData, msg.to_string()))
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        <Error as ser::Error>::custom(msg)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error(e)
+    }
+}
+
+impl<'a, W> RowWriter<'a, W>
+where
+    W: Read + Write + 'a,
+{
+    /// Write a struct, tuple, sequence or map as a single row of this resultset.
+    ///
+    /// Each field, element or map value is written to the next column with
+    /// [`write_col`](RowWriter::write_col), so they must come in the order of the columns given
+    /// to [`QueryResultWriter::start`](crate::QueryResultWriter::start); field names and map keys
+    /// are not looked at. [`columns_for`] describes columns that match a struct's fields.
+    ///
+    /// Fields may be numbers, booleans, strings, byte strings, unit enum variants (which are
+    /// written as their names), or options of those. Nested structs and sequences cannot be
+    /// written to a single column.
+    ///
+    /// A row whose length serde knows up front, like any struct or tuple, is rejected without
+    /// writing anything if it does not have one field for each column left in the row.
+    ///
+    /// ```no_run
+    /// # use msql_srv::QueryResultWriter;
+    /// # use std::io;
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct User<'a> {
+    ///     id: u32,
+    ///     name: &'a str,
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// # fn f<W: io::Read + io::Write>(users: &[User<'_>], results: QueryResultWriter<'_, W>) -> io::Result<()> {
+    /// let columns = msql_srv::columns_for::<User>()?;
+    /// let mut rows = results.start(&columns)?;
+    /// for user in users {
+    ///     rows.serialize(user)?;
+    /// }
+    /// rows.finish()
+    /// # }
+    /// ```
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, row: &T) -> io::Result<()> {
+        row.serialize(RowSerializer { row: self })
+            .map_err(|Error(e)| e)
+    }
+}
+
+fn not_a_row() -> Error {
+    ser::Error::custom("only structs, tuples, sequences and maps can be written as rows")
+}
+
+fn not_a_column() -> Error {
+    ser::Error::custom("nested structs, sequences and maps cannot be written to a single column")
+}
+
+macro_rules! reject {
+    ($err:ident: $($method:ident($($arg:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err($err())
+            }
+        )*
+    };
+}
+
+struct RowSerializer<'r, 'a, W: Read + Write> {
+    row: &'r mut RowWriter<'a, W>,
+}
+
+impl<'r, 'a, W: Read + Write + 'a> RowSerializer<'r, 'a, W> {
+    fn expect_len(self, len: Option<usize>) -> Result<Self, Error> {
+        let row = &self.row;
+        let left = row.columns.len().saturating_sub(row.col);
+        match len {
+            Some(len) if !row.columns.is_empty() && len != left => Err(Error(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "row has {} fields, but {} columns are left to write",
+                    len, left
+                ),
+            ))),
+            _ => Ok(self),
+        }
+    }
+
+    fn column<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(ColumnSerializer { row: self.row })
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::Serializer for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    reject! { not_a_row:
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), Error> {
+        Err(not_a_row())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        self.expect_len(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self, Error> {
+        self.expect_len(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Self, Error> {
+        self.expect_len(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(not_a_row())
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        self.expect_len(len)
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self, Error> {
+        self.expect_len(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(not_a_row())
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::SerializeSeq for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(self.row.end_row()?)
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::SerializeTuple for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(self.row.end_row()?)
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::SerializeTupleStruct for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(self.row.end_row()?)
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::SerializeMap for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(self.row.end_row()?)
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::SerializeStruct for RowSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.column(value).map_err(|e| e.in_field(key))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(self.row.end_row()?)
+    }
+}
+
+/// Writes a single field to the next column.
+struct ColumnSerializer<'r, 'a, W: Read + Write> {
+    row: &'r mut RowWriter<'a, W>,
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ColumnSerializer<'r, 'a, W> {
+    // integers go through mysql_common's values, which are written as whichever integer type
+    // the column has room for, whatever the type of the field was
+    fn int(self, n: i64) -> Result<(), Error> {
+        Ok(self.row.write_col(myc::value::Value::Int(n))?)
+    }
+}
+
+impl<'r, 'a, W: Read + Write + 'a> ser::Serializer for ColumnSerializer<'r, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.int(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.int(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        match i64::try_from(v) {
+            Ok(n) => self.int(n),
+            Err(_) => Ok(self.row.write_col(v)?),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        Ok(self.row.write_col(v)?)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        Ok(self.row.write_col(v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        Ok(self.row.write_col(&*v.encode_utf8(&mut [0; 4]))?)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        Ok(self.row.write_col(v)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        Ok(self.row.write_col(v)?)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(self.row.write_col(None::<u8>)?)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(not_a_column())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(not_a_column())
+    }
+}
+
+/// Describe the columns of a resultset whose rows are `T`s.
+///
+/// `T` must be a struct or a tuple. There is one column per field, named after the field (or,
+/// for tuples, after its position), with a column type to match: for example, a `u32` field
+/// becomes an unsigned `MYSQL_TYPE_LONG` column, a string becomes `MYSQL_TYPE_VAR_STRING`, and
+/// a byte string a binary `MYSQL_TYPE_BLOB`. Fields are `NOT NULL` unless they are `Option`s, and
+/// unit enums are string columns. Rows can then be written with
+/// [`RowWriter::serialize`].
+///
+/// The field types are learned from `T`'s `Deserialize` implementation, so `T` must implement
+/// it even if only its `Serialize` implementation is used to write rows. This only works for
+/// fields that `RowWriter::serialize` can write; types that deserialize from strings they then
+/// parse, such as dates, cannot be described.
+///
+/// Since rows are written with `Serialize`, the two implementations must agree on the fields:
+/// serde attributes that only apply one way, such as `skip_serializing`, `skip_deserializing` or
+/// a `serialize_with` that changes a field's type, make the columns disagree with the rows.
+/// `RowWriter::serialize` rejects rows with the wrong number of fields, but a field written as a
+/// different type than it was described as is only caught if the column cannot hold it.
+pub fn columns_for<T: Deserialize<'static>>() -> io::Result<Vec<Column>> {
+    let mut columns = Vec::new();
+    T::deserialize(Describer {
+        columns: &mut columns,
+    })
+    .map_err(|Error(e)| e)?;
+    Ok(columns)
+}
+
+fn not_a_resultset() -> Error {
+    de::Error::custom("only structs and tuples can describe the columns of a resultset")
+}
+
+/// Traces a struct or tuple's `Deserialize` implementation to find its fields.
+struct Describer<'c> {
+    columns: &'c mut Vec<Column>,
+}
+
+impl<'c> Describer<'c> {
+    fn fields<V: Visitor<'static>>(
+        self,
+        names: Vec<String>,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(Fields {
+            columns: self.columns,
+            names: names.into_iter(),
+        })
+    }
+}
+
+impl<'c> de::Deserializer<'static> for Describer<'c> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'static>>(self, _: V) -> Result<V::Value, Error> {
+        Err(not_a_resultset())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'static>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.fields((0..len).map(|i| i.to_string()).collect(), visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.fields(fields.iter().map(|&f| f.to_owned()).collect(), visitor)
+    }
+
+    forward_to_deserialize_any! {
+        <V: Visitor<'static>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq map enum identifier ignored_any
+    }
+}
+
+struct Fields<'c> {
+    columns: &'c mut Vec<Column>,
+    names: std::vec::IntoIter<String>,
+}
+
+impl<'c> SeqAccess<'static> for Fields<'c> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'static>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let name = match self.names.next() {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let mut column = Column {
+            colflags: ColumnFlags::NOT_NULL_FLAG,
+            ..Column::new(name, ColumnType::MYSQL_TYPE_VAR_STRING)
+        };
+        let value = seed
+            .deserialize(ColumnDescriber {
+                column: &mut column,
+            })
+            .map_err(|e| e.in_field(&column.column))?;
+        self.columns.push(column);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.names.len())
+    }
+}
+
+/// Works out the column type of a single field, and hands its visitor a placeholder value.
+struct ColumnDescriber<'c> {
+    column: &'c mut Column,
+}
+
+impl<'c> ColumnDescriber<'c> {
+    fn set(self, coltype: ColumnType, flags: ColumnFlags) {
+        self.column.coltype = coltype;
+        self.column.colflags.insert(flags);
+    }
+}
+
+macro_rules! describe {
+    ($($method:ident => $coltype:ident, $flags:expr, $visit:ident($v:expr);)*) => {
+        $(
+            fn $method<V: Visitor<'static>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.set(ColumnType::$coltype, $flags);
+                visitor.$visit($v)
+            }
+        )*
+    };
+}
+
+impl<'c> de::Deserializer<'static> for ColumnDescriber<'c> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'static>>(self, _: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom(
+            "only numbers, booleans, strings, byte strings, unit enums and options of those \
+             can be columns",
+        ))
+    }
+
+    describe! {
+        deserialize_bool => MYSQL_TYPE_TINY, ColumnFlags::empty(), visit_bool(false);
+        deserialize_i8 => MYSQL_TYPE_TINY, ColumnFlags::empty(), visit_i8(0);
+        deserialize_i16 => MYSQL_TYPE_SHORT, ColumnFlags::empty(), visit_i16(0);
+        deserialize_i32 => MYSQL_TYPE_LONG, ColumnFlags::empty(), visit_i32(0);
+        deserialize_i64 => MYSQL_TYPE_LONGLONG, ColumnFlags::empty(), visit_i64(0);
+        deserialize_u8 => MYSQL_TYPE_TINY, ColumnFlags::UNSIGNED_FLAG, visit_u8(0);
+        deserialize_u16 => MYSQL_TYPE_SHORT, ColumnFlags::UNSIGNED_FLAG, visit_u16(0);
+        deserialize_u32 => MYSQL_TYPE_LONG, ColumnFlags::UNSIGNED_FLAG, visit_u32(0);
+        deserialize_u64 => MYSQL_TYPE_LONGLONG, ColumnFlags::UNSIGNED_FLAG, visit_u64(0);
+        deserialize_f32 => MYSQL_TYPE_FLOAT, ColumnFlags::empty(), visit_f32(0.0);
+        deserialize_f64 => MYSQL_TYPE_DOUBLE, ColumnFlags::empty(), visit_f64(0.0);
+        deserialize_char => MYSQL_TYPE_VAR_STRING, ColumnFlags::empty(), visit_char(' ');
+        deserialize_str => MYSQL_TYPE_VAR_STRING, ColumnFlags::empty(), visit_borrowed_str("");
+        deserialize_string => MYSQL_TYPE_VAR_STRING, ColumnFlags::empty(), visit_borrowed_str("");
+        deserialize_bytes => MYSQL_TYPE_BLOB, ColumnFlags::BINARY_FLAG, visit_borrowed_bytes(b"");
+        deserialize_byte_buf => MYSQL_TYPE_BLOB, ColumnFlags::BINARY_FLAG, visit_borrowed_bytes(b"");
+    }
+
+    fn deserialize_option<V: Visitor<'static>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.column.colflags.remove(ColumnFlags::NOT_NULL_FLAG);
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'static>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.column.colflags.remove(ColumnFlags::NOT_NULL_FLAG);
+        self.set(ColumnType::MYSQL_TYPE_NULL, ColumnFlags::empty());
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'static>>(
+        self,
+        _: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let first = *variants.first().ok_or_else(|| {
+            <Error as de::Error>::custom("enums without variants cannot be columns")
+        })?;
+        self.set(ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty());
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(first))
+    }
+
+    forward_to_deserialize_any! {
+        <V: Visitor<'static>>
+        i128 u128 seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Role {
+        Admin,
+        Member,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct User<'a> {
+        id: u32,
+        #[serde(rename = "login")]
+        name: &'a str,
+        karma: i64,
+        score: f32,
+        active: bool,
+        email: Option<String>,
+        #[serde(with = "serde_bytes_like")]
+        avatar: Vec<u8>,
+        role: Role,
+        #[serde(skip)]
+        secret: String,
+    }
+
+    // Vec<u8> deserializes from a sequence unless told otherwise
+    mod serde_bytes_like {
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            <&[u8]>::deserialize(d).map(<[u8]>::to_vec)
+        }
+    }
+
+    fn described(c: &Column) -> (&str, ColumnType, ColumnFlags) {
+        (&c.column, c.coltype, c.colflags)
+    }
+
+    #[test]
+    fn describes_struct_fields() {
+        let columns = columns_for::<User<'_>>().unwrap();
+        let nn = ColumnFlags::NOT_NULL_FLAG;
+        assert_eq!(
+            columns.iter().map(described).collect::<Vec<_>>(),
+            [
+                (
+                    "id",
+                    ColumnType::MYSQL_TYPE_LONG,
+                    nn | ColumnFlags::UNSIGNED_FLAG
+                ),
+                ("login", ColumnType::MYSQL_TYPE_VAR_STRING, nn),
+                ("karma", ColumnType::MYSQL_TYPE_LONGLONG, nn),
+                ("score", ColumnType::MYSQL_TYPE_FLOAT, nn),
+                ("active", ColumnType::MYSQL_TYPE_TINY, nn),
+                (
+                    "email",
+                    ColumnType::MYSQL_TYPE_VAR_STRING,
+                    ColumnFlags::empty()
+                ),
+                (
+                    "avatar",
+                    ColumnType::MYSQL_TYPE_BLOB,
+                    nn | ColumnFlags::BINARY_FLAG
+                ),
+                ("role", ColumnType::MYSQL_TYPE_VAR_STRING, nn),
+            ]
+        );
+        assert!(columns.iter().all(|c| c.table.is_empty()));
+    }
+
+    #[test]
+    fn describes_tuples() {
+        let columns = columns_for::<(u8, Option<f64>)>().unwrap();
+        assert_eq!(
+            columns.iter().map(described).collect::<Vec<_>>(),
+            [
+                (
+                    "0",
+                    ColumnType::MYSQL_TYPE_TINY,
+                    ColumnFlags::NOT_NULL_FLAG | ColumnFlags::UNSIGNED_FLAG
+                ),
+                ("1", ColumnType::MYSQL_TYPE_DOUBLE, ColumnFlags::empty()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_what_cannot_be_a_column() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Nested {
+            id: u32,
+            tags: Vec<String>,
+        }
+
+        let e = columns_for::<Nested>().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().starts_with("field `tags`: only numbers"));
+
+        let e = columns_for::<u32>().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "only structs and tuples can describe the columns of a resultset"
+        );
+    }
+}
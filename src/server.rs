@@ -0,0 +1,501 @@
+use crate::packet::PacketConn;
+use crate::{writers, ErrorKind, MysqlIntermediary, MysqlShim, Transport};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{self as unix, UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of client connections for a [`MysqlServer`](struct.MysqlServer.html).
+///
+/// This is implemented for `TcpListener` and, on Unix, `UnixListener`.
+pub trait Listener: Send + Sync + 'static {
+    /// A connection to a single client.
+    type Stream: Read + Write + Send + 'static;
+    /// The address a client connected from.
+    type Addr: Clone + Send + 'static;
+    /// How clients accepted by this listener are connected.
+    const TRANSPORT: Transport;
+
+    /// Wait for the next client to connect.
+    fn accept(&self) -> io::Result<(Self::Stream, Self::Addr)>;
+
+    /// Set the read timeout of a client's stream, which enforces `connect_timeout` and
+    /// `wait_timeout`.
+    fn set_read_timeout(stream: &Self::Stream, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Create another handle to a client's stream, which the server uses to close it when
+    /// shutting down.
+    fn try_clone(stream: &Self::Stream) -> io::Result<Self::Stream>;
+
+    /// Shut down the reading or writing half of a client's stream, or both.
+    fn shutdown(stream: &Self::Stream, how: Shutdown) -> io::Result<()>;
+
+    /// Make a pending [`accept`](#tymethod.accept) return, by connecting to the listener.
+    fn wake(&self) -> io::Result<()>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+    type Addr = SocketAddr;
+    const TRANSPORT: Transport = Transport::Tcp;
+
+    fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        TcpListener::accept(self)
+    }
+
+    fn set_read_timeout(stream: &TcpStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_read_timeout(timeout)
+    }
+
+    fn try_clone(stream: &TcpStream) -> io::Result<TcpStream> {
+        stream.try_clone()
+    }
+
+    fn shutdown(stream: &TcpStream, how: Shutdown) -> io::Result<()> {
+        stream.shutdown(how)
+    }
+
+    fn wake(&self) -> io::Result<()> {
+        let mut addr = self.local_addr()?;
+        // a listener on all interfaces can be reached over loopback
+        if addr.ip().is_unspecified() {
+            match addr {
+                SocketAddr::V4(_) => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+                SocketAddr::V6(_) => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+            }
+        }
+        TcpStream::connect(addr).map(drop)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+    type Addr = unix::SocketAddr;
+    const TRANSPORT: Transport = Transport::Unix;
+
+    fn accept(&self) -> io::Result<(UnixStream, unix::SocketAddr)> {
+        UnixListener::accept(self)
+    }
+
+    fn set_read_timeout(stream: &UnixStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_read_timeout(timeout)
+    }
+
+    fn try_clone(stream: &UnixStream) -> io::Result<UnixStream> {
+        stream.try_clone()
+    }
+
+    fn shutdown(stream: &UnixStream, how: Shutdown) -> io::Result<()> {
+        stream.shutdown(how)
+    }
+
+    fn wake(&self) -> io::Result<()> {
+        let addr = self.local_addr()?;
+        let path = addr
+            .as_pathname()
+            .ok_or_else(|| io::Error::other("listener is not bound to a path"))?;
+        UnixStream::connect(path).map(drop)
+    }
+}
+
+/// Creates a [`MysqlShim`](trait.MysqlShim.html) for each client that connects to a
+/// [`MysqlServer`](struct.MysqlServer.html).
+///
+/// This is implemented for closures that take the client's address and return a shim.
+pub trait ShimFactory<L: Listener = TcpListener>: Send + Sync + 'static {
+    /// The shim that serves a single connection.
+    type Shim: MysqlShim<L::Stream> + Send + 'static;
+
+    /// Create the shim for a client that connected from `peer`.
+    fn new_connection(&self, peer: L::Addr) -> Self::Shim;
+}
+
+impl<F, B, L> ShimFactory<L> for F
+where
+    L: Listener,
+    F: Fn(L::Addr) -> B + Send + Sync + 'static,
+    B: MysqlShim<L::Stream> + Send + 'static,
+{
+    type Shim = B;
+
+    fn new_connection(&self, peer: L::Addr) -> B {
+        self(peer)
+    }
+}
+
+type ShimError<F, L> = <<F as ShimFactory<L>>::Shim as MysqlShim<<L as Listener>::Stream>>::Error;
+type ErrorHandler<F, L> = dyn Fn(<L as Listener>::Addr, ShimError<F, L>) + Send + Sync;
+type AcceptErrorHandler = dyn Fn(io::Error) + Send + Sync;
+
+/// How long to wait before accepting again after the first of a run of failed accepts, which
+/// doubles with each further failure up to `MAX_ACCEPT_BACKOFF`.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Whether a failed accept only concerns the client that was being accepted, so that the next
+/// one can be accepted right away.
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+/// A server that accepts MySQL clients on a [`Listener`](trait.Listener.html), by default a TCP
+/// listener, and serves each of them on its own thread with a shim from a
+/// [`ShimFactory`](trait.ShimFactory.html).
+///
+/// ```no_run
+/// # use msql_srv::*;
+/// # use std::io;
+/// # struct Backend;
+/// # impl<W: io::Read + io::Write> MysqlShim<W> for Backend {
+/// #     type Error = io::Error;
+/// #     fn on_prepare(&mut self, _: &str, info: StatementMetaWriter<W>) -> io::Result<()> {
+/// #         info.reply(42, &[], &[])
+/// #     }
+/// #     fn on_execute(&mut self, _: u32, _: ParamParser, w: QueryResultWriter<W>) -> io::Result<()> {
+/// #         w.completed(0, 0)
+/// #     }
+/// #     fn on_close(&mut self, _: u32) {}
+/// #     fn on_query(&mut self, _: &str, w: QueryResultWriter<W>) -> io::Result<()> {
+/// #         w.completed(0, 0)
+/// #     }
+/// # }
+/// MysqlServer::bind("127.0.0.1:3306", |_| Backend)
+///     .unwrap()
+///     .max_connections(100)
+///     .on_error(|peer, e| eprintln!("connection from {} failed: {}", peer, e))
+///     .run()
+///     .unwrap();
+/// ```
+///
+/// A running server can be stopped with a [`ShutdownHandle`](struct.ShutdownHandle.html) from
+/// [`shutdown_handle`](#method.shutdown_handle).
+pub struct MysqlServer<F: ShimFactory<L>, L: Listener = TcpListener> {
+    listener: Arc<L>,
+    factory: Arc<F>,
+    max_connections: Option<usize>,
+    on_error: Option<Arc<ErrorHandler<F, L>>>,
+    on_accept_error: Option<Box<AcceptErrorHandler>>,
+    connections: Arc<Connections>,
+}
+
+impl<F: ShimFactory> MysqlServer<F> {
+    /// Listen for clients on `addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, factory: F) -> io::Result<Self> {
+        Ok(MysqlServer::new(TcpListener::bind(addr)?, factory))
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+#[cfg(unix)]
+impl<F: ShimFactory<UnixListener>> MysqlServer<F, UnixListener> {
+    /// Listen for clients on a Unix domain socket at `path`, such as
+    /// `/var/run/mysqld/mysqld.sock`.
+    ///
+    /// Clients connected this way are reported as [`Transport::Unix`](enum.Transport.html).
+    pub fn bind_unix<P: AsRef<Path>>(path: P, factory: F) -> io::Result<Self> {
+        Ok(MysqlServer::new(UnixListener::bind(path)?, factory))
+    }
+}
+
+impl<F: ShimFactory<L>, L: Listener> MysqlServer<F, L> {
+    /// Accept clients on an existing listener.
+    pub fn new(listener: L, factory: F) -> Self {
+        let listener = Arc::new(listener);
+        // a handle that outlives the server has no listener left to wake
+        let weak = Arc::downgrade(&listener);
+        let wake = move || Weak::upgrade(&weak).map_or(Ok(()), |l| l.wake());
+        MysqlServer {
+            listener,
+            factory: Arc::new(factory),
+            max_connections: None,
+            on_error: None,
+            on_accept_error: None,
+            connections: Arc::new(Connections::new(Box::new(wake))),
+        }
+    }
+
+    /// A handle that stops the server once it has been started with [`run`](#method.run).
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            connections: Arc::clone(&self.connections),
+        }
+    }
+
+    /// Limit how many clients are served at the same time.
+    ///
+    /// Clients that connect while the limit is reached are turned away with
+    /// `ER_CON_COUNT_ERROR`.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Called with the client's address and the error whenever a connection ends with an error.
+    ///
+    /// By default, such errors are ignored.
+    pub fn on_error<E>(mut self, on_error: E) -> Self
+    where
+        E: Fn(L::Addr, ShimError<F, L>) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Called with the error whenever accepting a client fails.
+    ///
+    /// The server keeps accepting clients regardless. After errors that are not about a single
+    /// client, such as running out of file descriptors, it waits a little before trying again, and
+    /// a little longer each time it fails again, up to a second. By default, such errors are
+    /// ignored.
+    pub fn on_accept_error<E>(mut self, on_accept_error: E) -> Self
+    where
+        E: Fn(io::Error) + Send + Sync + 'static,
+    {
+        self.on_accept_error = Some(Box::new(on_accept_error));
+        self
+    }
+
+    /// Accept and serve clients until the server is shut down.
+    pub fn run(self) -> io::Result<()> {
+        let mut backoff = None;
+        loop {
+            let accepted = self.listener.accept();
+            if self.connections.shutting_down() {
+                return Ok(());
+            }
+            let (stream, peer) = match accepted {
+                Ok(accepted) => {
+                    backoff = None;
+                    accepted
+                }
+                Err(e) => {
+                    let transient = is_connection_error(&e);
+                    if let Some(ref on_accept_error) = self.on_accept_error {
+                        on_accept_error(e);
+                    }
+                    if !transient {
+                        let wait = backoff.map_or(MIN_ACCEPT_BACKOFF, |b: Duration| {
+                            (b * 2).min(MAX_ACCEPT_BACKOFF)
+                        });
+                        backoff = Some(wait);
+                        thread::sleep(wait);
+                    }
+                    continue;
+                }
+            };
+
+            if matches!(self.max_connections, Some(max) if self.connections.len() >= max) {
+                // nobody is interested in whether the client heard us
+                let _ = too_many_connections(stream);
+                continue;
+            }
+
+            let close = match closer::<L>(&stream) {
+                Ok(close) => close,
+                // we could not shut the connection down later, so do not serve it
+                Err(_) => continue,
+            };
+            let guard = match self.connections.open(close) {
+                Some(guard) => guard,
+                None => return Ok(()),
+            };
+            let factory = Arc::clone(&self.factory);
+            let on_error = self.on_error.clone();
+            let stop = Stop {
+                shutting_down: Arc::clone(&self.connections.shutting_down),
+                // `guard` counts the connection until `on_error` has run as well
+                _open: None,
+            };
+            thread::spawn(move || {
+                let _guard = guard;
+                let shim = factory.new_connection(peer.clone());
+                if let Err(e) = MysqlIntermediary::run_with(
+                    shim,
+                    stream,
+                    L::TRANSPORT,
+                    Some(L::set_read_timeout),
+                    Some(stop),
+                ) {
+                    if let Some(on_error) = on_error {
+                        on_error(peer, e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Stops a running [`MysqlServer`](struct.MysqlServer.html), or intermediaries that are run on
+/// their own.
+///
+/// To stop intermediaries that are not run by a `MysqlServer`, create a handle with
+/// [`new`](#method.new) and hand out clones of it from
+/// [`MysqlShim::shutdown_handle`](trait.MysqlShim.html#method.shutdown_handle) or
+/// [`AsyncMysqlShim::shutdown_handle`](trait.AsyncMysqlShim.html#method.shutdown_handle).
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    connections: Arc<Connections>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        ShutdownHandle::new()
+    }
+}
+
+impl ShutdownHandle {
+    /// A handle for stopping the intermediaries whose shims return it from `shutdown_handle`.
+    pub fn new() -> Self {
+        ShutdownHandle {
+            connections: Arc::new(Connections::new(Box::new(|| Ok(())))),
+        }
+    }
+
+    /// Stop accepting clients, and disconnect the connected ones.
+    ///
+    /// Clients that are running a command get to finish it first. Each client is then sent
+    /// `ER_SERVER_SHUTDOWN` and disconnected. Connections that are still open after `grace` are
+    /// closed without further ado. Returns once all connections are closed, or when `grace` runs
+    /// out.
+    pub fn shutdown(&self, grace: Duration) {
+        let deadline = Instant::now() + grace;
+        let connections = &self.connections;
+        {
+            let open = connections.open.lock().unwrap();
+            connections.shutting_down.store(true, Ordering::SeqCst);
+            // idle clients are woken up by the end of their input
+            for close in open.values() {
+                close(Shutdown::Read);
+            }
+        }
+        // there is nothing to wake if the server has stopped already
+        let _ = (connections.wake)();
+
+        let mut open = connections.open.lock().unwrap();
+        while !open.is_empty() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                for close in open.values() {
+                    close(Shutdown::Both);
+                }
+                break;
+            }
+            open = connections.closed.wait_timeout(open, left).unwrap().0;
+        }
+    }
+
+    /// Count a connection as open until the returned `Stop` is dropped, and let it know when we
+    /// shut down.
+    pub(crate) fn register(&self, close: Close) -> Stop {
+        Stop {
+            shutting_down: Arc::clone(&self.connections.shutting_down),
+            _open: self.connections.open(close),
+        }
+    }
+}
+
+/// Tells an intermediary that it should hang up before the next command.
+pub(crate) struct Stop {
+    shutting_down: Arc<AtomicBool>,
+    _open: Option<OpenConnection>,
+}
+
+impl Stop {
+    pub(crate) fn requested(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+/// Closes one half of a client's stream, or both.
+pub(crate) type Close = Box<dyn Fn(Shutdown) + Send>;
+
+/// Closes `stream` through another handle to it.
+pub(crate) fn closer<L: Listener>(stream: &L::Stream) -> io::Result<Close> {
+    let handle = L::try_clone(stream)?;
+    Ok(Box::new(move |how| {
+        let _ = L::shutdown(&handle, how);
+    }))
+}
+
+/// The connections a server is serving.
+struct Connections {
+    shutting_down: Arc<AtomicBool>,
+    open: Mutex<HashMap<u64, Close>>,
+    next_id: AtomicU64,
+    // notified whenever a connection closes
+    closed: Condvar,
+    wake: Box<dyn Fn() -> io::Result<()> + Send + Sync>,
+}
+
+impl Connections {
+    fn new(wake: Box<dyn Fn() -> io::Result<()> + Send + Sync>) -> Self {
+        Connections {
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            open: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            closed: Condvar::new(),
+            wake,
+        }
+    }
+
+    fn shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    fn len(&self) -> usize {
+        self.open.lock().unwrap().len()
+    }
+
+    /// Count a connection as open until the returned guard is dropped, unless we are shutting
+    /// down.
+    fn open(self: &Arc<Self>, close: Close) -> Option<OpenConnection> {
+        let mut open = self.open.lock().unwrap();
+        // checked under the lock, so that a shutdown either sees the connection or stops it here
+        if self.shutting_down() {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        open.insert(id, close);
+        Some(OpenConnection(Arc::clone(self), id))
+    }
+}
+
+/// Counts a connection as open until it is dropped.
+struct OpenConnection(Arc<Connections>, u64);
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.open.lock().unwrap().remove(&self.1);
+        self.0.closed.notify_all();
+    }
+}
+
+/// Turn a client away in place of the server's greeting.
+fn too_many_connections<S: Read + Write>(stream: S) -> io::Result<()> {
+    let mut rw = PacketConn::new(stream);
+    writers::write_err(
+        ErrorKind::ER_CON_COUNT_ERROR,
+        b"Too many connections",
+        &mut rw,
+    )?;
+    rw.flush()
+}
@@ -0,0 +1,277 @@
+//! Just enough of a SQL lexer to recognize the handful of statements that the intermediary can
+//! answer on behalf of the shim (system variable lookups and assignments, for example).
+//!
+//! Anything that does not lex cleanly is simply handed to the shim, so this errs on the side of
+//! rejecting input rather than guessing.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// A bare word; either a keyword or an unquoted identifier.
+    Word(&'a str),
+    /// A backtick-quoted identifier.
+    Ident(String),
+    /// A single- or double-quoted string literal.
+    Str(String),
+    /// A numeric literal (possibly with a leading sign).
+    Number(&'a str),
+    /// A system variable reference (`@@name` or `@@scope.name`), without the leading `@@`.
+    SysVar(&'a str),
+    /// A user variable reference (`@name`), without the leading `@`.
+    UserVar(&'a str),
+    /// Any other single character.
+    Punct(char),
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Split `q` into tokens, skipping whitespace and comments.
+///
+/// Returns `None` if the input contains unterminated strings, identifiers, or comments.
+pub(crate) fn tokenize(q: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = q;
+    loop {
+        rest = skip_blank(rest)?;
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return Some(tokens),
+        };
+
+        if c == '\'' || c == '"' {
+            let (s, r) = quoted(&rest[1..], c)?;
+            tokens.push(Token::Str(s));
+            rest = r;
+        } else if c == '`' {
+            let (s, r) = quoted(&rest[1..], c)?;
+            tokens.push(Token::Ident(s));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("@@") {
+            let end = r
+                .find(|c: char| !is_word_char(c) && c != '.')
+                .unwrap_or(r.len());
+            if end == 0 {
+                return None;
+            }
+            tokens.push(Token::SysVar(&r[..end]));
+            rest = &r[end..];
+        } else if let Some(r) = rest.strip_prefix('@') {
+            let end = r.find(|c: char| !is_word_char(c)).unwrap_or(r.len());
+            if end == 0 {
+                return None;
+            }
+            tokens.push(Token::UserVar(&r[..end]));
+            rest = &r[end..];
+        } else if c.is_ascii_digit()
+            || ((c == '-' || c == '+' || c == '.')
+                && rest[1..].starts_with(|c: char| c.is_ascii_digit())
+                && !matches!(
+                    tokens.last(),
+                    Some(Token::Word(_))
+                        | Some(Token::Ident(_))
+                        | Some(Token::Number(_))
+                        | Some(Token::Punct(')'))
+                ))
+        {
+            let end = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .map(|i| i + 1)
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(&rest[..end]));
+            rest = &rest[end..];
+        } else if is_word_char(c) {
+            let end = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..end]));
+            rest = &rest[end..];
+        } else {
+            tokens.push(Token::Punct(c));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+}
+
+fn skip_blank(mut rest: &str) -> Option<&str> {
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix("/*") {
+            rest = &r[r.find("*/")? + 2..];
+        } else if rest.starts_with('#')
+            || (rest.starts_with("--")
+                && rest[2..]
+                    .chars()
+                    .next()
+                    .map(char::is_whitespace)
+                    .unwrap_or(true))
+        {
+            rest = rest.find('\n').map(|i| &rest[i..]).unwrap_or("");
+        } else {
+            return Some(rest);
+        }
+    }
+}
+
+fn quoted(s: &str, quote: char) -> Option<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            if let Some(&(_, c2)) = chars.peek() {
+                if c2 == quote {
+                    // doubled quote is an escaped quote
+                    chars.next();
+                    out.push(quote);
+                    continue;
+                }
+            }
+            return Some((out, &s[i + 1..]));
+        } else if c == '\\' && quote != '`' {
+            let (_, e) = chars.next()?;
+            if e == '%' || e == '_' {
+                // like MySQL, keep these escaped so that a LIKE pattern still sees the escape
+                out.push('\\');
+            }
+            out.push(match e {
+                '0' => '\0',
+                'b' => '\x08',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'Z' => '\x1a',
+                e => e,
+            });
+        } else {
+            out.push(c);
+        }
+    }
+    None
+}
+
+/// A cursor over a token stream with helpers for matching keywords and punctuation.
+pub(crate) struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(q: &'a str) -> Option<Self> {
+        Some(Parser {
+            tokens: tokenize(q)?,
+            pos: 0,
+        })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    pub(crate) fn next(&mut self) -> Option<Token<'a>> {
+        let t = self.tokens.get(self.pos).cloned();
+        if t.is_some() {
+            self.pos += 1;
+        }
+        t
+    }
+
+    /// Consume the next token if it is the (case-insensitive) keyword `kw`.
+    pub(crate) fn keyword(&mut self, kw: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consume the next token if it is the punctuation character `c`.
+    pub(crate) fn punct(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume an identifier, quoted or not.
+    pub(crate) fn ident(&mut self) -> Option<String> {
+        match self.peek()? {
+            Token::Word(w) => {
+                let w = w.to_string();
+                self.pos += 1;
+                Some(w)
+            }
+            Token::Ident(i) => {
+                let i = i.clone();
+                self.pos += 1;
+                Some(i)
+            }
+            _ => None,
+        }
+    }
+
+    /// True if only statement terminators remain.
+    pub(crate) fn at_end(&mut self) -> bool {
+        while self.punct(';') {}
+        self.pos == self.tokens.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_skips_comments() {
+        assert_eq!(
+            tokenize("/* x */ SELECT -- y\n 1 # z").unwrap(),
+            vec![Token::Word("SELECT"), Token::Number("1")]
+        );
+    }
+
+    #[test]
+    fn it_lexes_variables() {
+        assert_eq!(
+            tokenize("SELECT @@session.tx_isolation, @x").unwrap(),
+            vec![
+                Token::Word("SELECT"),
+                Token::SysVar("session.tx_isolation"),
+                Token::Punct(','),
+                Token::UserVar("x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_lexes_strings() {
+        assert_eq!(
+            tokenize(r#"'it''s' "a\"b" `c``d`"#).unwrap(),
+            vec![
+                Token::Str("it's".to_owned()),
+                Token::Str("a\"b".to_owned()),
+                Token::Ident("c`d".to_owned()),
+            ]
+        );
+        assert_eq!(
+            tokenize(r"'tx\_read\_only\%' 'a\nb'").unwrap(),
+            vec![
+                Token::Str(r"tx\_read\_only\%".to_owned()),
+                Token::Str("a\nb".to_owned()),
+            ]
+        );
+        assert_eq!(tokenize("'unterminated"), None);
+    }
+
+    #[test]
+    fn it_lexes_signed_numbers() {
+        assert_eq!(
+            tokenize("x=-1").unwrap(),
+            vec![Token::Word("x"), Token::Punct('='), Token::Number("-1")]
+        );
+        assert_eq!(
+            tokenize("x-1").unwrap(),
+            vec![Token::Word("x"), Token::Punct('-'), Token::Number("1")]
+        );
+    }
+}
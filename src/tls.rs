@@ -50,6 +50,21 @@ impl<T: Read + Write> SwitchableConn<T> {
         SwitchableConn(Some(EitherConn::Plain(rw)))
     }
 
+    pub fn get_ref(&self) -> &T {
+        match self.0.as_ref().unwrap() {
+            EitherConn::Plain(p) => p,
+            EitherConn::Tls(t) => t.sock.inner.get_ref().1,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut T {
+        match self.0.as_mut().unwrap() {
+            EitherConn::Plain(p) => p,
+            EitherConn::Tls(t) => t.sock.inner.get_mut().1,
+        }
+    }
+
     pub fn switch_to_tls(
         &mut self,
         config: Arc<ServerConfig>,
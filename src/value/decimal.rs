@@ -0,0 +1,323 @@
+//! Exact decimal values, for `DECIMAL` and `NEWDECIMAL` columns and parameters.
+//!
+//! MySQL sends decimals as strings in both the text and the binary protocol, so encoding mostly
+//! comes down to formatting the value with as many decimals as the column declares.
+
+use super::encode::bad;
+use super::{FromValueError, ToMysqlValue, Value, ValueInner, WriteContext};
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::Column;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::str;
+
+/// The number of decimals values written to `c` should have, if it is a decimal column.
+fn scale_of(c: &Column) -> Option<u8> {
+    match c.coltype {
+        ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => c.decimals,
+        _ => None,
+    }
+}
+
+fn write_bin<W: Write, V: std::fmt::Debug>(
+    w: &mut W,
+    c: &Column,
+    v: &V,
+    s: String,
+) -> io::Result<()> {
+    match c.coltype {
+        ColumnType::MYSQL_TYPE_DECIMAL
+        | ColumnType::MYSQL_TYPE_NEWDECIMAL
+        | ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_VARCHAR => w.write_lenenc_str(s.as_bytes()).map(|_| ()),
+        _ => Err(bad(v, c)),
+    }
+}
+
+/// Parse a decimal out of a parameter, which clients send as a string, but may also have sent as
+/// an integer or a float.
+fn parse<'a, T, E>(
+    val: Value<'a>,
+    target: &'static str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+    from_int: impl FnOnce(ValueInner<'a>) -> T,
+) -> Result<T, FromValueError>
+where
+    E: std::fmt::Display,
+{
+    let err = || FromValueError::new(val.0, target);
+    match val.0 {
+        ValueInner::Bytes(b) => {
+            let s = str::from_utf8(b).map_err(|e| err().because(e))?;
+            parse(s.trim()).map_err(|e| err().because(e))
+        }
+        v @ ValueInner::Int(_) | v @ ValueInner::UInt(_) => Ok(from_int(v)),
+        ValueInner::Double(f) if f.is_finite() => {
+            parse(&f.to_string()).map_err(|e| err().because(e))
+        }
+        _ => Err(err()),
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_impls {
+    use super::*;
+    use rust_decimal::{Decimal, RoundingStrategy};
+
+    fn format(v: &Decimal, c: &Column) -> String {
+        match scale_of(c) {
+            Some(scale) => {
+                let mut v = v.round_dp_with_strategy(
+                    u32::from(scale),
+                    RoundingStrategy::MidpointAwayFromZero,
+                );
+                v.rescale(u32::from(scale));
+                v.to_string()
+            }
+            None => v.to_string(),
+        }
+    }
+
+    impl ToMysqlValue for Decimal {
+        fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+            w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+        }
+        fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+            w.write_lenenc_str(format(self, cx.column()).as_bytes())
+                .map(|_| ())
+        }
+        fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+            write_bin(w, c, self, format(self, c))
+        }
+    }
+
+    impl<'a> TryFrom<Value<'a>> for Decimal {
+        type Error = FromValueError;
+        fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+            parse(val, "Decimal", Decimal::from_str_exact, |v| match v {
+                ValueInner::Int(i) => Decimal::from(i),
+                ValueInner::UInt(u) => Decimal::from(u),
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+mod bigdecimal_impls {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    fn format(v: &BigDecimal, c: &Column) -> String {
+        match scale_of(c) {
+            Some(scale) => v
+                .round(i64::from(scale))
+                .with_scale(i64::from(scale))
+                .to_string(),
+            None => v.to_string(),
+        }
+    }
+
+    impl ToMysqlValue for BigDecimal {
+        fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+            w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+        }
+        fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+            w.write_lenenc_str(format(self, cx.column()).as_bytes())
+                .map(|_| ())
+        }
+        fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+            write_bin(w, c, self, format(self, c))
+        }
+    }
+
+    impl<'a> TryFrom<Value<'a>> for BigDecimal {
+        type Error = FromValueError;
+        fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+            parse(val, "BigDecimal", BigDecimal::from_str, |v| match v {
+                ValueInner::Int(i) => BigDecimal::from(i),
+                ValueInner::UInt(u) => BigDecimal::from(u),
+                _ => unreachable!(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::io::ReadMysqlExt;
+
+    fn text<T: ToMysqlValue>(v: T, c: &Column) -> String {
+        let mut data = Vec::new();
+        v.to_mysql_text_for(&mut data, &WriteContext::utc(c))
+            .unwrap();
+        String::from_utf8((&data[..]).read_lenenc_str().unwrap()).unwrap()
+    }
+
+    fn bin<T: ToMysqlValue>(v: T, c: &Column) -> io::Result<String> {
+        let mut data = Vec::new();
+        v.to_mysql_bin(&mut data, c)?;
+        Ok(String::from_utf8((&data[..]).read_lenenc_str()?).unwrap())
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    mod rust_decimal {
+        use super::*;
+        use ::rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        fn d(s: &str) -> Decimal {
+            Decimal::from_str(s).unwrap()
+        }
+
+        #[test]
+        fn keeps_own_scale_without_decimals() {
+            let c = Column::new("", ColumnType::MYSQL_TYPE_NEWDECIMAL);
+            assert_eq!(text(d("12.3450"), &c), "12.3450");
+            assert_eq!(bin(d("-0.5"), &c).unwrap(), "-0.5");
+        }
+
+        #[test]
+        fn rounds_and_pads_to_column_decimals() {
+            let c = Column {
+                decimals: Some(2),
+                ..Column::new("", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            };
+            assert_eq!(text(d("12.345"), &c), "12.35");
+            assert_eq!(text(d("-12.345"), &c), "-12.35");
+            assert_eq!(text(d("12"), &c), "12.00");
+            assert_eq!(bin(d("0.1"), &c).unwrap(), "0.10");
+            assert_eq!(text(Some(d("1.005")), &c), "1.01");
+
+            let c = Column {
+                decimals: Some(0),
+                ..Column::new("", ColumnType::MYSQL_TYPE_DECIMAL)
+            };
+            assert_eq!(text(d("2.5"), &c), "3");
+        }
+
+        #[test]
+        fn ignores_decimals_of_string_columns() {
+            let c = Column {
+                decimals: Some(2),
+                ..Column::new("", ColumnType::MYSQL_TYPE_VAR_STRING)
+            };
+            assert_eq!(text(d("1.2345"), &c), "1.2345");
+            assert_eq!(bin(d("1.2345"), &c).unwrap(), "1.2345");
+        }
+
+        #[test]
+        fn rejects_numeric_columns() {
+            let c = Column::new("", ColumnType::MYSQL_TYPE_LONGLONG);
+            assert!(bin(d("1"), &c).is_err());
+        }
+
+        #[test]
+        fn decodes_params() {
+            let v = Value::bytes(b"123.4500");
+            let v = Decimal::try_from(v).unwrap();
+            assert_eq!(v, d("123.45"));
+            assert_eq!(v.scale(), 4);
+            assert_eq!(
+                Decimal::try_from(Value::new(ValueInner::Int(-7))).unwrap(),
+                d("-7")
+            );
+            assert_eq!(
+                Decimal::try_from(Value::new(ValueInner::UInt(u64::MAX))).unwrap(),
+                Decimal::from(u64::MAX)
+            );
+            assert_eq!(
+                Decimal::try_from(Value::new(ValueInner::Double(0.1))).unwrap(),
+                d("0.1")
+            );
+        }
+
+        #[test]
+        fn rejects_bad_params() {
+            let e = Decimal::try_from(Value::bytes(b"12abc")).unwrap_err();
+            assert_eq!(e.target(), "Decimal");
+            assert!(e
+                .to_string()
+                .starts_with("cannot convert Bytes(\"12abc\") to Decimal: "));
+            assert!(Decimal::try_from(Value::bytes(b"1e400")).is_err());
+            assert!(Decimal::try_from(Value::new(ValueInner::Double(f64::NAN))).is_err());
+            assert!(Decimal::try_from(Value::null()).is_err());
+        }
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    mod bigdecimal {
+        use super::*;
+        use ::bigdecimal::BigDecimal;
+        use std::str::FromStr;
+
+        fn d(s: &str) -> BigDecimal {
+            BigDecimal::from_str(s).unwrap()
+        }
+
+        #[test]
+        fn keeps_own_scale_without_decimals() {
+            let c = Column::new("", ColumnType::MYSQL_TYPE_NEWDECIMAL);
+            assert_eq!(text(d("12.3450"), &c), "12.3450");
+            assert_eq!(bin(d("-0.5"), &c).unwrap(), "-0.5");
+        }
+
+        #[test]
+        fn rounds_and_pads_to_column_decimals() {
+            let c = Column {
+                decimals: Some(2),
+                ..Column::new("", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            };
+            assert_eq!(text(d("12.345"), &c), "12.35");
+            assert_eq!(text(d("-12.345"), &c), "-12.35");
+            assert_eq!(text(d("12"), &c), "12.00");
+            assert_eq!(bin(d("0.1"), &c).unwrap(), "0.10");
+            assert_eq!(text(Some(d("1.005")), &c), "1.01");
+
+            let c = Column {
+                decimals: Some(0),
+                ..Column::new("", ColumnType::MYSQL_TYPE_DECIMAL)
+            };
+            assert_eq!(text(d("2.5"), &c), "3");
+        }
+
+        #[test]
+        fn keeps_digits_beyond_64_bits() {
+            let c = Column {
+                decimals: Some(3),
+                ..Column::new("", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            };
+            let big = "123456789012345678901234567890.123";
+            assert_eq!(text(d(big), &c), big);
+        }
+
+        #[test]
+        fn decodes_params() {
+            let big = "-98765432109876543210.0123456789";
+            assert_eq!(
+                BigDecimal::try_from(Value::bytes(big.as_bytes())).unwrap(),
+                d(big)
+            );
+            assert_eq!(
+                BigDecimal::try_from(Value::new(ValueInner::Int(-7))).unwrap(),
+                d("-7")
+            );
+            assert_eq!(
+                BigDecimal::try_from(Value::new(ValueInner::Double(2.25))).unwrap(),
+                d("2.25")
+            );
+        }
+
+        #[test]
+        fn rejects_bad_params() {
+            let e = BigDecimal::try_from(Value::bytes(b"abc")).unwrap_err();
+            assert_eq!(e.target(), "BigDecimal");
+            assert!(BigDecimal::try_from(Value::new(ValueInner::Double(f64::INFINITY))).is_err());
+            assert!(BigDecimal::try_from(Value::new(ValueInner::Date(&[]))).is_err());
+        }
+    }
+}
@@ -1,11 +1,27 @@
+use super::OwnedValue;
 use crate::myc::constants::ColumnType;
 use crate::myc::io::ReadMysqlExt;
 use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::FixedOffset;
+use std::convert::TryFrom;
+use std::fmt;
 use std::io;
 
 /// MySQL value as provided when executing prepared statements.
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub struct Value<'a>(ValueInner<'a>);
+///
+/// Values are converted into Rust types with `TryFrom`, which fails with a [`FromValueError`]
+/// rather than panicking if a parameter has an incompatible type or does not fit.
+///
+/// Values also remember the session time zone of the client that sent them, which conversions
+/// into zoned date and time types interpret `DATETIME` and `TIMESTAMP` values in.
+#[derive(PartialEq, Copy, Clone)]
+pub struct Value<'a>(pub(super) ValueInner<'a>, pub(super) FixedOffset);
+
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Value").field(&self.0).finish()
+    }
+}
 
 /// A representation of a concrete, typed MySQL value.
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -43,8 +59,29 @@ impl<'a> Value<'a> {
         self.0
     }
 
+    /// Copy this value out of the packet it arrived in, so it can be kept around for longer.
+    pub fn to_owned(self) -> OwnedValue {
+        OwnedValue::from(self)
+    }
+
+    /// The session time zone of the client that sent this value.
+    ///
+    /// This is the offset of the session's `time_zone` variable at the time the value was sent,
+    /// or UTC if that does not name a fixed offset.
+    pub fn time_zone(&self) -> FixedOffset {
+        self.1
+    }
+
+    pub(crate) fn new(inner: ValueInner<'a>) -> Self {
+        Value(inner, FixedOffset::east_opt(0).unwrap())
+    }
+
+    pub(crate) fn in_time_zone(self, time_zone: FixedOffset) -> Self {
+        Value(self.0, time_zone)
+    }
+
     pub(crate) fn null() -> Self {
-        Value(ValueInner::NULL)
+        Value::new(ValueInner::NULL)
     }
 
     /// Returns true if this is a NULL value
@@ -57,11 +94,11 @@ impl<'a> Value<'a> {
         ct: ColumnType,
         unsigned: bool,
     ) -> io::Result<Self> {
-        ValueInner::parse_from(input, ct, unsigned).map(Value)
+        ValueInner::parse_from(input, ct, unsigned).map(Value::new)
     }
 
     pub(crate) fn bytes(input: &'a [u8]) -> Value<'a> {
-        Value(ValueInner::Bytes(input))
+        Value::new(ValueInner::Bytes(input))
     }
 }
 
@@ -146,14 +183,17 @@ impl<'a> ValueInner<'a> {
             }
             ColumnType::MYSQL_TYPE_TIMESTAMP | ColumnType::MYSQL_TYPE_DATETIME => {
                 let len = input.read_u8()?;
+                check_len(ct, len, &[0, 4, 7, 11])?;
                 Ok(ValueInner::Datetime(read_bytes!(input, len)?))
             }
             ColumnType::MYSQL_TYPE_DATE => {
                 let len = input.read_u8()?;
+                check_len(ct, len, &[0, 4, 7, 11])?;
                 Ok(ValueInner::Date(read_bytes!(input, len)?))
             }
             ColumnType::MYSQL_TYPE_TIME => {
                 let len = input.read_u8()?;
+                check_len(ct, len, &[0, 8, 12])?;
                 Ok(ValueInner::Time(read_bytes!(input, len)?))
             }
             ColumnType::MYSQL_TYPE_NULL => Ok(ValueInner::NULL),
@@ -165,117 +205,440 @@ impl<'a> ValueInner<'a> {
     }
 }
 
-// NOTE: these should all be TryFrom
-macro_rules! impl_into {
-    ($t:ty, $($variant:path),*) => {
-        impl<'a> From<Value<'a>> for $t {
-            fn from(val: Value<'a>) -> Self {
+fn check_len(ct: ColumnType, len: u8, valid: &[u8]) -> io::Result<()> {
+    if valid.contains(&len) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} value of invalid length {}", ct, len),
+        ))
+    }
+}
+
+/// An error converting a [`Value`] into a Rust type.
+///
+/// This is returned when a parameter has a type that does not map to the requested type, or when
+/// its value does not fit in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromValueError {
+    target: &'static str,
+    value: String,
+    reason: Option<String>,
+}
+
+impl FromValueError {
+    pub(crate) fn new(value: ValueInner<'_>, target: &'static str) -> Self {
+        let value = match value {
+            ValueInner::Bytes(b) => format!("Bytes({:?})", String::from_utf8_lossy(b)),
+            v => format!("{:?}", v),
+        };
+        FromValueError {
+            target,
+            value,
+            reason: None,
+        }
+    }
+
+    pub(crate) fn because<R: fmt::Display>(mut self, reason: R) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    pub(crate) fn for_target(mut self, target: &'static str) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// The name of the type the value could not be converted into.
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+}
+
+impl fmt::Display for FromValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot convert {} to {}", self.value, self.target)?;
+        if let Some(ref reason) = self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FromValueError {}
+
+impl From<FromValueError> for io::Error {
+    fn from(e: FromValueError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Integers can be read from integer parameters of any width and signedness, as long as the value
+/// fits, and from text parameters that hold a number.
+macro_rules! impl_try_from_int {
+    ($($t:ty),*) => {$(
+        impl<'a> TryFrom<Value<'a>> for $t {
+            type Error = FromValueError;
+            #[allow(clippy::useless_conversion)]
+            fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+                let err = || FromValueError::new(val.0, stringify!($t));
                 match val.0 {
-                    $($variant(v) => v as $t),*,
-                    v => panic!(concat!("invalid type conversion from {:?} to ", stringify!($t)), v)
+                    ValueInner::Int(i) => <$t>::try_from(i).map_err(|_| err().because("out of range")),
+                    ValueInner::UInt(u) => <$t>::try_from(u).map_err(|_| err().because("out of range")),
+                    ValueInner::Bytes(_) => text(val, stringify!($t))?
+                        .trim()
+                        .parse()
+                        .map_err(|e| err().because(e)),
+                    _ => Err(err()),
                 }
             }
         }
+    )*};
+}
+
+impl_try_from_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Floats can also be read from integer parameters and from text parameters that hold a number.
+impl<'a> TryFrom<Value<'a>> for f64 {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        match val.0 {
+            ValueInner::Double(f) => Ok(f),
+            ValueInner::Int(i) => Ok(i as f64),
+            ValueInner::UInt(u) => Ok(u as f64),
+            ValueInner::Bytes(_) => text(val, "f64")?
+                .trim()
+                .parse()
+                .map_err(|e| FromValueError::new(val.0, "f64").because(e)),
+            _ => Err(FromValueError::new(val.0, "f64")),
+        }
     }
 }
 
-impl_into!(u8, ValueInner::UInt, ValueInner::Int);
-impl_into!(u16, ValueInner::UInt, ValueInner::Int);
-impl_into!(u32, ValueInner::UInt, ValueInner::Int);
-impl_into!(u64, ValueInner::UInt);
-impl_into!(i8, ValueInner::UInt, ValueInner::Int);
-impl_into!(i16, ValueInner::UInt, ValueInner::Int);
-impl_into!(i32, ValueInner::UInt, ValueInner::Int);
-impl_into!(i64, ValueInner::Int);
-impl_into!(f32, ValueInner::Double);
-impl_into!(f64, ValueInner::Double);
-impl_into!(&'a [u8], ValueInner::Bytes);
+impl<'a> TryFrom<Value<'a>> for f32 {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let f = f64::try_from(val).map_err(|e| e.for_target("f32"))?;
+        if f.is_finite() && (f as f32).is_infinite() {
+            return Err(FromValueError::new(val.0, "f32").because("out of range"));
+        }
+        Ok(f as f32)
+    }
+}
 
-impl<'a> From<Value<'a>> for &'a str {
-    fn from(val: Value<'a>) -> Self {
-        if let ValueInner::Bytes(v) = val.0 {
-            ::std::str::from_utf8(v).unwrap()
-        } else {
-            panic!("invalid type conversion from {:?} to string", val)
+impl<'a> TryFrom<Value<'a>> for &'a [u8] {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        match val.0 {
+            ValueInner::Bytes(b) => Ok(b),
+            _ => Err(FromValueError::new(val.0, "&[u8]")),
         }
     }
 }
 
-use chrono::{NaiveDate, NaiveDateTime};
-impl<'a> From<Value<'a>> for NaiveDate {
-    fn from(val: Value<'a>) -> Self {
-        if let ValueInner::Date(mut v) = val.0 {
-            assert_eq!(v.len(), 4);
-            NaiveDate::from_ymd(
-                i32::from(v.read_u16::<LittleEndian>().unwrap()),
-                u32::from(v.read_u8().unwrap()),
-                u32::from(v.read_u8().unwrap()),
-            )
-        } else {
-            panic!("invalid type conversion from {:?} to date", val)
+impl<'a> TryFrom<Value<'a>> for &'a str {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        text(val, "&str")
+    }
+}
+
+/// The text of a `Bytes` parameter, for a conversion into `target`.
+fn text<'a>(val: Value<'a>, target: &'static str) -> Result<&'a str, FromValueError> {
+    match val.0 {
+        ValueInner::Bytes(b) => {
+            std::str::from_utf8(b).map_err(|e| FromValueError::new(val.0, target).because(e))
         }
+        _ => Err(FromValueError::new(val.0, target)),
     }
 }
 
-impl<'a> From<Value<'a>> for NaiveDateTime {
-    fn from(val: Value<'a>) -> Self {
-        if let ValueInner::Datetime(mut v) = val.0 {
-            assert!(v.len() == 7 || v.len() == 11);
-            let d = NaiveDate::from_ymd(
-                i32::from(v.read_u16::<LittleEndian>().unwrap()),
-                u32::from(v.read_u8().unwrap()),
-                u32::from(v.read_u8().unwrap()),
-            );
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+impl<'a> TryFrom<Value<'a>> for NaiveDate {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let dt = RawDatetime::from_value(val, "NaiveDate")?;
+        if !dt.is_date() {
+            return Err(FromValueError::new(val.0, "NaiveDate").because("value has a time of day"));
+        }
+        dt.to_naive()
+            .map(|dt| dt.date())
+            .ok_or_else(|| FromValueError::new(val.0, "NaiveDate").because("invalid date"))
+    }
+}
 
-            let h = u32::from(v.read_u8().unwrap());
-            let m = u32::from(v.read_u8().unwrap());
-            let s = u32::from(v.read_u8().unwrap());
+impl<'a> TryFrom<Value<'a>> for NaiveDateTime {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        RawDatetime::from_value(val, "NaiveDateTime")?
+            .to_naive()
+            .ok_or_else(|| {
+                FromValueError::new(val.0, "NaiveDateTime").because("invalid date or time")
+            })
+    }
+}
 
-            if v.len() == 11 {
-                let us = v.read_u32::<LittleEndian>().unwrap();
-                d.and_hms_micro(h, m, s, us)
-            } else {
-                d.and_hms(h, m, s)
-            }
-        } else {
-            panic!("invalid type conversion from {:?} to datetime", val)
+/// Zoned datetimes are read in the session time zone of the client that sent them.
+impl<'a> TryFrom<Value<'a>> for DateTime<FixedOffset> {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        NaiveDateTime::try_from(val)
+            .map(|dt| val.1.from_local_datetime(&dt).unwrap())
+            .map_err(|e| e.for_target("DateTime<FixedOffset>"))
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for DateTime<Utc> {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        DateTime::<FixedOffset>::try_from(val)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|e| e.for_target("DateTime<Utc>"))
+    }
+}
+
+/// A `TIME` is only a time of day if it is positive and shorter than a day.
+impl<'a> TryFrom<Value<'a>> for NaiveTime {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let t = RawTime::from_value(val, "NaiveTime")?;
+        let secs = t.abs_seconds();
+        if t.negative || secs >= 86_400 {
+            return Err(FromValueError::new(val.0, "NaiveTime").because("not a time of day"));
         }
+        Ok(NaiveTime::from_num_seconds_from_midnight_opt(secs as u32, t.micros * 1_000).unwrap())
     }
 }
 
-use std::time::Duration;
-impl<'a> From<Value<'a>> for Duration {
-    fn from(val: Value<'a>) -> Self {
-        if let ValueInner::Time(mut v) = val.0 {
-            assert!(v.is_empty() || v.len() == 8 || v.len() == 12);
+/// Negative `TIME`s cannot be represented, and are an error.
+impl<'a> TryFrom<Value<'a>> for std::time::Duration {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let t = RawTime::from_value(val, "Duration")?;
+        if t.negative {
+            return Err(FromValueError::new(val.0, "Duration").because("negative time"));
+        }
+        Ok(std::time::Duration::new(t.abs_seconds(), t.micros * 1_000))
+    }
+}
 
-            if v.is_empty() {
-                return Duration::from_secs(0);
-            }
+impl<'a> TryFrom<Value<'a>> for Duration {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let t = RawTime::from_value(val, "msql_srv::Duration")?;
+        Ok(Duration::new(t.negative, t.abs_seconds(), t.micros))
+    }
+}
 
-            let neg = v.read_u8().unwrap();
-            if neg != 0u8 {
-                unimplemented!();
-            }
+/// A MySQL `TIME` value.
+///
+/// Unlike a time of day, a `TIME` can be negative and span more than a day, since MySQL also uses
+/// it for elapsed time and for the difference between two points in time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Duration {
+    negative: bool,
+    seconds: u64,
+    micros: u32,
+}
 
-            let days = u64::from(v.read_u32::<LittleEndian>().unwrap());
-            let hours = u64::from(v.read_u8().unwrap());
-            let minutes = u64::from(v.read_u8().unwrap());
-            let seconds = u64::from(v.read_u8().unwrap());
-            let micros = if v.len() == 12 {
-                v.read_u32::<LittleEndian>().unwrap()
-            } else {
-                0
-            };
-
-            Duration::new(
-                days * 86_400 + hours * 3_600 + minutes * 60 + seconds,
-                micros * 1_000,
-            )
-        } else {
-            panic!("invalid type conversion from {:?} to datetime", val)
+impl Duration {
+    /// A duration of `seconds` and `micros` microseconds, negated if `negative` is set.
+    ///
+    /// Microseconds beyond a second carry over into `seconds`.
+    pub fn new(negative: bool, seconds: u64, micros: u32) -> Self {
+        let seconds = seconds + u64::from(micros / 1_000_000);
+        let micros = micros % 1_000_000;
+        Duration {
+            negative: negative && (seconds != 0 || micros != 0),
+            seconds,
+            micros,
         }
     }
+
+    /// True if this duration is less than zero.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The whole days of this duration, ignoring its sign.
+    pub fn days(&self) -> u64 {
+        self.seconds / 86_400
+    }
+
+    /// The hours of this duration beyond its whole days, from 0 to 23.
+    pub fn hours(&self) -> u8 {
+        (self.seconds % 86_400 / 3_600) as u8
+    }
+
+    /// The minutes of this duration beyond its whole hours, from 0 to 59.
+    pub fn minutes(&self) -> u8 {
+        (self.seconds % 3_600 / 60) as u8
+    }
+
+    /// The seconds of this duration beyond its whole minutes, from 0 to 59.
+    pub fn seconds(&self) -> u8 {
+        (self.seconds % 60) as u8
+    }
+
+    /// The fractional part of this duration, in microseconds.
+    pub fn microseconds(&self) -> u32 {
+        self.micros
+    }
+
+    /// The magnitude of this duration.
+    pub fn abs(&self) -> std::time::Duration {
+        std::time::Duration::new(self.seconds, self.micros * 1_000)
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(d: std::time::Duration) -> Self {
+        Duration::new(false, d.as_secs(), d.subsec_micros())
+    }
+}
+
+/// Formats the duration the way MySQL does, as `[-]HH:MM:SS[.ffffff]` with as many hours as
+/// needed.
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}",
+            if self.negative { "-" } else { "" },
+            self.seconds / 3_600,
+            self.minutes(),
+            self.seconds()
+        )?;
+        if self.micros != 0 {
+            write!(f, ".{:06}", self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields of a binary-encoded `DATE`, `DATETIME` or `TIMESTAMP` value.
+///
+/// Trailing fields that the client left out are zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RawDatetime {
+    pub(super) year: u16,
+    pub(super) month: u8,
+    pub(super) day: u8,
+    pub(super) hour: u8,
+    pub(super) minute: u8,
+    pub(super) second: u8,
+    pub(super) micros: u32,
+}
+
+impl RawDatetime {
+    /// Parse a value of length 0, 4, 7 or 11.
+    pub(super) fn parse(mut v: &[u8]) -> Option<Self> {
+        let mut dt = RawDatetime::default();
+        if !matches!(v.len(), 0 | 4 | 7 | 11) {
+            return None;
+        }
+        if !v.is_empty() {
+            dt.year = v.read_u16::<LittleEndian>().ok()?;
+            dt.month = v.read_u8().ok()?;
+            dt.day = v.read_u8().ok()?;
+        }
+        if !v.is_empty() {
+            dt.hour = v.read_u8().ok()?;
+            dt.minute = v.read_u8().ok()?;
+            dt.second = v.read_u8().ok()?;
+        }
+        if !v.is_empty() {
+            dt.micros = v.read_u32::<LittleEndian>().ok()?;
+        }
+        Some(dt)
+    }
+
+    /// The fields of a `Date` or `Datetime` parameter, for a conversion into `target`.
+    pub(super) fn from_value(val: Value<'_>, target: &'static str) -> Result<Self, FromValueError> {
+        match val.0 {
+            ValueInner::Date(v) | ValueInner::Datetime(v) => RawDatetime::parse(v)
+                .ok_or_else(|| FromValueError::new(val.0, target).because("malformed value")),
+            _ => Err(FromValueError::new(val.0, target)),
+        }
+    }
+
+    /// True if this has no time of day.
+    pub(super) fn is_date(&self) -> bool {
+        self.hour == 0 && self.minute == 0 && self.second == 0 && self.micros == 0
+    }
+
+    /// This date and time, if it is a valid one.
+    pub(super) fn to_naive(self) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(
+            i32::from(self.year),
+            u32::from(self.month),
+            u32::from(self.day),
+        )?
+        .and_hms_micro_opt(
+            u32::from(self.hour),
+            u32::from(self.minute),
+            u32::from(self.second),
+            self.micros,
+        )
+    }
+}
+
+/// The fields of a binary-encoded `TIME` value.
+///
+/// The hours, minutes and seconds are not checked against their usual ranges, and simply add up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RawTime {
+    pub(super) negative: bool,
+    pub(super) days: u32,
+    pub(super) hours: u8,
+    pub(super) minutes: u8,
+    pub(super) seconds: u8,
+    pub(super) micros: u32,
+}
+
+impl RawTime {
+    /// Parse a value of length 0, 8 or 12.
+    pub(super) fn parse(mut v: &[u8]) -> Option<Self> {
+        let mut t = RawTime::default();
+        if !matches!(v.len(), 0 | 8 | 12) {
+            return None;
+        }
+        if !v.is_empty() {
+            t.negative = v.read_u8().ok()? != 0;
+            t.days = v.read_u32::<LittleEndian>().ok()?;
+            t.hours = v.read_u8().ok()?;
+            t.minutes = v.read_u8().ok()?;
+            t.seconds = v.read_u8().ok()?;
+        }
+        if !v.is_empty() {
+            t.micros = v.read_u32::<LittleEndian>().ok()?;
+        }
+        Some(t)
+    }
+
+    /// The fields of a `Time` parameter, for a conversion into `target`.
+    pub(super) fn from_value(val: Value<'_>, target: &'static str) -> Result<Self, FromValueError> {
+        let err = || FromValueError::new(val.0, target);
+        let t = match val.0 {
+            ValueInner::Time(v) => {
+                RawTime::parse(v).ok_or_else(|| err().because("malformed value"))?
+            }
+            _ => return Err(err()),
+        };
+        if t.micros >= 1_000_000 {
+            return Err(err().because("microseconds out of range"));
+        }
+        Ok(t)
+    }
+
+    /// The whole seconds of this time, ignoring its sign.
+    pub(super) fn abs_seconds(&self) -> u64 {
+        u64::from(self.days) * 86_400
+            + u64::from(self.hours) * 3_600
+            + u64::from(self.minutes) * 60
+            + u64::from(self.seconds)
+    }
 }
 
 #[cfg(test)]
@@ -287,6 +650,7 @@ mod tests {
     use crate::{Column, ColumnFlags, ColumnType};
     use chrono::{self, TimeZone};
     use myc::proto::MySerialize;
+    use std::convert::TryFrom;
     use std::time;
 
     macro_rules! rt {
@@ -297,12 +661,7 @@ mod tests {
             #[test]
             fn $name() {
                 let mut data = Vec::new();
-                let mut col = Column {
-                    table: String::new(),
-                    column: String::new(),
-                    coltype: $ct,
-                    colflags: ColumnFlags::empty(),
-                };
+                let mut col = Column::new("", $ct);
 
                 if !$sig {
                     col.colflags.insert(ColumnFlags::UNSIGNED_FLAG);
@@ -311,7 +670,7 @@ mod tests {
                 let v: $t = $v;
                 myc::value::Value::from(v).serialize(&mut data);
                 assert_eq!(
-                    Into::<$t>::into(Value::parse_from(&mut &data[..], $ct, !$sig).unwrap()),
+                    <$t>::try_from(Value::parse_from(&mut &data[..], $ct, !$sig).unwrap()).unwrap(),
                     v
                 );
             }
@@ -466,4 +825,175 @@ mod tests {
         ColumnType::MYSQL_TYPE_BLOB
     );
     rt!(string, &str, "foobar", ColumnType::MYSQL_TYPE_STRING);
+
+    #[test]
+    fn zoned_datetimes_are_in_the_session_time_zone() {
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let v = Value::new(super::ValueInner::Datetime(&[0xe8, 0x07, 3, 1, 1, 30, 0]));
+        let utc = chrono::Utc.ymd(2024, 3, 1).and_hms(1, 30, 0);
+        assert_eq!(chrono::DateTime::<chrono::Utc>::try_from(v).unwrap(), utc);
+
+        let v = v.in_time_zone(tz);
+        assert_eq!(v.time_zone(), tz);
+        let local = chrono::DateTime::<chrono::FixedOffset>::try_from(v).unwrap();
+        assert_eq!(local.offset(), &tz);
+        assert_eq!(local, utc - chrono::Duration::hours(2));
+
+        let v = Value::new(super::ValueInner::Datetime(&[0xe8, 0x07, 2, 30]));
+        let e = chrono::DateTime::<chrono::Utc>::try_from(v).unwrap_err();
+        assert_eq!(e.target(), "DateTime<Utc>");
+        assert!(e.to_string().ends_with(": invalid date or time"));
+        let v = Value::new(super::ValueInner::Int(0));
+        assert!(chrono::DateTime::<chrono::Utc>::try_from(v).is_err());
+    }
+
+    #[test]
+    fn dates_and_times() {
+        use super::ValueInner::{Date, Datetime, Time};
+        let date = chrono::NaiveDate::from_ymd(2024, 2, 29);
+
+        let v = Value::new(Date(&[0xe8, 0x07, 2, 29]));
+        assert_eq!(chrono::NaiveDate::try_from(v).unwrap(), date);
+        assert_eq!(
+            chrono::NaiveDateTime::try_from(v).unwrap(),
+            date.and_hms(0, 0, 0)
+        );
+        let v = Value::new(Datetime(&[
+            0xe8, 0x07, 2, 29, 13, 14, 15, 0x40, 0xe2, 0x01, 0,
+        ]));
+        assert_eq!(
+            chrono::NaiveDateTime::try_from(v).unwrap(),
+            date.and_hms_micro(13, 14, 15, 123_456)
+        );
+        let e = chrono::NaiveDate::try_from(v).unwrap_err();
+        assert!(e.to_string().ends_with(": value has a time of day"));
+
+        let v = Value::new(Time(&[0, 0, 0, 0, 0, 13, 14, 15, 1, 0, 0, 0]));
+        assert_eq!(
+            chrono::NaiveTime::try_from(v).unwrap(),
+            chrono::NaiveTime::from_hms_micro(13, 14, 15, 1)
+        );
+        assert_eq!(
+            chrono::NaiveTime::try_from(Value::new(Time(&[]))).unwrap(),
+            chrono::NaiveTime::from_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn signed_durations() {
+        use super::ValueInner::Time;
+        use crate::Duration;
+
+        // -1 day, 25 hours: hours beyond a day carry over
+        let v = Value::new(Time(&[1, 1, 0, 0, 0, 25, 0, 3]));
+        let d = Duration::try_from(v).unwrap();
+        assert!(d.is_negative());
+        assert_eq!(
+            (d.days(), d.hours(), d.minutes(), d.seconds()),
+            (2, 1, 0, 3)
+        );
+        assert_eq!(d, Duration::new(true, 2 * 86_400 + 3_603, 0));
+        assert_eq!(d.to_string(), "-49:00:03");
+        assert!(time::Duration::try_from(v).is_err());
+        assert!(chrono::NaiveTime::try_from(v).is_err());
+
+        let v = Value::new(Time(&[0, 0, 0, 0, 0, 0, 0, 1, 5, 0, 0, 0]));
+        let d = Duration::try_from(v).unwrap();
+        assert_eq!(d.abs(), time::Duration::new(1, 5_000));
+        assert_eq!(d.to_string(), "00:00:01.000005");
+        assert_eq!(Duration::from(d.abs()), d);
+
+        assert_eq!(Duration::new(true, 0, 0), Duration::default());
+        assert_eq!(
+            Duration::new(false, 1, 2_000_003),
+            Duration::new(false, 3, 3)
+        );
+    }
+
+    #[test]
+    fn malformed_dates_and_times() {
+        use super::ValueInner::{Date, Datetime, Int, Time};
+
+        let e = chrono::NaiveDate::try_from(Value::new(Date(&[0xe8, 0x07, 2]))).unwrap_err();
+        assert_eq!(e.target(), "NaiveDate");
+        assert!(e.to_string().ends_with(": malformed value"));
+        let e = chrono::NaiveDate::try_from(Value::new(Date(&[]))).unwrap_err();
+        assert!(e.to_string().ends_with(": invalid date"));
+        let v = Value::new(Datetime(&[0xe8, 0x07, 2, 29, 24, 0, 0]));
+        assert!(chrono::NaiveDateTime::try_from(v).is_err());
+        assert!(chrono::NaiveDateTime::try_from(Value::new(Int(0))).is_err());
+
+        let v = Value::new(Time(&[0, 0, 0, 0, 0, 1, 2]));
+        let e = crate::Duration::try_from(v).unwrap_err();
+        assert_eq!(e.target(), "msql_srv::Duration");
+        let v = Value::new(Time(&[0, 0, 0, 0, 0, 1, 2, 3, 0x40, 0x42, 0x0f, 0]));
+        assert!(crate::Duration::try_from(v).is_err());
+        let v = Value::new(Time(&[0, 1, 0, 0, 0, 0, 0, 0]));
+        assert!(chrono::NaiveTime::try_from(v).is_err());
+        assert!(time::Duration::try_from(Value::new(Date(&[]))).is_err());
+    }
+
+    #[test]
+    fn integers_are_range_checked() {
+        use super::ValueInner::{Double, Int, UInt};
+
+        assert_eq!(u8::try_from(Value::new(Int(255))).unwrap(), 255);
+        let e = u8::try_from(Value::new(Int(256))).unwrap_err();
+        assert_eq!(e.to_string(), "cannot convert Int(256) to u8: out of range");
+        assert!(u32::try_from(Value::new(Int(-1))).is_err());
+        assert!(i8::try_from(Value::new(UInt(128))).is_err());
+        assert_eq!(i64::try_from(Value::new(UInt(7))).unwrap(), 7);
+        assert_eq!(u64::try_from(Value::new(Int(7))).unwrap(), 7);
+        assert!(i64::try_from(Value::new(UInt(u64::MAX))).is_err());
+        assert!(i32::try_from(Value::new(Double(1.0))).is_err());
+        assert!(u16::try_from(Value::null()).is_err());
+    }
+
+    #[test]
+    fn numbers_are_parsed_from_text() {
+        assert_eq!(i16::try_from(Value::bytes(b" -42 ")).unwrap(), -42);
+        assert_eq!(
+            u64::try_from(Value::bytes(b"18446744073709551615")).unwrap(),
+            u64::MAX
+        );
+        let e = u8::try_from(Value::bytes(b"300")).unwrap_err();
+        assert_eq!(e.target(), "u8");
+        assert!(e
+            .to_string()
+            .starts_with("cannot convert Bytes(\"300\") to u8: "));
+        assert!(i32::try_from(Value::bytes(b"4.2")).is_err());
+        assert!(i32::try_from(Value::bytes(b"\xff")).is_err());
+
+        assert_eq!(f64::try_from(Value::bytes(b"2.5e3")).unwrap(), 2500.0);
+        assert_eq!(f32::try_from(Value::bytes(b"0.5")).unwrap(), 0.5);
+        assert!(f64::try_from(Value::bytes(b"abc")).is_err());
+    }
+
+    #[test]
+    fn floats() {
+        use super::ValueInner::{Date, Double, Int, UInt};
+
+        assert_eq!(f64::try_from(Value::new(Int(-3))).unwrap(), -3.0);
+        assert_eq!(f32::try_from(Value::new(UInt(3))).unwrap(), 3.0);
+        assert_eq!(
+            f32::try_from(Value::new(Double(f64::INFINITY))).unwrap(),
+            f32::INFINITY
+        );
+        let e = f32::try_from(Value::new(Double(1e300))).unwrap_err();
+        assert_eq!(e.target(), "f32");
+        assert!(f64::try_from(Value::new(Date(&[]))).is_err());
+        assert_eq!(f32::try_from(Value::null()).unwrap_err().target(), "f32");
+    }
+
+    #[test]
+    fn strings_and_bytes() {
+        assert_eq!(<&str>::try_from(Value::bytes(b"abc")).unwrap(), "abc");
+        let e = <&str>::try_from(Value::bytes(b"a\xffc")).unwrap_err();
+        assert_eq!(e.target(), "&str");
+        assert!(e.to_string().contains("invalid utf-8"));
+        assert!(<&str>::try_from(Value::new(super::ValueInner::Int(1))).is_err());
+
+        assert_eq!(<&[u8]>::try_from(Value::bytes(b"a\xff")).unwrap(), b"a\xff");
+        assert!(<&[u8]>::try_from(Value::null()).is_err());
+    }
 }
@@ -1,9 +1,42 @@
+use super::decode::RawDatetime;
+use super::OwnedValue;
 use crate::myc::constants::{ColumnFlags, ColumnType};
 use crate::myc::io::WriteMysqlExt;
 use crate::Column;
 use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::FixedOffset;
 use std::io::{self, Write};
 
+/// The column a value is written to, and what is known about the client it is written for.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteContext<'a> {
+    column: &'a Column,
+    time_zone: FixedOffset,
+}
+
+impl<'a> WriteContext<'a> {
+    /// A context for writing to `column`, for a client whose session time zone is `time_zone`.
+    pub fn new(column: &'a Column, time_zone: FixedOffset) -> Self {
+        WriteContext { column, time_zone }
+    }
+
+    /// The column the value is written to.
+    pub fn column(&self) -> &'a Column {
+        self.column
+    }
+
+    /// The client's session time zone, which `TIMESTAMP` values are written in.
+    pub fn time_zone(&self) -> FixedOffset {
+        self.time_zone
+    }
+
+    /// A context for writing to `column` for a client whose session is in UTC.
+    #[cfg(test)]
+    pub(crate) fn utc(column: &'a Column) -> Self {
+        Self::new(column, FixedOffset::east_opt(0).unwrap())
+    }
+}
+
 /// Implementors of this trait can be sent as a single resultset value to a MySQL/MariaDB client.
 pub trait ToMysqlValue {
     /// Encode value using the text-based protocol.
@@ -12,6 +45,24 @@ pub trait ToMysqlValue {
     /// Encode value using the binary protocol.
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()>;
 
+    /// Encode value using the text-based protocol, for the column and client in `cx`.
+    ///
+    /// This defaults to [`to_mysql_text`](ToMysqlValue::to_mysql_text), and only needs to be
+    /// overridden by values whose text form depends on the column, such as exact decimals, or on
+    /// the client, such as zoned datetimes.
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        let _ = cx;
+        self.to_mysql_text(w)
+    }
+
+    /// Encode value using the binary protocol, for the column and client in `cx`.
+    ///
+    /// This defaults to [`to_mysql_bin`](ToMysqlValue::to_mysql_bin), and only needs to be
+    /// overridden by values whose encoding depends on the client, such as zoned datetimes.
+    fn to_mysql_bin_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        self.to_mysql_bin(w, cx.column())
+    }
+
     /// Is this value NULL?
     fn is_null(&self) -> bool {
         false
@@ -28,7 +79,7 @@ macro_rules! mysql_text_trivial {
 }
 
 use std::fmt;
-fn bad<V: fmt::Debug>(v: V, c: &Column) -> io::Error {
+pub(super) fn bad<V: fmt::Debug>(v: V, c: &Column) -> io::Error {
     io::Error::new(
         io::ErrorKind::InvalidData,
         format!("tried to use {:?} as {:?}", v, c.coltype),
@@ -47,6 +98,23 @@ where
         }
     }
 
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        if let Some(ref v) = *self {
+            v.to_mysql_text_for(w, cx)
+        } else {
+            w.write_u8(0xFB)
+        }
+    }
+
+    fn to_mysql_bin_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        if let Some(ref v) = *self {
+            v.to_mysql_bin_for(w, cx)
+        } else {
+            // should be handled by NULL map
+            unreachable!();
+        }
+    }
+
     fn to_mysql_bin<W: Write>(&self, w: &mut W, ct: &Column) -> io::Result<()> {
         if let Some(ref v) = *self {
             v.to_mysql_bin(w, ct)
@@ -406,12 +474,18 @@ where
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
         (*self).to_mysql_text(w)
     }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        (*self).to_mysql_text_for(w, cx)
+    }
+    fn to_mysql_bin_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        (*self).to_mysql_bin_for(w, cx)
+    }
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
         (*self).to_mysql_bin(w, c)
     }
 }
 
-use chrono::{self, Datelike, NaiveDate, NaiveDateTime, Timelike};
+use chrono::{self, DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Timelike};
 impl ToMysqlValue for NaiveDate {
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
         w.write_lenenc_str(
@@ -494,6 +568,32 @@ impl ToMysqlValue for NaiveDateTime {
     }
 }
 
+/// Zoned datetimes are written to `TIMESTAMP` columns in the client's session time zone, like
+/// MySQL does, and in UTC otherwise.
+impl<Tz: TimeZone> ToMysqlValue for DateTime<Tz> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.naive_utc().to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        self.naive_utc().to_mysql_bin(w, c)
+    }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        local_time(self, cx).to_mysql_text(w)
+    }
+    fn to_mysql_bin_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        local_time(self, cx).to_mysql_bin(w, cx.column())
+    }
+}
+
+/// The wall-clock time of `v` as it is written for `cx`.
+fn local_time<Tz: TimeZone>(v: &DateTime<Tz>, cx: &WriteContext<'_>) -> NaiveDateTime {
+    if cx.column().coltype == ColumnType::MYSQL_TYPE_TIMESTAMP {
+        v.with_timezone(&cx.time_zone()).naive_local()
+    } else {
+        v.naive_utc()
+    }
+}
+
 use std::time::Duration;
 impl ToMysqlValue for Duration {
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
@@ -552,8 +652,139 @@ impl ToMysqlValue for Duration {
     }
 }
 
+/// Like MySQL's own `TIME` columns, these are limited to 838:59:59.999999 in either direction.
+impl ToMysqlValue for super::Duration {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        check_time_range(self)?;
+        w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_TIME => {
+                check_time_range(self)?;
+                let us = self.microseconds();
+                if self.abs().as_secs() == 0 && us == 0 {
+                    return w.write_u8(0u8);
+                }
+                w.write_u8(if us != 0 { 12u8 } else { 8u8 })?;
+                w.write_u8(u8::from(self.is_negative()))?;
+                w.write_u32::<LittleEndian>(self.days() as u32)?;
+                w.write_u8(self.hours())?;
+                w.write_u8(self.minutes())?;
+                w.write_u8(self.seconds())?;
+                if us != 0 {
+                    w.write_u32::<LittleEndian>(us)?;
+                }
+                Ok(())
+            }
+            _ => Err(bad(self, c)),
+        }
+    }
+}
+
+fn check_time_range(d: &super::Duration) -> io::Result<()> {
+    if d.abs().as_secs() > 838 * 3_600 + 59 * 60 + 59 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is out of range for MySQL", d),
+        ));
+    }
+    Ok(())
+}
+
+/// The date and time fields of a `mysql_common` or owned value, which unlike chrono's types may
+/// hold MySQL's zero date.
+impl ToMysqlValue for RawDatetime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut s = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        if self.micros != 0 {
+            s.push_str(&format!(".{:06}", self.micros));
+        }
+        w.write_lenenc_str(s.as_bytes()).map(|_| ())
+    }
+
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        match cx.column().coltype {
+            ColumnType::MYSQL_TYPE_DATE => w
+                .write_lenenc_str(
+                    format!("{:04}-{:02}-{:02}", self.year, self.month, self.day).as_bytes(),
+                )
+                .map(|_| ()),
+            _ => self.to_mysql_text(w),
+        }
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        let zero_date = self.year == 0 && self.month == 0 && self.day == 0;
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_DATE => {
+                if zero_date {
+                    return w.write_u8(0u8);
+                }
+                w.write_u8(4u8)?;
+                w.write_u16::<LittleEndian>(self.year)?;
+                w.write_u8(self.month)?;
+                w.write_u8(self.day)
+            }
+            ColumnType::MYSQL_TYPE_DATETIME | ColumnType::MYSQL_TYPE_TIMESTAMP => {
+                if zero_date && self.is_date() {
+                    return w.write_u8(0u8);
+                }
+                w.write_u8(if self.micros != 0 { 11u8 } else { 7u8 })?;
+                w.write_u16::<LittleEndian>(self.year)?;
+                w.write_u8(self.month)?;
+                w.write_u8(self.day)?;
+                w.write_u8(self.hour)?;
+                w.write_u8(self.minute)?;
+                w.write_u8(self.second)?;
+                if self.micros != 0 {
+                    w.write_u32::<LittleEndian>(self.micros)?;
+                }
+                Ok(())
+            }
+            _ => Err(bad(self, c)),
+        }
+    }
+}
+
+/// Write an integer to a column of any integer type it fits in.
+///
+/// We *could* just delegate to the i64 impl here, but then you couldn't use a
+/// `myc::value::Value` and return, say, a short. Also, myc uses i64 for *every* number type,
+/// *except* u64, so we even need to coerce across unsigned :( The good news is that our impls for
+/// numbers auto-upgrade to wider coltypes, so we can just downcast to the smallest containing
+/// type, and then call on that.
+pub(super) fn write_int_bin<W: Write>(n: i64, w: &mut W, c: &Column) -> io::Result<()> {
+    let signed = !c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
+    if signed {
+        if n >= i64::from(i8::MIN) && n <= i64::from(i8::MAX) {
+            (n as i8).to_mysql_bin(w, c)
+        } else if n >= i64::from(i16::MIN) && n <= i64::from(i16::MAX) {
+            (n as i16).to_mysql_bin(w, c)
+        } else if n >= i64::from(i32::MIN) && n <= i64::from(i32::MAX) {
+            (n as i32).to_mysql_bin(w, c)
+        } else {
+            n.to_mysql_bin(w, c)
+        }
+    } else if n < 0 {
+        Err(bad(n, c))
+    } else if n <= i64::from(u8::MAX) {
+        (n as u8).to_mysql_bin(w, c)
+    } else if n <= i64::from(u16::MAX) {
+        (n as u16).to_mysql_bin(w, c)
+    } else if n <= i64::from(u32::MAX) {
+        (n as u32).to_mysql_bin(w, c)
+    } else {
+        // must work since u64::MAX > i64::MAX, and n >= 0
+        (n as u64).to_mysql_bin(w, c)
+    }
+}
+
 impl ToMysqlValue for myc::value::Value {
-    #[allow(clippy::many_single_char_names)]
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
         match *self {
             myc::value::Value::NULL => None::<u8>.to_mysql_text(w),
@@ -562,91 +793,32 @@ impl ToMysqlValue for myc::value::Value {
             myc::value::Value::UInt(n) => n.to_mysql_text(w),
             myc::value::Value::Float(f) => f.to_mysql_text(w),
             myc::value::Value::Double(f) => f.to_mysql_text(w),
-            myc::value::Value::Date(y, mo, d, h, mi, s, us) => {
-                NaiveDate::from_ymd(i32::from(y), u32::from(mo), u32::from(d))
-                    .and_hms_micro(u32::from(h), u32::from(mi), u32::from(s), us)
-                    .to_mysql_text(w)
-            }
-            myc::value::Value::Time(neg, d, h, m, s, us) => {
-                if neg {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "negative times not yet supported",
-                    ));
-                }
-                (chrono::Duration::days(i64::from(d))
-                    + chrono::Duration::hours(i64::from(h))
-                    + chrono::Duration::minutes(i64::from(m))
-                    + chrono::Duration::seconds(i64::from(s))
-                    + chrono::Duration::microseconds(i64::from(us)))
-                .to_std()
-                .expect("only positive times at the moment")
-                .to_mysql_text(w)
+            myc::value::Value::Date(..) | myc::value::Value::Time(..) => {
+                OwnedValue::from(self.clone()).to_mysql_text(w)
             }
         }
     }
 
-    #[allow(clippy::many_single_char_names)]
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        match *self {
+            myc::value::Value::Date(..) => OwnedValue::from(self.clone()).to_mysql_text_for(w, cx),
+            _ => self.to_mysql_text(w),
+        }
+    }
+
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
         match *self {
             myc::value::Value::NULL => unreachable!(),
             myc::value::Value::Bytes(ref bytes) => bytes.to_mysql_bin(w, c),
-            myc::value::Value::Int(n) => {
-                // we *could* just delegate to i64 impl here, but then you couldn't use myc::value::Value
-                // and return, say, a short. also, myc uses i64 for *every* number type, *except*
-                // u64, so we even need to coerce across unsigned :( the good news is that our
-                // impls for numbers auto-upgrade to wider coltypes, so we can just downcast to the
-                // smallest containing type, and then call on that
-                let signed = !c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
-                if signed {
-                    if n >= i64::from(i8::min_value()) && n <= i64::from(i8::max_value()) {
-                        (n as i8).to_mysql_bin(w, c)
-                    } else if n >= i64::from(i16::min_value()) && n <= i64::from(i16::max_value()) {
-                        (n as i16).to_mysql_bin(w, c)
-                    } else if n >= i64::from(i32::min_value()) && n <= i64::from(i32::max_value()) {
-                        (n as i32).to_mysql_bin(w, c)
-                    } else {
-                        n.to_mysql_bin(w, c)
-                    }
-                } else if n < 0 {
-                    Err(bad(self, c))
-                } else if n <= i64::from(u8::max_value()) {
-                    (n as u8).to_mysql_bin(w, c)
-                } else if n <= i64::from(u16::max_value()) {
-                    (n as u16).to_mysql_bin(w, c)
-                } else if n <= i64::from(u32::max_value()) {
-                    (n as u32).to_mysql_bin(w, c)
-                } else {
-                    // must work since u64::max_value() > i64::max_value(), and n >= 0
-                    (n as u64).to_mysql_bin(w, c)
-                }
-            }
+            myc::value::Value::Int(n) => write_int_bin(n, w, c),
             myc::value::Value::UInt(n) => {
                 // we are not as lenient with unsigned ints because the mysql crate isn't either
                 n.to_mysql_bin(w, c)
             }
             myc::value::Value::Float(f) => f.to_mysql_bin(w, c),
             myc::value::Value::Double(f) => f.to_mysql_bin(w, c),
-            myc::value::Value::Date(y, mo, d, h, mi, s, us) => {
-                NaiveDate::from_ymd(i32::from(y), u32::from(mo), u32::from(d))
-                    .and_hms_micro(u32::from(h), u32::from(mi), u32::from(s), us)
-                    .to_mysql_bin(w, c)
-            }
-            myc::value::Value::Time(neg, d, h, m, s, us) => {
-                if neg {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "negative times not yet supported",
-                    ));
-                }
-                (chrono::Duration::days(i64::from(d))
-                    + chrono::Duration::hours(i64::from(h))
-                    + chrono::Duration::minutes(i64::from(m))
-                    + chrono::Duration::seconds(i64::from(s))
-                    + chrono::Duration::microseconds(i64::from(us)))
-                .to_std()
-                .expect("only positive times at the moment")
-                .to_mysql_bin(w, c)
+            myc::value::Value::Date(..) | myc::value::Value::Time(..) => {
+                OwnedValue::from(self.clone()).to_mysql_bin(w, c)
             }
         }
     }
@@ -659,7 +831,7 @@ impl ToMysqlValue for myc::value::Value {
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
-    use super::ToMysqlValue;
+    use super::{ToMysqlValue, WriteContext};
     use crate::myc::value;
     use crate::myc::value::convert::from_value;
     use crate::myc::value::Value;
@@ -753,12 +925,7 @@ mod tests {
                 #[test]
                 fn $name() {
                     let mut data = Vec::new();
-                    let mut col = Column {
-                        table: String::new(),
-                        column: String::new(),
-                        coltype: $ct,
-                        colflags: ColumnFlags::empty(),
-                    };
+                    let mut col = Column::new("", $ct);
 
                     if !$sig {
                         col.colflags.insert(ColumnFlags::UNSIGNED_FLAG);
@@ -939,4 +1106,63 @@ mod tests {
             ColumnType::MYSQL_TYPE_STRING
         );
     }
+
+    #[test]
+    fn zoned_datetimes_are_written_in_the_session_time_zone() {
+        let dt = chrono::FixedOffset::west_opt(3600)
+            .unwrap()
+            .ymd(2024, 2, 29)
+            .and_hms(23, 30, 0);
+        let col = Column::new("", ColumnType::MYSQL_TYPE_TIMESTAMP);
+
+        let mut data = Vec::new();
+        dt.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[1..], b"2024-03-01 00:30:00");
+
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let mut data = Vec::new();
+        dt.to_mysql_text_for(&mut data, &WriteContext::new(&col, tz))
+            .unwrap();
+        assert_eq!(&data[1..], b"2024-03-01 02:30:00");
+
+        let mut data = Vec::new();
+        Some(dt)
+            .to_mysql_bin_for(&mut data, &WriteContext::new(&col, tz))
+            .unwrap();
+        assert_eq!(data, [7, 0xe8, 0x07, 3, 1, 2, 30, 0]);
+
+        let col = Column::new("", ColumnType::MYSQL_TYPE_DATETIME);
+        let mut data = Vec::new();
+        dt.to_mysql_text_for(&mut data, &WriteContext::new(&col, tz))
+            .unwrap();
+        assert_eq!(&data[1..], b"2024-03-01 00:30:00");
+    }
+
+    #[test]
+    fn signed_durations() {
+        use std::convert::TryFrom;
+
+        let col = Column::new("", ColumnType::MYSQL_TYPE_TIME);
+        let d = crate::Duration::new(true, 2 * 86_400 + 3_723, 500);
+
+        let mut data = Vec::new();
+        d.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[1..], b"-49:02:03.000500");
+
+        let mut data = Vec::new();
+        d.to_mysql_bin(&mut data, &col).unwrap();
+        assert_eq!(data, [12, 1, 2, 0, 0, 0, 1, 2, 3, 0xf4, 0x01, 0, 0]);
+        let v = crate::Value::parse_from(&mut &data[..], col.coltype, false).unwrap();
+        assert_eq!(crate::Duration::try_from(v).unwrap(), d);
+
+        let mut data = Vec::new();
+        crate::Duration::default()
+            .to_mysql_bin(&mut data, &col)
+            .unwrap();
+        assert_eq!(data, [0]);
+
+        let too_long = crate::Duration::new(false, 839 * 3_600, 0);
+        assert!(too_long.to_mysql_text(&mut Vec::new()).is_err());
+        assert!(too_long.to_mysql_bin(&mut Vec::new(), &col).is_err());
+    }
 }
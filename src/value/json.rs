@@ -0,0 +1,215 @@
+//! JSON documents, for `JSON` columns and parameters.
+//!
+//! On the wire, JSON values are plain strings, so they can also be written to `BLOB` and string
+//! columns, which is what MariaDB reports its `JSON` columns as.
+
+use super::encode::bad;
+use super::{FromValueError, ToMysqlValue, Value, ValueInner, WriteContext};
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::Column;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// A wrapper that reads and writes any serde-compatible type as a JSON document.
+///
+/// Use it to write a `Serialize` value to a `JSON` column, or to decode a `JSON` parameter into a
+/// `Deserialize` value:
+///
+/// ```no_run
+/// # use msql_srv::{Json, ParamValue};
+/// # use std::convert::TryFrom;
+/// #[derive(serde::Deserialize)]
+/// struct Address<'a> {
+///     city: &'a str,
+/// }
+///
+/// # fn f(param: ParamValue<'_>) -> Result<(), msql_srv::FromValueError> {
+/// let Json(address) = Json::<Address<'_>>::try_from(param.value)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Json<T>(pub T);
+
+fn write_json<W: Write, T: Serialize + ?Sized>(w: &mut W, v: &T) -> io::Result<()> {
+    let doc = serde_json::to_vec(v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    w.write_lenenc_str(&doc).map(|_| ())
+}
+
+fn check_column<V: std::fmt::Debug>(v: V, c: &Column) -> io::Result<()> {
+    match c.coltype {
+        ColumnType::MYSQL_TYPE_JSON
+        | ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_VARCHAR => Ok(()),
+        _ => Err(bad(v, c)),
+    }
+}
+
+impl ToMysqlValue for serde_json::Value {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_json(w, self)
+    }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        check_column(self, cx.column())?;
+        write_json(w, self)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        check_column(self, c)?;
+        write_json(w, self)
+    }
+}
+
+impl<T: Serialize> ToMysqlValue for Json<T> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_json(w, &self.0)
+    }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        check_column("Json", cx.column())?;
+        write_json(w, &self.0)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        check_column("Json", c)?;
+        write_json(w, &self.0)
+    }
+}
+
+/// Turn a parameter that is not a JSON document into the JSON value it stands for.
+fn scalar(val: ValueInner<'_>) -> Option<serde_json::Value> {
+    match val {
+        ValueInner::NULL => Some(serde_json::Value::Null),
+        ValueInner::Int(i) => Some(i.into()),
+        ValueInner::UInt(u) => Some(u.into()),
+        ValueInner::Double(f) => serde_json::Number::from_f64(f).map(serde_json::Value::Number),
+        _ => None,
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for serde_json::Value {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        Json::try_from(val).map(|Json(v)| v)
+    }
+}
+
+impl<'a, T: Deserialize<'a>> TryFrom<Value<'a>> for Json<T> {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let err = || FromValueError::new(val.0, std::any::type_name::<T>());
+        match val.0 {
+            ValueInner::Bytes(b) => serde_json::from_slice(b),
+            v => T::deserialize(scalar(v).ok_or_else(err)?),
+        }
+        .map(Json)
+        .map_err(|e| err().because(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::io::ReadMysqlExt;
+    use serde_json::json;
+
+    fn bin<T: ToMysqlValue>(v: T, c: &Column) -> io::Result<String> {
+        let mut data = Vec::new();
+        v.to_mysql_bin(&mut data, c)?;
+        Ok(String::from_utf8((&data[..]).read_lenenc_str()?).unwrap())
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point<'a> {
+        label: &'a str,
+        x: i32,
+        y: Option<i32>,
+    }
+
+    #[test]
+    fn writes_documents() {
+        let c = Column::new("", ColumnType::MYSQL_TYPE_JSON);
+        assert_eq!(
+            bin(json!({"a": [1, null]}), &c).unwrap(),
+            r#"{"a":[1,null]}"#
+        );
+        let p = Point {
+            label: "origin",
+            x: 0,
+            y: None,
+        };
+        assert_eq!(
+            bin(Json(&p), &c).unwrap(),
+            r#"{"label":"origin","x":0,"y":null}"#
+        );
+
+        let mut data = Vec::new();
+        Json(vec![1, 2])
+            .to_mysql_text_for(
+                &mut data,
+                &WriteContext::utc(&Column::new("", ColumnType::MYSQL_TYPE_BLOB)),
+            )
+            .unwrap();
+        assert_eq!(&data[..], b"\x05[1,2]");
+    }
+
+    #[test]
+    fn rejects_non_json_columns() {
+        let c = Column::new("", ColumnType::MYSQL_TYPE_LONGLONG);
+        assert!(bin(json!(1), &c).is_err());
+        assert!(Json(1)
+            .to_mysql_text_for(&mut Vec::new(), &WriteContext::utc(&c))
+            .is_err());
+    }
+
+    #[test]
+    fn decodes_documents() {
+        let v = Value::bytes(br#"{"label":"a\"b","x":1}"#);
+        assert_eq!(
+            serde_json::Value::try_from(v).unwrap(),
+            json!({"label": "a\"b", "x": 1})
+        );
+        // `Point` borrows its label from the packet, so it must not contain escapes
+        let v = Value::bytes(br#"{"label":"ab","x":1,"y":2}"#);
+        assert_eq!(
+            Json::<Point<'_>>::try_from(v).unwrap().0,
+            Point {
+                label: "ab",
+                x: 1,
+                y: Some(2)
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_scalars() {
+        assert_eq!(
+            serde_json::Value::try_from(Value::null()).unwrap(),
+            json!(null)
+        );
+        assert_eq!(
+            Json::<Option<u8>>::try_from(Value::new(ValueInner::Int(3))).unwrap(),
+            Json(Some(3))
+        );
+        assert_eq!(
+            serde_json::Value::try_from(Value::new(ValueInner::Double(0.5))).unwrap(),
+            json!(0.5)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_params() {
+        let e = serde_json::Value::try_from(Value::bytes(b"{")).unwrap_err();
+        assert_eq!(e.target(), "serde_json::value::Value");
+        assert!(e
+            .to_string()
+            .starts_with(r#"cannot convert Bytes("{") to "#));
+        assert!(Json::<u8>::try_from(Value::new(ValueInner::Int(-1))).is_err());
+        assert!(Json::<Point<'_>>::try_from(Value::bytes(b"[1]")).is_err());
+        assert!(serde_json::Value::try_from(Value::new(ValueInner::Date(&[]))).is_err());
+    }
+}
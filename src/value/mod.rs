@@ -1,5 +1,17 @@
+#[cfg(any(feature = "rust_decimal", feature = "bigdecimal"))]
+mod decimal;
 mod decode;
 mod encode;
+#[cfg(feature = "serde_json")]
+mod json;
+mod owned;
+#[cfg(feature = "time")]
+mod time;
+#[cfg(feature = "uuid")]
+mod uuid;
 
-pub use self::decode::{Value, ValueInner};
-pub use self::encode::ToMysqlValue;
+pub use self::decode::{Duration, FromValueError, Value, ValueInner};
+pub use self::encode::{ToMysqlValue, WriteContext};
+#[cfg(feature = "serde_json")]
+pub use self::json::Json;
+pub use self::owned::OwnedValue;
@@ -0,0 +1,265 @@
+//! Values that own their data, for keeping parameters around after `on_execute` returns.
+
+use super::decode::{RawDatetime, RawTime};
+use super::encode::write_int_bin;
+use super::{Duration, ToMysqlValue, Value, ValueInner, WriteContext};
+use crate::myc;
+use crate::Column;
+use std::io::{self, Write};
+
+/// An owned version of a [`Value`].
+///
+/// [`Value`]s borrow from the packet they arrived in, so use [`Value::to_owned`] to queue one up
+/// for another thread, or to cache it. Dates and times are decoded from their binary encoding,
+/// but dates are kept as plain fields rather than as chrono types so that MySQL's zero dates
+/// survive.
+///
+/// Owned values can be written back to clients, and converted to and from `mysql_common` values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedValue {
+    /// The MySQL `NULL` value.
+    NULL,
+    /// An untyped sequence of bytes (usually a text type or `MYSQL_TYPE_BLOB`).
+    Bytes(Vec<u8>),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number.
+    Double(f64),
+    /// A `DATE`, `DATETIME` or `TIMESTAMP`, as its year, month, day, hour, minutes, seconds and
+    /// microseconds.
+    Date(u16, u8, u8, u8, u8, u8, u32),
+    /// A `TIME`.
+    Time(Duration),
+}
+
+impl OwnedValue {
+    /// Returns true if this is a NULL value
+    pub fn is_null(&self) -> bool {
+        matches!(*self, OwnedValue::NULL)
+    }
+}
+
+/// Dates and times are parsed when the parameters are read, so this conversion cannot fail for
+/// values that came from a client.
+impl<'a> From<Value<'a>> for OwnedValue {
+    fn from(val: Value<'a>) -> Self {
+        match val.0 {
+            ValueInner::NULL => OwnedValue::NULL,
+            ValueInner::Bytes(b) => OwnedValue::Bytes(b.to_vec()),
+            ValueInner::Int(i) => OwnedValue::Int(i),
+            ValueInner::UInt(u) => OwnedValue::UInt(u),
+            ValueInner::Double(f) => OwnedValue::Double(f),
+            ValueInner::Date(v) | ValueInner::Datetime(v) => {
+                let dt = RawDatetime::parse(v).unwrap_or_default();
+                OwnedValue::Date(
+                    dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.micros,
+                )
+            }
+            ValueInner::Time(v) => {
+                let t = RawTime::parse(v).unwrap_or_default();
+                OwnedValue::Time(Duration::new(t.negative, t.abs_seconds(), t.micros))
+            }
+        }
+    }
+}
+
+impl From<myc::value::Value> for OwnedValue {
+    fn from(val: myc::value::Value) -> Self {
+        match val {
+            myc::value::Value::NULL => OwnedValue::NULL,
+            myc::value::Value::Bytes(b) => OwnedValue::Bytes(b),
+            myc::value::Value::Int(i) => OwnedValue::Int(i),
+            myc::value::Value::UInt(u) => OwnedValue::UInt(u),
+            myc::value::Value::Float(f) => OwnedValue::Double(f64::from(f)),
+            myc::value::Value::Double(f) => OwnedValue::Double(f),
+            myc::value::Value::Date(y, mo, d, h, mi, s, us) => {
+                OwnedValue::Date(y, mo, d, h, mi, s, us)
+            }
+            myc::value::Value::Time(neg, d, h, m, s, us) => {
+                let secs =
+                    u64::from(d) * 86_400 + u64::from(h) * 3_600 + u64::from(m) * 60 + u64::from(s);
+                OwnedValue::Time(Duration::new(neg, secs, us))
+            }
+        }
+    }
+}
+
+/// `TIME`s longer than `mysql_common` can represent, over eleven million years, saturate.
+impl From<OwnedValue> for myc::value::Value {
+    fn from(val: OwnedValue) -> Self {
+        match val {
+            OwnedValue::NULL => myc::value::Value::NULL,
+            OwnedValue::Bytes(b) => myc::value::Value::Bytes(b),
+            OwnedValue::Int(i) => myc::value::Value::Int(i),
+            OwnedValue::UInt(u) => myc::value::Value::UInt(u),
+            OwnedValue::Double(f) => myc::value::Value::Double(f),
+            OwnedValue::Date(y, mo, d, h, mi, s, us) => {
+                myc::value::Value::Date(y, mo, d, h, mi, s, us)
+            }
+            OwnedValue::Time(t) => myc::value::Value::Time(
+                t.is_negative(),
+                t.days().min(u64::from(u32::MAX)) as u32,
+                t.hours(),
+                t.minutes(),
+                t.seconds(),
+                t.microseconds(),
+            ),
+        }
+    }
+}
+
+fn datetime(y: u16, mo: u8, d: u8, h: u8, mi: u8, s: u8, us: u32) -> RawDatetime {
+    RawDatetime {
+        year: y,
+        month: mo,
+        day: d,
+        hour: h,
+        minute: mi,
+        second: s,
+        micros: us,
+    }
+}
+
+impl ToMysqlValue for OwnedValue {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            OwnedValue::NULL => None::<u8>.to_mysql_text(w),
+            OwnedValue::Bytes(ref bytes) => bytes.to_mysql_text(w),
+            OwnedValue::Int(n) => n.to_mysql_text(w),
+            OwnedValue::UInt(n) => n.to_mysql_text(w),
+            OwnedValue::Double(f) => f.to_mysql_text(w),
+            OwnedValue::Date(y, mo, d, h, mi, s, us) => {
+                datetime(y, mo, d, h, mi, s, us).to_mysql_text(w)
+            }
+            OwnedValue::Time(t) => t.to_mysql_text(w),
+        }
+    }
+
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        match *self {
+            OwnedValue::Date(y, mo, d, h, mi, s, us) => {
+                datetime(y, mo, d, h, mi, s, us).to_mysql_text_for(w, cx)
+            }
+            _ => self.to_mysql_text(w),
+        }
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match *self {
+            OwnedValue::NULL => unreachable!(),
+            OwnedValue::Bytes(ref bytes) => bytes.to_mysql_bin(w, c),
+            OwnedValue::Int(n) => write_int_bin(n, w, c),
+            OwnedValue::UInt(n) => n.to_mysql_bin(w, c),
+            OwnedValue::Double(f) => f.to_mysql_bin(w, c),
+            OwnedValue::Date(y, mo, d, h, mi, s, us) => {
+                datetime(y, mo, d, h, mi, s, us).to_mysql_bin(w, c)
+            }
+            OwnedValue::Time(t) => t.to_mysql_bin(w, c),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        self.is_null()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+
+    /// Encode `v` in the binary protocol, and read it back as an owned parameter.
+    fn rt(v: &OwnedValue, ct: ColumnType) -> OwnedValue {
+        let mut data = Vec::new();
+        v.to_mysql_bin(&mut data, &Column::new("", ct)).unwrap();
+        Value::parse_from(&mut &data[..], ct, false)
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn outlives_the_packet() {
+        let owned = {
+            let data = vec![3, b'a', b'b', b'c'];
+            Value::parse_from(&mut &data[..], ColumnType::MYSQL_TYPE_STRING, false)
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(owned, OwnedValue::Bytes(b"abc".to_vec()));
+        assert!(Value::null().to_owned().is_null());
+    }
+
+    #[test]
+    fn decodes_dates_and_times() {
+        let dt = OwnedValue::Date(2024, 2, 29, 13, 14, 15, 16);
+        assert_eq!(rt(&dt, ColumnType::MYSQL_TYPE_DATETIME), dt);
+        let d = OwnedValue::Date(2024, 2, 29, 0, 0, 0, 0);
+        assert_eq!(rt(&d, ColumnType::MYSQL_TYPE_DATE), d);
+        let zero = OwnedValue::Date(0, 0, 0, 0, 0, 0, 0);
+        assert_eq!(rt(&zero, ColumnType::MYSQL_TYPE_DATE), zero);
+        assert_eq!(rt(&zero, ColumnType::MYSQL_TYPE_TIMESTAMP), zero);
+
+        let t = OwnedValue::Time(Duration::new(true, 90_061, 5));
+        assert_eq!(rt(&t, ColumnType::MYSQL_TYPE_TIME), t);
+    }
+
+    #[test]
+    fn writes_text() {
+        let text = |v: OwnedValue, ct| {
+            let mut data = Vec::new();
+            v.to_mysql_text_for(&mut data, &WriteContext::utc(&Column::new("", ct)))
+                .unwrap();
+            String::from_utf8(data[1..].to_vec()).unwrap()
+        };
+        let d = OwnedValue::Date(0, 0, 0, 0, 0, 0, 0);
+        assert_eq!(text(d.clone(), ColumnType::MYSQL_TYPE_DATE), "0000-00-00");
+        assert_eq!(
+            text(d, ColumnType::MYSQL_TYPE_DATETIME),
+            "0000-00-00 00:00:00"
+        );
+        let t = OwnedValue::Time(Duration::new(true, 90_061, 0));
+        assert_eq!(text(t, ColumnType::MYSQL_TYPE_TIME), "-25:01:01");
+        assert_eq!(text(OwnedValue::Int(-3), ColumnType::MYSQL_TYPE_LONG), "-3");
+    }
+
+    #[test]
+    fn writes_ints_to_narrower_columns() {
+        let mut data = Vec::new();
+        OwnedValue::Int(7)
+            .to_mysql_bin(&mut data, &Column::new("", ColumnType::MYSQL_TYPE_TINY))
+            .unwrap();
+        assert_eq!(data, [7]);
+        assert!(OwnedValue::Int(300)
+            .to_mysql_bin(
+                &mut Vec::new(),
+                &Column::new("", ColumnType::MYSQL_TYPE_TINY)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn converts_to_and_from_mysql_common() {
+        let values = vec![
+            myc::value::Value::NULL,
+            myc::value::Value::Bytes(b"abc".to_vec()),
+            myc::value::Value::Int(-1),
+            myc::value::Value::UInt(u64::MAX),
+            myc::value::Value::Double(0.5),
+            myc::value::Value::Date(2024, 2, 29, 13, 14, 15, 16),
+            myc::value::Value::Time(true, 1, 2, 3, 4, 5),
+        ];
+        for v in values {
+            assert_eq!(myc::value::Value::from(OwnedValue::from(v.clone())), v);
+        }
+        assert_eq!(
+            OwnedValue::from(myc::value::Value::Float(0.5)),
+            OwnedValue::Double(0.5)
+        );
+        assert_eq!(
+            OwnedValue::from(myc::value::Value::Time(false, 0, 25, 0, 0, 0)),
+            OwnedValue::Time(Duration::new(false, 90_000, 0))
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Dates and times from the `time` crate.
+//!
+//! These are encoded just like their `chrono` counterparts. Like chrono's zoned `DateTime`s,
+//! `OffsetDateTime`s are written and read in the client's session time zone.
+
+use super::decode::{RawDatetime, RawTime};
+use super::encode::bad;
+use super::{FromValueError, ToMysqlValue, Value, WriteContext};
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::Column;
+use ::time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::FixedOffset;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// The largest magnitude of a `TIME` value, 838:59:59.999999.
+const MAX_TIME_MICROS: i128 = (838 * 3_600 + 59 * 60 + 59) * 1_000_000 + 999_999;
+
+fn out_of_range<V: std::fmt::Debug>(v: V) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{:?} is out of range for MySQL", v),
+    )
+}
+
+fn year(d: Date) -> io::Result<u16> {
+    u16::try_from(d.year())
+        .ok()
+        .filter(|&y| y <= 9999)
+        .ok_or_else(|| out_of_range(d))
+}
+
+impl ToMysqlValue for Date {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let s = format!(
+            "{:04}-{:02}-{:02}",
+            year(*self)?,
+            u8::from(self.month()),
+            self.day()
+        );
+        w.write_lenenc_str(s.as_bytes()).map(|_| ())
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_DATE => {
+                w.write_u8(4u8)?;
+                w.write_u16::<LittleEndian>(year(*self)?)?;
+                w.write_u8(u8::from(self.month()))?;
+                w.write_u8(self.day())
+            }
+            _ => Err(bad(self, c)),
+        }
+    }
+}
+
+impl ToMysqlValue for PrimitiveDateTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut s = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year(self.date())?,
+            u8::from(self.month()),
+            self.day(),
+            self.hour(),
+            self.minute(),
+            self.second(),
+        );
+        if self.microsecond() != 0 {
+            s.push_str(&format!(".{:06}", self.microsecond()));
+        }
+        w.write_lenenc_str(s.as_bytes()).map(|_| ())
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_DATETIME | ColumnType::MYSQL_TYPE_TIMESTAMP => {
+                let us = self.microsecond();
+                w.write_u8(if us != 0 { 11 } else { 7 })?;
+                w.write_u16::<LittleEndian>(year(self.date())?)?;
+                w.write_u8(u8::from(self.month()))?;
+                w.write_u8(self.day())?;
+                w.write_u8(self.hour())?;
+                w.write_u8(self.minute())?;
+                w.write_u8(self.second())?;
+                if us != 0 {
+                    w.write_u32::<LittleEndian>(us)?;
+                }
+                Ok(())
+            }
+            _ => Err(bad(self, c)),
+        }
+    }
+}
+
+fn offset(tz: FixedOffset) -> UtcOffset {
+    UtcOffset::from_whole_seconds(tz.local_minus_utc()).unwrap()
+}
+
+/// The offset that `OffsetDateTime`s are written in for `cx`: the client's session time zone for
+/// `TIMESTAMP` columns, like MySQL does, and UTC otherwise.
+fn session_offset(cx: &WriteContext<'_>) -> UtcOffset {
+    if cx.column().coltype == ColumnType::MYSQL_TYPE_TIMESTAMP {
+        offset(cx.time_zone())
+    } else {
+        UtcOffset::UTC
+    }
+}
+
+fn local(v: &OffsetDateTime, tz: UtcOffset) -> PrimitiveDateTime {
+    let v = v.to_offset(tz);
+    PrimitiveDateTime::new(v.date(), v.time())
+}
+
+impl ToMysqlValue for OffsetDateTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        local(self, UtcOffset::UTC).to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        local(self, UtcOffset::UTC).to_mysql_bin(w, c)
+    }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        local(self, session_offset(cx)).to_mysql_text(w)
+    }
+    fn to_mysql_bin_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        local(self, session_offset(cx)).to_mysql_bin(w, cx.column())
+    }
+}
+
+impl ToMysqlValue for Duration {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if self.whole_microseconds().abs() > MAX_TIME_MICROS {
+            return Err(out_of_range(self));
+        }
+        let s = self.whole_seconds().unsigned_abs();
+        let us = self.subsec_microseconds().unsigned_abs();
+        let mut t = format!(
+            "{}{:02}:{:02}:{:02}",
+            if self.is_negative() { "-" } else { "" },
+            s / 3_600,
+            (s % 3_600) / 60,
+            s % 60
+        );
+        if us != 0 {
+            t.push_str(&format!(".{:06}", us));
+        }
+        w.write_lenenc_str(t.as_bytes()).map(|_| ())
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_TIME => {
+                if self.whole_microseconds().abs() > MAX_TIME_MICROS {
+                    return Err(out_of_range(self));
+                }
+                let s = self.whole_seconds().unsigned_abs();
+                let us = self.subsec_microseconds().unsigned_abs();
+                if s == 0 && us == 0 {
+                    return w.write_u8(0);
+                }
+                w.write_u8(if us != 0 { 12 } else { 8 })?;
+                w.write_u8(u8::from(self.is_negative()))?;
+                w.write_u32::<LittleEndian>((s / 86_400) as u32)?;
+                w.write_u8(((s % 86_400) / 3_600) as u8)?;
+                w.write_u8(((s % 3_600) / 60) as u8)?;
+                w.write_u8((s % 60) as u8)?;
+                if us != 0 {
+                    w.write_u32::<LittleEndian>(us)?;
+                }
+                Ok(())
+            }
+            _ => Err(bad(self, c)),
+        }
+    }
+}
+
+fn date(dt: &RawDatetime) -> Result<Date, ::time::error::ComponentRange> {
+    let month = Month::try_from(dt.month)?;
+    Date::from_calendar_date(i32::from(dt.year), month, dt.day)
+}
+
+impl<'a> TryFrom<Value<'a>> for Date {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let dt = RawDatetime::from_value(val, "Date")?;
+        if !dt.is_date() {
+            return Err(FromValueError::new(val.0, "Date").because("value has a time of day"));
+        }
+        date(&dt).map_err(|e| FromValueError::new(val.0, "Date").because(e))
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for PrimitiveDateTime {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let dt = RawDatetime::from_value(val, "PrimitiveDateTime")?;
+        date(&dt)
+            .and_then(|d| {
+                let t = Time::from_hms_micro(dt.hour, dt.minute, dt.second, dt.micros)?;
+                Ok(PrimitiveDateTime::new(d, t))
+            })
+            .map_err(|e| FromValueError::new(val.0, "PrimitiveDateTime").because(e))
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for OffsetDateTime {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        PrimitiveDateTime::try_from(val)
+            .map(|dt| dt.assume_offset(offset(val.time_zone())))
+            .map_err(|e| e.for_target("OffsetDateTime"))
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Duration {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let t = RawTime::from_value(val, "Duration")?;
+        let d = Duration::new(t.abs_seconds() as i64, t.micros as i32 * 1_000);
+        Ok(if t.negative { -d } else { d })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::io::ReadMysqlExt;
+    use crate::ValueInner;
+    use ::time::macros::{date, datetime};
+
+    fn text<T: ToMysqlValue>(v: T) -> String {
+        let mut data = Vec::new();
+        v.to_mysql_text(&mut data).unwrap();
+        String::from_utf8((&data[..]).read_lenenc_str().unwrap()).unwrap()
+    }
+
+    fn bin<T: ToMysqlValue>(v: T, ct: ColumnType) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        v.to_mysql_bin(&mut data, &Column::new("", ct))?;
+        Ok(data)
+    }
+
+    /// Encode `v` in the binary protocol, and decode it as a parameter of the same type.
+    fn rt<T>(v: T, ct: ColumnType) -> T
+    where
+        T: ToMysqlValue + for<'a> TryFrom<Value<'a>, Error = FromValueError>,
+    {
+        let data = bin(v, ct).unwrap();
+        T::try_from(Value::parse_from(&mut &data[..], ct, false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn dates() {
+        let d = date!(2024 - 02 - 29);
+        assert_eq!(text(d), "2024-02-29");
+        assert_eq!(
+            bin(d, ColumnType::MYSQL_TYPE_DATE).unwrap(),
+            [4, 0xe8, 0x07, 2, 29]
+        );
+        assert_eq!(rt(d, ColumnType::MYSQL_TYPE_DATE), d);
+        assert!(bin(d, ColumnType::MYSQL_TYPE_LONG).is_err());
+        assert!(bin(date!(-1 - 01 - 01), ColumnType::MYSQL_TYPE_DATE).is_err());
+    }
+
+    #[test]
+    fn datetimes() {
+        let dt = datetime!(2024-02-29 13:14:15);
+        assert_eq!(text(dt), "2024-02-29 13:14:15");
+        assert_eq!(bin(dt, ColumnType::MYSQL_TYPE_DATETIME).unwrap()[0], 7);
+        assert_eq!(rt(dt, ColumnType::MYSQL_TYPE_DATETIME), dt);
+
+        let dt = datetime!(2024-02-29 13:14:15.000123);
+        assert_eq!(text(dt), "2024-02-29 13:14:15.000123");
+        assert_eq!(bin(dt, ColumnType::MYSQL_TYPE_TIMESTAMP).unwrap()[0], 11);
+        assert_eq!(rt(dt, ColumnType::MYSQL_TYPE_TIMESTAMP), dt);
+    }
+
+    #[test]
+    fn offset_datetimes_are_in_the_session_time_zone() {
+        let dt = datetime!(2024-02-29 23:30 -1);
+        assert_eq!(text(dt), "2024-03-01 00:30:00");
+        assert_eq!(
+            rt(dt, ColumnType::MYSQL_TYPE_DATETIME),
+            datetime!(2024-03-01 00:30 UTC)
+        );
+
+        let c = Column::new("", ColumnType::MYSQL_TYPE_TIMESTAMP);
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let mut data = Vec::new();
+        dt.to_mysql_bin_for(&mut data, &WriteContext::new(&c, tz))
+            .unwrap();
+        let v = Value::parse_from(&mut &data[..], c.coltype, false).unwrap();
+        assert_eq!(
+            PrimitiveDateTime::try_from(v).unwrap(),
+            datetime!(2024-03-01 02:30)
+        );
+        let v = OffsetDateTime::try_from(v.in_time_zone(tz)).unwrap();
+        assert_eq!(v, dt);
+        assert_eq!(v.offset(), offset(tz));
+    }
+
+    #[test]
+    fn durations() {
+        let d = Duration::new(3 * 86_400 + 3_723, 456_789_000);
+        assert_eq!(text(d), "73:02:03.456789");
+        assert_eq!(text(-d), "-73:02:03.456789");
+        assert_eq!(text(Duration::ZERO), "00:00:00");
+        assert_eq!(
+            bin(-d, ColumnType::MYSQL_TYPE_TIME).unwrap(),
+            [12, 1, 3, 0, 0, 0, 1, 2, 3, 0x55, 0xf8, 0x06, 0]
+        );
+        assert_eq!(
+            bin(Duration::ZERO, ColumnType::MYSQL_TYPE_TIME).unwrap(),
+            [0]
+        );
+        assert_eq!(rt(d, ColumnType::MYSQL_TYPE_TIME), d);
+        assert_eq!(rt(-d, ColumnType::MYSQL_TYPE_TIME), -d);
+        assert_eq!(
+            rt(Duration::seconds(-1), ColumnType::MYSQL_TYPE_TIME),
+            Duration::seconds(-1)
+        );
+        assert!(bin(Duration::hours(839), ColumnType::MYSQL_TYPE_TIME).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_params() {
+        let v = Value::new(ValueInner::Datetime(&[0xe8, 0x07, 2, 30]));
+        let e = Date::try_from(v).unwrap_err();
+        assert_eq!(e.target(), "Date");
+        assert!(Date::try_from(Value::new(ValueInner::Datetime(&[
+            0xe8, 0x07, 2, 3, 1, 0, 0
+        ])))
+        .is_err());
+        assert!(PrimitiveDateTime::try_from(Value::new(ValueInner::Datetime(&[1, 2, 3]))).is_err());
+        assert!(PrimitiveDateTime::try_from(Value::new(ValueInner::Datetime(&[]))).is_err());
+        assert!(Duration::try_from(Value::new(ValueInner::Time(&[1, 2, 3]))).is_err());
+        assert!(Duration::try_from(Value::bytes(b"12:00:00")).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! UUIDs, stored either as raw bytes or in their textual form.
+//!
+//! MySQL has no UUID type of its own, so UUIDs usually live in `BINARY(16)` columns, or as
+//! 36-character strings in `CHAR(36)` columns. MariaDB does have a native `UUID` type, which it
+//! announces as a string column with the `uuid` extended type name.
+
+use super::encode::bad;
+use super::{FromValueError, ToMysqlValue, Value, ValueInner, WriteContext};
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::myc::io::WriteMysqlExt;
+use crate::Column;
+use ::uuid::Uuid;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::str;
+
+/// Whether a UUID should be written to `c` as its 16 raw bytes, rather than as text.
+fn is_binary(v: &Uuid, c: &Column) -> io::Result<bool> {
+    let native =
+        matches!(c.extended_type_name.as_deref(), Some(name) if name.eq_ignore_ascii_case("uuid"));
+    match c.coltype {
+        _ if native => Ok(false),
+        ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB => Ok(c.colflags.contains(ColumnFlags::BINARY_FLAG)),
+        _ => Err(bad(v, c)),
+    }
+}
+
+fn write<W: Write>(v: &Uuid, w: &mut W, c: &Column) -> io::Result<()> {
+    if is_binary(v, c)? {
+        w.write_lenenc_str(v.as_bytes()).map(|_| ())
+    } else {
+        v.to_mysql_text(w)
+    }
+}
+
+impl ToMysqlValue for Uuid {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut buf = Uuid::encode_buffer();
+        w.write_lenenc_str(self.hyphenated().encode_lower(&mut buf).as_bytes())
+            .map(|_| ())
+    }
+    fn to_mysql_text_for<W: Write>(&self, w: &mut W, cx: &WriteContext<'_>) -> io::Result<()> {
+        write(self, w, cx.column())
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        write(self, w, c)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Uuid {
+    type Error = FromValueError;
+    fn try_from(val: Value<'a>) -> Result<Self, Self::Error> {
+        let err = || FromValueError::new(val.0, "Uuid");
+        match val.0 {
+            ValueInner::Bytes(b) if b.len() == 16 => Ok(Uuid::from_slice(b).unwrap()),
+            ValueInner::Bytes(b) => {
+                let s = str::from_utf8(b).map_err(|e| err().because(e))?;
+                Uuid::parse_str(s).map_err(|e| err().because(e))
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::io::ReadMysqlExt;
+
+    const ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    fn bin(c: &Column) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        Uuid::parse_str(ID).unwrap().to_mysql_bin(&mut data, c)?;
+        (&data[..]).read_lenenc_str()
+    }
+
+    #[test]
+    fn writes_binary_columns_as_bytes() {
+        let id = Uuid::parse_str(ID).unwrap();
+        let c = Column {
+            colflags: ColumnFlags::BINARY_FLAG,
+            ..Column::new("", ColumnType::MYSQL_TYPE_STRING)
+        };
+        assert_eq!(bin(&c).unwrap(), id.as_bytes());
+
+        let mut data = Vec::new();
+        id.to_mysql_text_for(&mut data, &WriteContext::utc(&c))
+            .unwrap();
+        assert_eq!(data[0], 16);
+        assert_eq!(&data[1..], id.as_bytes());
+    }
+
+    #[test]
+    fn writes_text_columns_as_strings() {
+        let c = Column::new("", ColumnType::MYSQL_TYPE_VAR_STRING);
+        assert_eq!(bin(&c).unwrap(), ID.as_bytes());
+
+        let mut data = Vec::new();
+        Uuid::parse_str(ID)
+            .unwrap()
+            .to_mysql_text(&mut data)
+            .unwrap();
+        assert_eq!(&data[1..], ID.as_bytes());
+    }
+
+    #[test]
+    fn writes_mariadb_uuids_as_strings() {
+        let mut c = Column {
+            colflags: ColumnFlags::BINARY_FLAG,
+            ..Column::new("", ColumnType::MYSQL_TYPE_STRING)
+        };
+        c.extended_type_name = Some("uuid".to_owned());
+        assert_eq!(bin(&c).unwrap(), ID.as_bytes());
+    }
+
+    #[test]
+    fn rejects_other_columns() {
+        let c = Column::new("", ColumnType::MYSQL_TYPE_LONGLONG);
+        assert!(bin(&c).is_err());
+    }
+
+    #[test]
+    fn decodes_either_form() {
+        let id = Uuid::parse_str(ID).unwrap();
+        assert_eq!(Uuid::try_from(Value::bytes(id.as_bytes())).unwrap(), id);
+        assert_eq!(Uuid::try_from(Value::bytes(ID.as_bytes())).unwrap(), id);
+        let upper = ID.to_uppercase();
+        assert_eq!(Uuid::try_from(Value::bytes(upper.as_bytes())).unwrap(), id);
+        let simple = ID.replace('-', "");
+        assert_eq!(Uuid::try_from(Value::bytes(simple.as_bytes())).unwrap(), id);
+    }
+
+    #[test]
+    fn rejects_bad_params() {
+        let e = Uuid::try_from(Value::bytes(b"not-a-uuid")).unwrap_err();
+        assert_eq!(e.target(), "Uuid");
+        assert!(Uuid::try_from(Value::bytes(&[0; 15])).is_err());
+        assert!(Uuid::try_from(Value::new(ValueInner::UInt(1))).is_err());
+    }
+}
@@ -0,0 +1,928 @@
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::resultset::QueryResultWriter;
+use crate::sql::{Parser, Token};
+use crate::value::ToMysqlValue;
+use crate::Column;
+use chrono::FixedOffset;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, RwLock};
+
+/// The value of a MySQL system variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableValue {
+    /// The variable is set, but has no value.
+    Null,
+    /// A boolean flag, reported as `1`/`0` when selected and `ON`/`OFF` by `SHOW VARIABLES`.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A string.
+    Str(String),
+}
+
+impl VariableValue {
+    fn column_type(&self) -> (ColumnType, ColumnFlags) {
+        match *self {
+            VariableValue::Null => (ColumnType::MYSQL_TYPE_NULL, ColumnFlags::empty()),
+            VariableValue::Bool(_) | VariableValue::Int(_) => {
+                (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::empty())
+            }
+            VariableValue::UInt(_) => (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::UNSIGNED_FLAG),
+            VariableValue::Str(_) => (ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty()),
+        }
+    }
+
+    /// Coerce a value given in a `SET` statement to the type of the variable's current value.
+    fn coerce(self, like: Option<&VariableValue>) -> Option<Self> {
+        let word = match self {
+            VariableValue::Str(ref s) => Some(s.to_ascii_uppercase()),
+            _ => None,
+        };
+        match (like, self) {
+            (Some(VariableValue::Bool(_)), VariableValue::Int(i)) if i == 0 || i == 1 => {
+                Some(VariableValue::Bool(i == 1))
+            }
+            (Some(VariableValue::Bool(_)), VariableValue::UInt(i)) if i == 0 || i == 1 => {
+                Some(VariableValue::Bool(i == 1))
+            }
+            (Some(VariableValue::Bool(_)), VariableValue::Str(_)) => match word.as_deref() {
+                Some("ON") | Some("TRUE") => Some(VariableValue::Bool(true)),
+                Some("OFF") | Some("FALSE") => Some(VariableValue::Bool(false)),
+                _ => None,
+            },
+            (Some(VariableValue::Bool(_)), _) => None,
+            (Some(VariableValue::UInt(_)), VariableValue::Int(i)) if i >= 0 => {
+                Some(VariableValue::UInt(i as u64))
+            }
+            (Some(VariableValue::UInt(_)), VariableValue::Str(s))
+            | (Some(VariableValue::Int(_)), VariableValue::Str(s)) => s
+                .parse()
+                .map(VariableValue::Int)
+                .ok()
+                .or_else(|| s.parse().map(VariableValue::UInt).ok()),
+            (_, v) => Some(v),
+        }
+    }
+
+    fn show(&self) -> String {
+        match *self {
+            VariableValue::Null => String::new(),
+            VariableValue::Bool(true) => "ON".to_owned(),
+            VariableValue::Bool(false) => "OFF".to_owned(),
+            VariableValue::Int(i) => i.to_string(),
+            VariableValue::UInt(i) => i.to_string(),
+            VariableValue::Str(ref s) => s.clone(),
+        }
+    }
+}
+
+impl From<bool> for VariableValue {
+    fn from(v: bool) -> Self {
+        VariableValue::Bool(v)
+    }
+}
+
+impl From<i64> for VariableValue {
+    fn from(v: i64) -> Self {
+        VariableValue::Int(v)
+    }
+}
+
+impl From<u64> for VariableValue {
+    fn from(v: u64) -> Self {
+        VariableValue::UInt(v)
+    }
+}
+
+impl<'a> From<&'a str> for VariableValue {
+    fn from(v: &'a str) -> Self {
+        VariableValue::Str(v.to_owned())
+    }
+}
+
+impl From<String> for VariableValue {
+    fn from(v: String) -> Self {
+        VariableValue::Str(v)
+    }
+}
+
+impl ToMysqlValue for VariableValue {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            VariableValue::Null => None::<u8>.to_mysql_text(w),
+            VariableValue::Bool(b) => i64::from(b).to_mysql_text(w),
+            VariableValue::Int(i) => i.to_mysql_text(w),
+            VariableValue::UInt(i) => i.to_mysql_text(w),
+            VariableValue::Str(ref s) => s.to_mysql_text(w),
+        }
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match *self {
+            VariableValue::Null => unreachable!(),
+            VariableValue::Bool(b) => i64::from(b).to_mysql_bin(w, c),
+            VariableValue::Int(i) => i.to_mysql_bin(w, c),
+            VariableValue::UInt(i) => i.to_mysql_bin(w, c),
+            VariableValue::Str(ref s) => s.to_mysql_bin(w, c),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        matches!(*self, VariableValue::Null)
+    }
+}
+
+/// The scope a system variable is read from or assigned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableScope {
+    /// Server-wide values, shared by every connection created from the same
+    /// [`SystemVariables`](struct.SystemVariables.html).
+    Global,
+    /// Values that only apply to the current connection.
+    Session,
+}
+
+type VariableMap = BTreeMap<String, VariableValue>;
+
+/// Variables whose session value is read-only, as in MySQL.
+const READ_ONLY_SESSION: &[&str] = &["max_allowed_packet"];
+
+/// A store of MySQL system variables, as read by `SELECT @@var` and `SHOW VARIABLES`, and
+/// modified by `SET`.
+///
+/// The store has a global and a session scope. Reading a variable in the session scope falls back
+/// to its global value if it has not been assigned in the session. Variable names are
+/// case-insensitive.
+///
+/// Cloning a `SystemVariables` yields a handle to the *same* variables, so a shim can keep a
+/// clone around to inspect what a client has `SET`. To give each connection its own session
+/// scope while sharing global values, hand out
+/// [`new_session`](struct.SystemVariables.html#method.new_session) from
+/// [`MysqlShim::system_variables`](trait.MysqlShim.html#method.system_variables).
+///
+/// Like MySQL, a connection starts out with a copy of the global values in its session scope, so
+/// changing a global value later only affects connections made after the change.
+#[derive(Debug, Clone)]
+pub struct SystemVariables {
+    global: Arc<RwLock<VariableMap>>,
+    session: Arc<RwLock<VariableMap>>,
+}
+
+impl Default for SystemVariables {
+    fn default() -> Self {
+        SystemVariables::new()
+    }
+}
+
+impl SystemVariables {
+    /// Create a new store populated with MySQL's default values.
+    pub fn new() -> Self {
+        let global = defaults()
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect();
+        SystemVariables {
+            global: Arc::new(RwLock::new(global)),
+            session: Default::default(),
+        }
+    }
+
+    /// Create a store with a fresh session scope that shares global variables with this one.
+    pub fn new_session(&self) -> Self {
+        SystemVariables {
+            global: Arc::clone(&self.global),
+            session: Default::default(),
+        }
+    }
+
+    fn scope(&self, scope: VariableScope) -> &RwLock<VariableMap> {
+        match scope {
+            VariableScope::Global => &self.global,
+            VariableScope::Session => &self.session,
+        }
+    }
+
+    /// Look up the value of the variable `name` as seen by the current session.
+    pub fn get(&self, name: &str) -> Option<VariableValue> {
+        self.get_scoped(VariableScope::Session, name)
+    }
+
+    /// Look up the value of the variable `name` in the given scope.
+    pub fn get_scoped(&self, scope: VariableScope, name: &str) -> Option<VariableValue> {
+        let name = name.to_ascii_lowercase();
+        if scope == VariableScope::Session {
+            if let Some(v) = self.session.read().unwrap().get(&name) {
+                return Some(v.clone());
+            }
+        }
+        self.global.read().unwrap().get(&name).cloned()
+    }
+
+    /// Assign `value` to the variable `name` in the given scope.
+    pub fn set<V: Into<VariableValue>>(&self, scope: VariableScope, name: &str, value: V) {
+        self.scope(scope)
+            .write()
+            .unwrap()
+            .insert(name.to_ascii_lowercase(), value.into());
+    }
+
+    /// Remove the session value for `name`, so that it reads as its global value again.
+    pub fn reset(&self, name: &str) {
+        self.session
+            .write()
+            .unwrap()
+            .remove(&name.to_ascii_lowercase());
+    }
+
+    /// All variables visible in the given scope, sorted by name.
+    pub fn all(&self, scope: VariableScope) -> Vec<(String, VariableValue)> {
+        let mut vars = self.global.read().unwrap().clone();
+        if scope == VariableScope::Session {
+            vars.extend(
+                self.session
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+        vars.into_iter().collect()
+    }
+
+    /// Copy the global values into the session scope, where the session has not assigned them
+    /// already.
+    pub(crate) fn start_session(&self) {
+        let global = self.global.read().unwrap();
+        let mut session = self.session.write().unwrap();
+        for (k, v) in global.iter() {
+            session.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+    }
+
+    /// Apply a parsed `SET` statement.
+    ///
+    /// Assignments of values that cannot be converted to the variable's current type are ignored,
+    /// as are assignments to read-only session variables, and assignments to the global scope
+    /// unless `global` is set.
+    pub(crate) fn apply(&self, assignments: Vec<Assignment>, global: bool) {
+        for a in assignments {
+            let allowed = match a.scope {
+                VariableScope::Global => global,
+                VariableScope::Session => !READ_ONLY_SESSION
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&a.name)),
+            };
+            if !allowed {
+                continue;
+            }
+            match a.value {
+                None => self.set_default(a.scope, &a.name),
+                Some(v) => {
+                    if let Some(v) = v.coerce(self.get_scoped(a.scope, &a.name).as_ref()) {
+                        self.set(a.scope, &a.name, v);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Assign `DEFAULT` to `name`: MySQL's default value in the global scope, and the current
+    /// global value in the session scope.
+    fn set_default(&self, scope: VariableScope, name: &str) {
+        let default = match scope {
+            VariableScope::Global => defaults()
+                .into_iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v),
+            VariableScope::Session => self.get_scoped(VariableScope::Global, name),
+        };
+        match default {
+            Some(v) => self.set(scope, name, v),
+            None if scope == VariableScope::Session => self.reset(name),
+            // a variable that MySQL does not have keeps whatever global value the shim gave it
+            None => {}
+        }
+    }
+
+    /// Answer a parsed `SELECT @@...`, provided all the selected variables are known.
+    ///
+    /// If they are not, the writer is handed back so that the query can be passed to the shim.
+    pub(crate) fn answer_select<'a, W: Read + Write>(
+        &self,
+        items: &[Selected],
+        limit: Option<u64>,
+        results: QueryResultWriter<'a, W>,
+    ) -> io::Result<Option<QueryResultWriter<'a, W>>> {
+        let mut values = Vec::with_capacity(items.len());
+        for item in items {
+            match self.get_scoped(item.scope.unwrap_or(VariableScope::Session), &item.name) {
+                Some(v) => values.push(v),
+                None => return Ok(Some(results)),
+            }
+        }
+
+        let cols: Vec<_> = items
+            .iter()
+            .zip(&values)
+            .map(|(item, v)| {
+                let (coltype, colflags) = v.column_type();
+                Column {
+                    colflags,
+                    ..Column::new(item.column.clone(), coltype)
+                }
+            })
+            .collect();
+        let mut w = results.start(&cols)?;
+        if limit != Some(0) {
+            w.write_row(values)?;
+        }
+        w.finish()?;
+        Ok(None)
+    }
+
+    /// Answer a parsed `SHOW VARIABLES`.
+    pub(crate) fn answer_show<W: Read + Write>(
+        &self,
+        scope: VariableScope,
+        like: Option<&str>,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        let cols = [
+            Column {
+                colflags: ColumnFlags::NOT_NULL_FLAG,
+                ..Column::new("Variable_name", ColumnType::MYSQL_TYPE_VAR_STRING)
+            },
+            Column::new("Value", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ];
+        let mut w = results.start(&cols)?;
+        for (name, value) in self.all(scope) {
+            if like.map(|p| like_matches(p, &name)).unwrap_or(true) {
+                w.write_col(name)?;
+                w.write_col(value.show())?;
+                w.end_row()?;
+            }
+        }
+        w.finish()
+    }
+}
+
+fn defaults() -> Vec<(&'static str, VariableValue)> {
+    use self::VariableValue::*;
+    vec![
+        ("auto_increment_increment", Int(1)),
+        ("auto_increment_offset", Int(1)),
+        ("autocommit", Bool(true)),
+        ("character_set_client", Str("utf8mb4".into())),
+        ("character_set_connection", Str("utf8mb4".into())),
+        ("character_set_database", Str("utf8mb4".into())),
+        ("character_set_results", Str("utf8mb4".into())),
+        ("character_set_server", Str("utf8mb4".into())),
+        ("collation_connection", Str("utf8mb4_general_ci".into())),
+        ("collation_database", Str("utf8mb4_general_ci".into())),
+        ("collation_server", Str("utf8mb4_general_ci".into())),
+        ("connect_timeout", Int(10)),
+        ("default_storage_engine", Str("InnoDB".into())),
+        ("init_connect", Str(String::new())),
+        ("interactive_timeout", Int(28800)),
+        ("license", Str("GPL".into())),
+        ("lower_case_table_names", Int(0)),
+        ("max_allowed_packet", Int(67_108_864)),
+        ("net_buffer_length", Int(16384)),
+        ("net_read_timeout", Int(30)),
+        ("net_write_timeout", Int(60)),
+        ("performance_schema", Bool(false)),
+        ("query_cache_size", Int(0)),
+        ("query_cache_type", Str("OFF".into())),
+        ("socket", Str(String::new())),
+        ("sql_auto_is_null", Bool(false)),
+        (
+            "sql_mode",
+            Str(
+                "ONLY_FULL_GROUP_BY,STRICT_TRANS_TABLES,NO_ZERO_IN_DATE,NO_ZERO_DATE,\
+                 ERROR_FOR_DIVISION_BY_ZERO,NO_ENGINE_SUBSTITUTION"
+                    .into(),
+            ),
+        ),
+        ("sql_select_limit", UInt(u64::MAX)),
+        ("system_time_zone", Str("UTC".into())),
+        ("time_zone", Str("SYSTEM".into())),
+        ("transaction_isolation", Str("REPEATABLE-READ".into())),
+        ("transaction_read_only", Bool(false)),
+        ("tx_isolation", Str("REPEATABLE-READ".into())),
+        ("tx_read_only", Bool(false)),
+        ("version", Str(crate::SERVER_VERSION.into())),
+        ("version_comment", Str("msql-srv".into())),
+        ("wait_timeout", Int(28800)),
+    ]
+}
+
+/// A single element of a `LIKE` pattern.
+#[derive(Clone, Copy, PartialEq)]
+enum Like {
+    /// `%`, any run of characters.
+    Any,
+    /// `_`, exactly one character.
+    One,
+    Char(char),
+}
+
+/// Case-insensitive SQL `LIKE` matching with `%` and `_` wildcards and `\` escapes.
+///
+/// On a mismatch, this only ever backtracks to just after the last `%`, which keeps it linear in
+/// the length of the name for each `%` rather than exponential in the number of them.
+fn like_matches(pattern: &str, s: &str) -> bool {
+    let mut p = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        p.push(match c {
+            '%' => Like::Any,
+            '_' => Like::One,
+            '\\' => Like::Char(chars.next().unwrap_or('\\')),
+            c => Like::Char(c),
+        });
+    }
+    let s: Vec<_> = s.chars().collect();
+
+    let (mut pi, mut si) = (0, 0);
+    // the position just after the last `%`, and where in `s` it is currently matched up to
+    let mut backtrack = None;
+    while si < s.len() {
+        match p.get(pi) {
+            Some(Like::Any) => {
+                pi += 1;
+                backtrack = Some((pi, si));
+            }
+            Some(Like::One) => {
+                pi += 1;
+                si += 1;
+            }
+            Some(Like::Char(c)) if c.eq_ignore_ascii_case(&s[si]) => {
+                pi += 1;
+                si += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bs)) => {
+                    // let the `%` swallow one more character, and try again from there
+                    pi = bp;
+                    si = bs + 1;
+                    backtrack = Some((bp, si));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&l| l == Like::Any)
+}
+
+/// A variable referenced by a `SELECT @@...` statement.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Selected {
+    scope: Option<VariableScope>,
+    name: String,
+    column: String,
+}
+
+/// A single assignment in a `SET` statement. A `value` of `None` means `DEFAULT`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Assignment {
+    scope: VariableScope,
+    name: String,
+    value: Option<VariableValue>,
+}
+
+/// A statement concerning system variables.
+#[derive(Debug, PartialEq)]
+pub(crate) enum VarsQuery {
+    Select {
+        items: Vec<Selected>,
+        limit: Option<u64>,
+    },
+    Show {
+        scope: VariableScope,
+        like: Option<String>,
+    },
+    Set(Vec<Assignment>),
+}
+
+fn split_scope(var: &str) -> (Option<VariableScope>, &str) {
+    if let Some(dot) = var.find('.') {
+        let (scope, name) = (&var[..dot], &var[dot + 1..]);
+        if scope.eq_ignore_ascii_case("global") {
+            return (Some(VariableScope::Global), name);
+        } else if scope.eq_ignore_ascii_case("session") || scope.eq_ignore_ascii_case("local") {
+            return (Some(VariableScope::Session), name);
+        }
+    }
+    (None, var)
+}
+
+/// The fixed offset named by a `time_zone` value such as `+02:00`, `-05:30` or `UTC`.
+///
+/// Named time zones other than UTC need MySQL's time zone tables, and are not recognized.
+pub(crate) fn time_zone_offset(name: &str) -> Option<FixedOffset> {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("UTC") {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match name.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let (h, m) = name[1..].split_once(':')?;
+    if !(1..=2).contains(&h.len())
+        || m.len() != 2
+        || !h.bytes().chain(m.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let (h, m) = (h.parse::<i32>().ok()?, m.parse::<i32>().ok()?);
+    // MySQL accepts offsets from -13:59 to +14:00
+    let minutes = h * 60 + m;
+    if m >= 60 || minutes > 14 * 60 || (sign < 0 && minutes == 14 * 60) {
+        return None;
+    }
+    FixedOffset::east_opt(sign * minutes * 60)
+}
+
+/// Recognize `SELECT @@...`, `SHOW VARIABLES` and `SET` statements that only involve system
+/// variables.
+pub(crate) fn parse(q: &str) -> Option<VarsQuery> {
+    let mut p = Parser::new(q)?;
+    if p.keyword("SELECT") {
+        parse_select(&mut p)
+    } else if p.keyword("SHOW") {
+        parse_show(&mut p)
+    } else if p.keyword("SET") {
+        parse_set(&mut p)
+    } else {
+        None
+    }
+}
+
+fn parse_select(p: &mut Parser<'_>) -> Option<VarsQuery> {
+    let mut items = Vec::new();
+    loop {
+        let var = match p.next()? {
+            Token::SysVar(v) => v,
+            _ => return None,
+        };
+        let (scope, name) = split_scope(var);
+        let column = if p.keyword("AS") {
+            match p.next()? {
+                Token::Word(w) => w.to_owned(),
+                Token::Ident(s) | Token::Str(s) => s,
+                _ => return None,
+            }
+        } else {
+            match p.peek() {
+                Some(Token::Word(w)) if !w.eq_ignore_ascii_case("LIMIT") => p.ident()?,
+                Some(Token::Ident(_)) => p.ident()?,
+                _ => format!("@@{}", var),
+            }
+        };
+        items.push(Selected {
+            scope,
+            name: name.to_owned(),
+            column,
+        });
+        if !p.punct(',') {
+            break;
+        }
+    }
+
+    let limit = if p.keyword("LIMIT") {
+        match p.next()? {
+            Token::Number(n) => Some(n.parse().ok()?),
+            _ => return None,
+        }
+    } else {
+        None
+    };
+
+    if p.at_end() {
+        Some(VarsQuery::Select { items, limit })
+    } else {
+        None
+    }
+}
+
+fn parse_show(p: &mut Parser<'_>) -> Option<VarsQuery> {
+    let scope = if p.keyword("GLOBAL") {
+        VariableScope::Global
+    } else {
+        let _ = p.keyword("SESSION") || p.keyword("LOCAL");
+        VariableScope::Session
+    };
+    if !p.keyword("VARIABLES") {
+        return None;
+    }
+    let like = if p.keyword("LIKE") {
+        match p.next()? {
+            Token::Str(s) => Some(s),
+            _ => return None,
+        }
+    } else {
+        None
+    };
+    if p.at_end() {
+        Some(VarsQuery::Show { scope, like })
+    } else {
+        None
+    }
+}
+
+fn parse_set(p: &mut Parser<'_>) -> Option<VarsQuery> {
+    let mut assignments = Vec::new();
+    loop {
+        if p.keyword("NAMES") {
+            let charset = set_value(p)?;
+            let collation = if p.keyword("COLLATE") {
+                Some(set_value(p)?)
+            } else {
+                None
+            };
+            for name in &[
+                "character_set_client",
+                "character_set_connection",
+                "character_set_results",
+            ] {
+                assignments.push(Assignment {
+                    scope: VariableScope::Session,
+                    name: (*name).to_owned(),
+                    value: charset.clone(),
+                });
+            }
+            if let Some(collation) = collation {
+                assignments.push(Assignment {
+                    scope: VariableScope::Session,
+                    name: "collation_connection".to_owned(),
+                    value: collation,
+                });
+            }
+        } else {
+            let mut scope = if p.keyword("GLOBAL") {
+                Some(VariableScope::Global)
+            } else if p.keyword("SESSION") || p.keyword("LOCAL") {
+                Some(VariableScope::Session)
+            } else {
+                None
+            };
+            let name = match p.next()? {
+                Token::SysVar(v) => {
+                    let (s, name) = split_scope(v);
+                    if s.is_some() {
+                        scope = s;
+                    }
+                    name.to_owned()
+                }
+                Token::Word(w) => w.to_owned(),
+                Token::Ident(i) => i,
+                _ => return None,
+            };
+            let assigned = p.punct('=') || (p.punct(':') && p.punct('='));
+            if !assigned {
+                return None;
+            }
+            assignments.push(Assignment {
+                scope: scope.unwrap_or(VariableScope::Session),
+                name,
+                value: set_value(p)?,
+            });
+        }
+
+        if !p.punct(',') {
+            break;
+        }
+    }
+
+    if p.at_end() {
+        Some(VarsQuery::Set(assignments))
+    } else {
+        None
+    }
+}
+
+/// Parse the right-hand side of an assignment. `Some(None)` means `DEFAULT`.
+fn set_value(p: &mut Parser<'_>) -> Option<Option<VariableValue>> {
+    Some(Some(match p.next()? {
+        Token::Word(w) if w.eq_ignore_ascii_case("DEFAULT") => return Some(None),
+        Token::Word(w) if w.eq_ignore_ascii_case("NULL") => VariableValue::Null,
+        Token::Word(w) => VariableValue::Str(w.to_owned()),
+        Token::Ident(s) | Token::Str(s) => VariableValue::Str(s),
+        Token::Number(n) => {
+            if let Ok(i) = n.parse() {
+                VariableValue::Int(i)
+            } else if let Ok(i) = n.parse() {
+                VariableValue::UInt(i)
+            } else {
+                VariableValue::Str(n.to_owned())
+            }
+        }
+        _ => return None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_time_zone_offsets() {
+        let east = |s| FixedOffset::east_opt(s);
+        assert_eq!(time_zone_offset("+00:00"), east(0));
+        assert_eq!(time_zone_offset("utc"), east(0));
+        assert_eq!(time_zone_offset("+5:30"), east(5 * 3600 + 30 * 60));
+        assert_eq!(time_zone_offset("-08:00"), east(-8 * 3600));
+        assert_eq!(time_zone_offset("+14:00"), east(14 * 3600));
+        assert_eq!(time_zone_offset("-14:00"), None);
+        assert_eq!(time_zone_offset("+01:60"), None);
+        assert_eq!(time_zone_offset("+1"), None);
+        assert_eq!(time_zone_offset("+-1:00"), None);
+        assert_eq!(time_zone_offset("SYSTEM"), None);
+        assert_eq!(time_zone_offset("Europe/Oslo"), None);
+    }
+
+    #[test]
+    fn it_parses_selects() {
+        assert_eq!(
+            parse("SELECT @@a, @@session.b AS x LIMIT 1"),
+            Some(VarsQuery::Select {
+                items: vec![
+                    Selected {
+                        scope: None,
+                        name: "a".to_owned(),
+                        column: "@@a".to_owned(),
+                    },
+                    Selected {
+                        scope: Some(VariableScope::Session),
+                        name: "b".to_owned(),
+                        column: "x".to_owned(),
+                    },
+                ],
+                limit: Some(1),
+            })
+        );
+        assert_eq!(parse("SELECT @@a + 1"), None);
+        assert_eq!(parse("SELECT @@a, b"), None);
+    }
+
+    #[test]
+    fn it_parses_show() {
+        assert_eq!(
+            parse("show global variables like 'max%'"),
+            Some(VarsQuery::Show {
+                scope: VariableScope::Global,
+                like: Some("max%".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse(r"SHOW VARIABLES LIKE 'tx\_read\_only'"),
+            Some(VarsQuery::Show {
+                scope: VariableScope::Session,
+                like: Some(r"tx\_read\_only".to_owned()),
+            })
+        );
+        assert_eq!(parse("SHOW VARIABLES WHERE Value = 1"), None);
+    }
+
+    #[test]
+    fn it_parses_set() {
+        assert_eq!(
+            parse("SET @@global.wait_timeout = 10, autocommit := DEFAULT"),
+            Some(VarsQuery::Set(vec![
+                Assignment {
+                    scope: VariableScope::Global,
+                    name: "wait_timeout".to_owned(),
+                    value: Some(VariableValue::Int(10)),
+                },
+                Assignment {
+                    scope: VariableScope::Session,
+                    name: "autocommit".to_owned(),
+                    value: None,
+                },
+            ]))
+        );
+        assert_eq!(parse("SET @x = 1"), None);
+        assert_eq!(
+            parse("SET TRANSACTION ISOLATION LEVEL READ COMMITTED"),
+            None
+        );
+    }
+
+    #[test]
+    fn it_applies_set() {
+        let vars = SystemVariables::new();
+        let conn = vars.new_session();
+        let set = |q, global| match parse(q) {
+            Some(VarsQuery::Set(a)) => conn.apply(a, global),
+            q => panic!("{:?}", q),
+        };
+        set(
+            "SET NAMES latin1, autocommit = OFF, GLOBAL wait_timeout = 5",
+            true,
+        );
+        assert_eq!(conn.get("AUTOCOMMIT"), Some(VariableValue::Bool(false)));
+        assert_eq!(
+            conn.get("character_set_results"),
+            Some(VariableValue::Str("latin1".to_owned()))
+        );
+        assert_eq!(vars.get("autocommit"), Some(VariableValue::Bool(true)));
+        assert_eq!(vars.get("wait_timeout"), Some(VariableValue::Int(5)));
+
+        conn.reset("autocommit");
+        assert_eq!(conn.get("autocommit"), Some(VariableValue::Bool(true)));
+
+        set("SET max_allowed_packet = 1", false);
+        assert_eq!(
+            conn.get("max_allowed_packet"),
+            Some(VariableValue::Int(67_108_864))
+        );
+
+        set(
+            "SET GLOBAL wait_timeout = 6, @@global.autocommit = 0",
+            false,
+        );
+        assert_eq!(vars.get("wait_timeout"), Some(VariableValue::Int(5)));
+        assert_eq!(vars.get("autocommit"), Some(VariableValue::Bool(true)));
+    }
+
+    #[test]
+    fn it_applies_set_default_in_the_given_scope() {
+        let vars = SystemVariables::new();
+        let conn = vars.new_session();
+        conn.start_session();
+        let set = |q| match parse(q) {
+            Some(VarsQuery::Set(a)) => conn.apply(a, true),
+            q => panic!("{:?}", q),
+        };
+        vars.set(VariableScope::Global, "wait_timeout", 5i64);
+        vars.set(VariableScope::Global, "no_such_variable", 1i64);
+        set("SET wait_timeout = 7");
+
+        set("SET GLOBAL wait_timeout = DEFAULT, GLOBAL no_such_variable = DEFAULT");
+        assert_eq!(vars.get("wait_timeout"), Some(VariableValue::Int(28800)));
+        assert_eq!(vars.get("no_such_variable"), Some(VariableValue::Int(1)));
+        assert_eq!(conn.get("wait_timeout"), Some(VariableValue::Int(7)));
+
+        vars.set(VariableScope::Global, "wait_timeout", 9i64);
+        set("SET SESSION wait_timeout = DEFAULT");
+        assert_eq!(conn.get("wait_timeout"), Some(VariableValue::Int(9)));
+        // the session took a copy
+        vars.set(VariableScope::Global, "wait_timeout", 10i64);
+        assert_eq!(conn.get("wait_timeout"), Some(VariableValue::Int(9)));
+    }
+
+    #[test]
+    fn sessions_start_with_a_copy_of_the_global_values() {
+        let vars = SystemVariables::new();
+        let conn = vars.new_session();
+        conn.set(VariableScope::Session, "autocommit", false);
+        conn.start_session();
+        vars.set(VariableScope::Global, "wait_timeout", 5i64);
+        vars.set(VariableScope::Global, "autocommit", true);
+        assert_eq!(conn.get("wait_timeout"), Some(VariableValue::Int(28800)));
+        assert_eq!(conn.get("autocommit"), Some(VariableValue::Bool(false)));
+
+        let later = vars.new_session();
+        later.start_session();
+        assert_eq!(later.get("wait_timeout"), Some(VariableValue::Int(5)));
+    }
+
+    #[test]
+    fn it_matches_like() {
+        assert!(like_matches("max%", "max_allowed_packet"));
+        assert!(like_matches("%_TIMEOUT", "wait_timeout"));
+        assert!(like_matches("tx\\_read_only", "tx_read_only"));
+        assert!(!like_matches("tx\\_read_only", "txxread_only"));
+        assert!(!like_matches("max", "max_allowed_packet"));
+        assert!(like_matches("%", ""));
+        assert!(like_matches("%a%_%", "ab"));
+        assert!(!like_matches("%a%_%", "a"));
+        assert!(like_matches("100\\%", "100%"));
+        assert!(!like_matches("100\\%", "1000"));
+
+        let like = match parse(r"SHOW VARIABLES LIKE 'tx\_read\_only'") {
+            Some(VarsQuery::Show {
+                like: Some(like), ..
+            }) => like,
+            q => panic!("{:?}", q),
+        };
+        assert!(like_matches(&like, "tx_read_only"));
+        assert!(!like_matches(&like, "txxreadxonly"));
+    }
+
+    #[test]
+    fn it_matches_pathological_like_patterns_quickly() {
+        // with naive backtracking, each `%` multiplies the work by the length of the name
+        let pattern = format!("{}b", "%a".repeat(30));
+        let name = "a".repeat(200);
+        let start = std::time::Instant::now();
+        assert!(!like_matches(&pattern, &name));
+        assert!(like_matches(&pattern, &format!("{}b", name)));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+}
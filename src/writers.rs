@@ -1,4 +1,6 @@
-use crate::myc::constants::StatusFlags;
+use crate::charset::{self, Charset};
+use crate::commands::MARIADB_CLIENT_EXTENDED_TYPE_INFO;
+use crate::myc::constants::{ColumnFlags, StatusFlags};
 use crate::myc::io::WriteMysqlExt;
 use crate::packet::PacketConn;
 use crate::{Column, ErrorKind};
@@ -43,6 +45,23 @@ pub fn write_err<W: Read + Write>(
 
 use std::borrow::Borrow;
 
+pub(crate) fn write_progress_packet<W: Read + Write>(
+    stage: u8,
+    max_stage: u8,
+    progress: u32,
+    stage_name: &[u8],
+    w: &mut PacketConn<W>,
+) -> io::Result<()> {
+    w.write_u8(0xFF)?;
+    w.write_u16::<LittleEndian>(0xFFFF)?;
+    w.write_u8(1)?; // number of strings
+    w.write_u8(stage)?;
+    w.write_u8(max_stage)?;
+    w.write_u24::<LittleEndian>(progress)?;
+    w.write_lenenc_str(stage_name)?;
+    w.end_packet()
+}
+
 pub(crate) fn write_prepare_ok<'a, PI, CI, W: Read + Write>(
     id: u32,
     params: PI,
@@ -68,8 +87,8 @@ where
     w.write_u16::<LittleEndian>(0)?; // number of warnings
     w.end_packet()?;
 
-    write_column_definitions(pi, w, false, true)?;
-    write_column_definitions(ci, w, false, true)
+    write_column_definitions(pi, w, false, true, Charset::Utf8)?;
+    write_column_definitions(ci, w, false, true, Charset::Utf8)
 }
 
 pub(crate) fn write_column_definitions<'a, I, W: Read + Write>(
@@ -77,6 +96,7 @@ pub(crate) fn write_column_definitions<'a, I, W: Read + Write>(
     w: &mut PacketConn<W>,
     is_comm_field_list_response: bool,
     only_eof_on_nonempty: bool,
+    charset: Charset,
 ) -> io::Result<()>
 where
     I: IntoIterator<Item = &'a Column>,
@@ -92,12 +112,30 @@ where
         w.write_lenenc_str(b"")?;
         w.write_lenenc_str(c.column.as_bytes())?;
         w.write_lenenc_str(b"")?;
+        if w.has_mariadb_capability(MARIADB_CLIENT_EXTENDED_TYPE_INFO) {
+            let mut info = Vec::new();
+            if let Some(ref name) = c.extended_type_name {
+                info.write_u8(0x00)?;
+                info.write_lenenc_str(name.as_bytes())?;
+            }
+            if let Some(ref format) = c.extended_format {
+                info.write_u8(0x01)?;
+                info.write_lenenc_str(format.as_bytes())?;
+            }
+            w.write_lenenc_str(&info)?;
+        }
         w.write_lenenc_int(0xC)?;
-        w.write_u16::<LittleEndian>(UTF8_GENERAL_CI)?;
+        if charset::is_text_column(c) {
+            w.write_u16::<LittleEndian>(charset.collation())?;
+        } else if c.colflags.contains(ColumnFlags::BINARY_FLAG) {
+            w.write_u16::<LittleEndian>(Charset::Binary.collation())?;
+        } else {
+            w.write_u16::<LittleEndian>(UTF8_GENERAL_CI)?;
+        }
         w.write_u32::<LittleEndian>(1024)?;
         w.write_u8(c.coltype as u8)?;
         w.write_u16::<LittleEndian>(c.colflags.bits())?;
-        w.write_all(&[0x00])?; // decimals
+        w.write_u8(c.decimals.unwrap_or(0))?;
         w.write_all(&[0x00, 0x00])?; // unused
 
         if is_comm_field_list_response {
@@ -121,6 +159,7 @@ where
 pub(crate) fn column_definitions<'a, I, W: Read + Write>(
     i: I,
     w: &mut PacketConn<W>,
+    charset: Charset,
 ) -> io::Result<()>
 where
     I: IntoIterator<Item = &'a Column>,
@@ -130,5 +169,5 @@ where
     let i = i.into_iter();
     w.write_lenenc_int(i.len() as u64)?;
     w.end_packet()?;
-    write_column_definitions(i, w, false, false)
+    write_column_definitions(i, w, false, false, charset)
 }
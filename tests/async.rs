@@ -162,12 +162,10 @@ fn empty_response() {
 
 #[test]
 fn no_rows() {
-    let cols = [Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = [Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     TestingShim::new(
         move |_, w| w.start(&cols[..])?.finish(),
         |_| unreachable!(),
@@ -259,12 +257,10 @@ fn error_response() {
 
 #[test]
 fn empty_on_drop() {
-    let cols = [Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = [Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     TestingShim::new(
         move |_, w| w.start(&cols[..]).map(|_| ()),
         |_| unreachable!(),
@@ -281,12 +277,10 @@ fn empty_on_drop() {
 fn it_queries_nulls() {
     TestingShim::new(
         |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut w = w.start(cols)?;
             w.write_col(None::<i16>)?;
             w.finish()
@@ -307,12 +301,10 @@ fn it_queries_nulls() {
 fn it_queries() {
     TestingShim::new(
         |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut w = w.start(cols)?;
             w.write_col(1024i16)?;
             w.finish()
@@ -334,18 +326,8 @@ fn it_queries_many_rows() {
     TestingShim::new(
         |_, w| {
             let cols = &[
-                Column {
-                    table: String::new(),
-                    column: "a".to_owned(),
-                    coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                    colflags: myc::constants::ColumnFlags::empty(),
-                },
-                Column {
-                    table: String::new(),
-                    column: "b".to_owned(),
-                    coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                    colflags: myc::constants::ColumnFlags::empty(),
-                },
+                Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+                Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
             ];
             let mut w = w.start(cols)?;
             w.write_col(1024i16)?;
@@ -372,19 +354,17 @@ fn it_queries_many_rows() {
 
 #[test]
 fn it_prepares() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -400,7 +380,7 @@ fn it_prepares() {
                 params[0].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_LONGLONG
             );
-            assert_eq!(Into::<i8>::into(params[0].value), 42i8);
+            assert_eq!(i8::try_from(params[0].value).unwrap(), 42i8);
 
             let mut w = w.start(&cols)?;
             w.write_col(1024i16)?;
@@ -422,49 +402,16 @@ fn it_prepares() {
 
 #[test]
 fn insert_exec() {
+    use std::convert::TryFrom;
+
     let params = vec![
-        Column {
-            table: String::new(),
-            column: "username".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "email".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "pw".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "created".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_DATETIME,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "session".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "rss".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "mail".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("username", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("email", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("pw", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("created", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+        Column::new("session", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("rss", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("mail", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
     ];
 
     TestingShim::new(
@@ -500,19 +447,22 @@ fn insert_exec() {
                 params[6].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING
             );
-            assert_eq!(Into::<&str>::into(params[0].value), "user199");
-            assert_eq!(Into::<&str>::into(params[1].value), "user199@example.com");
+            assert_eq!(<&str>::try_from(params[0].value).unwrap(), "user199");
             assert_eq!(
-                Into::<&str>::into(params[2].value),
+                <&str>::try_from(params[1].value).unwrap(),
+                "user199@example.com"
+            );
+            assert_eq!(
+                <&str>::try_from(params[2].value).unwrap(),
                 "$2a$10$Tq3wrGeC0xtgzuxqOlc3v.07VTUvxvwI70kuoVihoO2cE5qj7ooka"
             );
             assert_eq!(
-                Into::<chrono::NaiveDateTime>::into(params[3].value),
+                chrono::NaiveDateTime::try_from(params[3].value).unwrap(),
                 chrono::NaiveDate::from_ymd(2018, 4, 6).and_hms(13, 0, 56)
             );
-            assert_eq!(Into::<&str>::into(params[4].value), "token199");
-            assert_eq!(Into::<&str>::into(params[5].value), "rsstoken199");
-            assert_eq!(Into::<&str>::into(params[6].value), "mtok199");
+            assert_eq!(<&str>::try_from(params[4].value).unwrap(), "token199");
+            assert_eq!(<&str>::try_from(params[5].value).unwrap(), "rsstoken199");
+            assert_eq!(<&str>::try_from(params[6].value).unwrap(), "mtok199");
 
             w.completed(42, 1)
         },
@@ -550,19 +500,17 @@ fn insert_exec() {
 
 #[test]
 fn send_long() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_BLOB,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_BLOB,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -578,7 +526,7 @@ fn send_long() {
                 params[0].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING
             );
-            assert_eq!(Into::<&[u8]>::into(params[0].value), b"Hello world");
+            assert_eq!(<&[u8]>::try_from(params[0].value).unwrap(), b"Hello world");
 
             let mut w = w.start(&cols)?;
             w.write_col(1024i16)?;
@@ -601,18 +549,8 @@ fn send_long() {
 #[test]
 fn it_prepares_many() {
     let cols = vec![
-        Column {
-            table: String::new(),
-            column: "a".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "b".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
     let cols2 = cols.clone();
 
@@ -652,19 +590,15 @@ fn it_prepares_many() {
 
 #[test]
 fn prepared_empty() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols;
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -686,12 +620,10 @@ fn prepared_empty() {
 
 #[test]
 fn prepared_no_params() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
     let params = vec![];
 
@@ -719,34 +651,16 @@ fn prepared_no_params() {
 
 #[test]
 fn prepared_nulls() {
+    use std::convert::TryFrom;
+
     let cols = vec![
-        Column {
-            table: String::new(),
-            column: "a".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "b".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
     let cols2 = cols.clone();
     let params = vec![
-        Column {
-            table: String::new(),
-            column: "c".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "d".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("c", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("d", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
 
     TestingShim::new(
@@ -765,7 +679,7 @@ fn prepared_nulls() {
                 params[1].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_LONGLONG
             );
-            assert_eq!(Into::<i8>::into(params[1].value), 42i8);
+            assert_eq!(i8::try_from(params[1].value).unwrap(), 42i8);
 
             let mut w = w.start(&cols)?;
             w.write_row(vec![None::<i16>, Some(42)])?;
@@ -787,12 +701,10 @@ fn prepared_nulls() {
 
 #[test]
 fn prepared_no_rows() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
     TestingShim::new(
         |_, _| unreachable!(),
@@ -0,0 +1,186 @@
+#![cfg(feature = "async")]
+
+extern crate async_trait;
+extern crate futures;
+extern crate msql_srv;
+extern crate mysql;
+extern crate mysql_common as myc;
+
+use async_trait::async_trait;
+use futures::io::AllowStdIo;
+use mysql::prelude::*;
+use mysql::OptsBuilder;
+use std::io;
+use std::net;
+use std::thread;
+
+use msql_srv::{
+    AsyncMysqlIntermediary, AsyncMysqlShim, AsyncStream, AuthenticationContext, Column,
+    ParamParser, QueryResultWriter, StatementMetaWriter,
+};
+
+#[derive(Default)]
+struct Backend {
+    #[cfg(feature = "tls")]
+    tls: Option<std::sync::Arc<rustls::ServerConfig>>,
+    username: Option<Vec<u8>>,
+}
+
+#[async_trait]
+impl AsyncMysqlShim for Backend {
+    type Error = io::Error;
+
+    async fn on_prepare(
+        &mut self,
+        _: &str,
+        info: StatementMetaWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        info.reply(1, &[], &[])
+    }
+
+    async fn on_execute(
+        &mut self,
+        _: u32,
+        _: ParamParser<'_>,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        results.completed(0, 0)
+    }
+
+    async fn on_close(&mut self, _: u32) {}
+
+    async fn on_query(
+        &mut self,
+        query: &str,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        if let Some(n) = query.strip_prefix("SELECT n FROM stream LIMIT ") {
+            let n: i64 = n.parse().unwrap();
+            let cols = [Column::new(
+                "n",
+                myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+            )];
+            // rows trickle in from another thread, and the stream fails after `n` of them
+            let (tx, rx) = futures::channel::mpsc::unbounded();
+            thread::spawn(move || {
+                for i in 0..n {
+                    tx.unbounded_send(Ok(vec![i])).unwrap();
+                }
+                tx.unbounded_send(Err("backend went away")).unwrap();
+            });
+            return results.stream_rows(&cols, rx).await;
+        }
+
+        let cols = [Column::new(
+            "user",
+            myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+        )];
+        let mut w = results.start(&cols)?;
+        w.write_row([self.username.as_deref().unwrap_or_default()])?;
+        w.finish()
+    }
+
+    #[cfg(feature = "tls")]
+    fn tls_config(&self) -> Option<std::sync::Arc<rustls::ServerConfig>> {
+        self.tls.clone()
+    }
+
+    async fn after_authentication(
+        &mut self,
+        context: &AuthenticationContext<'_>,
+    ) -> io::Result<()> {
+        self.username = context.username.clone();
+        Ok(())
+    }
+}
+
+impl Backend {
+    fn test<C>(self, opts: OptsBuilder, c: C)
+    where
+        C: FnOnce(&mut mysql::Conn),
+    {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // no async runtime at all; just the executor from `futures`, blocking on a std stream
+        let jh = thread::spawn(move || {
+            let (s, _) = listener.accept().unwrap();
+            futures::executor::block_on(AsyncMysqlIntermediary::run_on(self, AllowStdIo::new(s)))
+        });
+
+        let mut db = mysql::Conn::new(opts.tcp_port(port).user(Some("username"))).unwrap();
+        c(&mut db);
+        drop(db);
+        jh.join().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn it_runs_on_any_executor() {
+    Backend::default().test(OptsBuilder::default(), |db| {
+        let user: Option<String> = db.query_first("SELECT CURRENT_USER()").unwrap();
+        assert_eq!(user.as_deref(), Some("username"));
+    })
+}
+
+#[test]
+fn it_streams_rows() {
+    Backend::default().test(OptsBuilder::default(), |db| {
+        let mut n = 0;
+        let mut result = db.query_iter("SELECT n FROM stream LIMIT 10000").unwrap();
+        let err = loop {
+            match result.next() {
+                Some(Ok(row)) => {
+                    assert_eq!(mysql::from_row::<i64>(row), n);
+                    n += 1;
+                }
+                Some(Err(e)) => break e,
+                None => panic!("resultset ended without an error"),
+            }
+        };
+        drop(result);
+        assert_eq!(n, 10000);
+        match err {
+            mysql::Error::MySqlError(e) => {
+                assert_eq!(e.code, msql_srv::ErrorKind::ER_UNKNOWN_ERROR as u16);
+                assert_eq!(e.message, "backend went away");
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // the connection is still good after the failed resultset
+        let user: Option<String> = db.query_first("SELECT CURRENT_USER()").unwrap();
+        assert_eq!(user.as_deref(), Some("username"));
+    })
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn it_upgrades_to_tls() {
+    use rustls::{Certificate, PrivateKey, ServerConfig};
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(cert.serialize_der().unwrap())],
+            PrivateKey(cert.get_key_pair().serialize_der()),
+        )
+        .unwrap();
+
+    let backend = Backend {
+        tls: Some(std::sync::Arc::new(config)),
+        ..Backend::default()
+    };
+    let opts = OptsBuilder::default()
+        .ip_or_hostname(Some("localhost"))
+        .ssl_opts(Some(
+            mysql::SslOpts::default().with_danger_accept_invalid_certs(true),
+        ));
+    backend.test(opts, |db| {
+        for _ in 0..3 {
+            let user: Option<String> = db.query_first("SELECT CURRENT_USER()").unwrap();
+            assert_eq!(user.as_deref(), Some("username"));
+        }
+    })
+}
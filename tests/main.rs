@@ -6,8 +6,9 @@ extern crate nom;
 
 use msql_srv::AuthenticationContext;
 use msql_srv::{
-    Column, ErrorKind, InitWriter, MysqlIntermediary, MysqlShim, ParamParser, QueryResultWriter,
-    StatementMetaWriter,
+    BootstrapQuery, Column, ErrorKind, InitWriter, MysqlIntermediary, MysqlServer, MysqlShim,
+    ParamParser, QueryResultWriter, ShimFactory, ShutdownHandle, StatementMetaWriter,
+    SystemVariables, Transport, VariableScope,
 };
 use mysql::prelude::*;
 use mysql::MySqlError;
@@ -48,6 +49,10 @@ struct TestingShim<Q, P, E, I, A> {
     on_e: E,
     on_i: I,
     after_auth: A,
+    bootstrap: bool,
+    set_global: bool,
+    shutdown: Option<ShutdownHandle>,
+    vars: Option<SystemVariables>,
     #[cfg(feature = "tls")]
     server_tls: Option<std::sync::Arc<rustls::ServerConfig>>,
     client_tls: Option<SslOpts>,
@@ -97,6 +102,22 @@ where
         (self.on_q)(query, results)
     }
 
+    fn answer_bootstrap_query(&self, _: BootstrapQuery) -> bool {
+        self.bootstrap
+    }
+
+    fn allow_set_global(&self) -> bool {
+        self.set_global
+    }
+
+    fn shutdown_handle(&self) -> Option<ShutdownHandle> {
+        self.shutdown.clone()
+    }
+
+    fn system_variables(&mut self) -> SystemVariables {
+        self.vars.take().unwrap_or_default()
+    }
+
     #[cfg(feature = "tls")]
     fn tls_config(&self) -> Option<std::sync::Arc<rustls::ServerConfig>> {
         self.server_tls.as_ref().map(std::sync::Arc::clone)
@@ -126,6 +147,10 @@ where
             on_e,
             on_i,
             after_auth,
+            bootstrap: false,
+            set_global: false,
+            shutdown: None,
+            vars: None,
             #[cfg(feature = "tls")]
             server_tls: None,
             client_tls: None,
@@ -144,6 +169,26 @@ where
         self
     }
 
+    fn with_bootstrap(mut self) -> Self {
+        self.bootstrap = true;
+        self
+    }
+
+    fn with_set_global(mut self) -> Self {
+        self.set_global = true;
+        self
+    }
+
+    fn with_shutdown_handle(mut self, handle: ShutdownHandle) -> Self {
+        self.shutdown = Some(handle);
+        self
+    }
+
+    fn with_system_variables(mut self, vars: SystemVariables) -> Self {
+        self.vars = Some(vars);
+        self
+    }
+
     #[cfg(all(feature = "tls", unix))]
     fn with_tls(mut self, client: bool, server: bool, use_client_certs: bool) -> Self {
         use std::fs::File;
@@ -581,12 +626,10 @@ fn empty_response() {
 
 #[test]
 fn no_rows() {
-    let cols = [Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = [Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     TestingShim::new(
         move |_, w| w.start(&cols[..])?.finish(),
         |_| unreachable!(),
@@ -658,12 +701,10 @@ fn error_in_result_set_response() {
     let err = (ErrorKind::ER_NO, "clearly not");
     TestingShim::new(
         move |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut w = w.start(cols)?;
             w.write_col(1024)?;
             w.finish_error(err.0, &err.1.as_bytes())
@@ -694,12 +735,10 @@ fn error_in_result_set_response() {
 
 #[test]
 fn empty_on_drop() {
-    let cols = [Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = [Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     TestingShim::new(
         move |_, w| w.start(&cols[..]).map(|_| ()),
         |_| unreachable!(),
@@ -716,12 +755,10 @@ fn empty_on_drop() {
 fn it_queries_nulls() {
     TestingShim::new(
         |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut w = w.start(cols)?;
             w.write_col(None::<i16>)?;
             w.finish()
@@ -746,12 +783,10 @@ fn it_queries_nulls() {
 fn it_queries() {
     TestingShim::new(
         |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut w = w.start(cols)?;
             w.write_col(1024i16)?;
             w.finish()
@@ -776,12 +811,10 @@ fn it_queries() {
 fn multi_result() {
     TestingShim::new(
         |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
+            let cols = &[Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+            )];
             let mut row = w.start(cols)?;
             row.write_col(1024i16)?;
             let w = row.finish_one()?;
@@ -821,18 +854,8 @@ fn it_queries_many_rows() {
     TestingShim::new(
         |_, w| {
             let cols = &[
-                Column {
-                    table: String::new(),
-                    column: "a".to_owned(),
-                    coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                    colflags: myc::constants::ColumnFlags::empty(),
-                },
-                Column {
-                    table: String::new(),
-                    column: "b".to_owned(),
-                    coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                    colflags: myc::constants::ColumnFlags::empty(),
-                },
+                Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+                Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
             ];
             let mut w = w.start(cols)?;
             w.write_col(1024i16)?;
@@ -860,19 +883,17 @@ fn it_queries_many_rows() {
 
 #[test]
 fn it_prepares() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -888,7 +909,7 @@ fn it_prepares() {
                 params[0].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_LONGLONG
             );
-            assert_eq!(Into::<i8>::into(params[0].value), 42i8);
+            assert_eq!(i8::try_from(params[0].value).unwrap(), 42i8);
 
             let mut w = w.start(&cols)?;
             w.write_col(1024i16)?;
@@ -911,50 +932,396 @@ fn it_prepares() {
 }
 
 #[test]
-fn insert_exec() {
-    let params = vec![
-        Column {
-            table: String::new(),
-            column: "username".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+#[cfg(feature = "rust_decimal")]
+fn it_rounds_decimals_to_the_column_scale() {
+    use rust_decimal::Decimal;
+    use std::convert::TryFrom;
+
+    let cols = vec![Column {
+        decimals: Some(2),
+        ..Column::new("price", myc::constants::ColumnType::MYSQL_TYPE_NEWDECIMAL)
+    }];
+    let cols2 = cols.clone();
+    let cols3 = cols.clone();
+    let params = cols.clone();
+
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_col("12.345".parse::<Decimal>().unwrap())?;
+            w.finish()
         },
-        Column {
-            table: String::new(),
-            column: "email".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+        |_| 0,
+        move |_, params, w| {
+            let price = Decimal::try_from(params[0].value)?;
+            assert_eq!(price, "0.1".parse().unwrap());
+
+            let mut w = w.start(&cols2)?;
+            w.write_col(price * Decimal::from(3))?;
+            w.finish()
         },
-        Column {
-            table: String::new(),
-            column: "pw".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols3)
+    .test(|db| {
+        let price: String = db.query_first("SELECT price FROM t").unwrap().unwrap();
+        assert_eq!(price, "12.35");
+        let price: String = db
+            .exec_first("SELECT price * 3 FROM t WHERE price = ?", ("0.1",))
+            .unwrap()
+            .unwrap();
+        assert_eq!(price, "0.30");
+    })
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+fn it_reads_and_writes_json() {
+    use msql_srv::Json;
+    use std::convert::TryFrom;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Tags {
+        tags: Vec<String>,
+    }
+
+    let cols = vec![Column::new(
+        "doc",
+        myc::constants::ColumnType::MYSQL_TYPE_JSON,
+    )];
+    let cols2 = cols.clone();
+    let params = cols.clone();
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let Json(mut doc) = Json::<Tags>::try_from(params[0].value)?;
+            doc.tags.push("new".to_owned());
+
+            let mut w = w.start(&cols)?;
+            w.write_col(Json(doc))?;
+            w.finish()
         },
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols2)
+    .test(|db| {
+        let doc: String = db
+            .exec_first(
+                "SELECT JSON_ARRAY_APPEND(?, '$.tags', 'new')",
+                (r#"{"tags":["old"]}"#,),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(doc, r#"{"tags":["old","new"]}"#);
+    })
+}
+
+#[test]
+#[cfg(feature = "uuid")]
+fn it_reads_and_writes_uuids() {
+    use std::convert::TryFrom;
+    use uuid::Uuid;
+
+    const ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    let cols = vec![
         Column {
-            table: String::new(),
-            column: "created".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_DATETIME,
-            colflags: myc::constants::ColumnFlags::empty(),
+            colflags: myc::constants::ColumnFlags::BINARY_FLAG,
+            ..Column::new("id", myc::constants::ColumnType::MYSQL_TYPE_STRING)
         },
-        Column {
-            table: String::new(),
-            column: "session".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+        Column::new("name", myc::constants::ColumnType::MYSQL_TYPE_STRING),
+    ];
+    let cols2 = cols.clone();
+    let params = vec![cols[1].clone()];
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let id = Uuid::try_from(params[0].value)?;
+
+            let mut w = w.start(&cols)?;
+            w.write_col(id)?;
+            w.write_col(id)?;
+            w.finish()
         },
-        Column {
-            table: String::new(),
-            column: "rss".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols2)
+    .test(|db| {
+        let (id, name): (Vec<u8>, String) = db
+            .exec_first("SELECT id, name FROM t WHERE name = ?", (ID,))
+            .unwrap()
+            .unwrap();
+        assert_eq!(id, Uuid::parse_str(ID).unwrap().as_bytes());
+        assert_eq!(name, ID);
+    })
+}
+
+#[test]
+#[cfg(feature = "time")]
+fn it_reads_and_writes_time_values() {
+    use std::convert::TryFrom;
+
+    let cols = vec![
+        Column::new("at", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+        Column::new("took", myc::constants::ColumnType::MYSQL_TYPE_TIME),
+    ];
+    let cols2 = cols.clone();
+    let params = cols.clone();
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let at = time::PrimitiveDateTime::try_from(params[0].value)?;
+            let took = time::Duration::try_from(params[1].value)?;
+
+            let mut w = w.start(&cols)?;
+            w.write_col(at + took)?;
+            w.write_col(took - time::Duration::days(2))?;
+            w.finish()
         },
-        Column {
-            table: String::new(),
-            column: "mail".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
-            colflags: myc::constants::ColumnFlags::empty(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols2)
+    .test(|db| {
+        let row: mysql::Row = db
+            .exec_first(
+                "SELECT at + took, took - INTERVAL 2 DAY FROM t WHERE at = ? AND took = ?",
+                (
+                    mysql::Value::Date(2024, 2, 29, 23, 0, 0, 0),
+                    mysql::Value::Time(false, 1, 2, 3, 4, 5),
+                ),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            row.as_ref(0),
+            Some(&mysql::Value::Date(2024, 3, 2, 1, 3, 4, 5))
+        );
+        assert_eq!(
+            row.as_ref(1),
+            Some(&mysql::Value::Time(true, 0, 21, 56, 55, 999_995))
+        );
+    })
+}
+
+#[test]
+fn it_decodes_temporal_params() {
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "took",
+        myc::constants::ColumnType::MYSQL_TYPE_TIME,
+    )];
+    let cols2 = cols.clone();
+    let params = vec![
+        Column::new("on", myc::constants::ColumnType::MYSQL_TYPE_DATE),
+        cols[0].clone(),
+        cols[0].clone(),
+    ];
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let params: Vec<_> = params.into_iter().collect();
+            assert_eq!(
+                chrono::NaiveDate::try_from(params[0].value)?,
+                chrono::NaiveDate::from_ymd(2024, 2, 29)
+            );
+            assert_eq!(
+                chrono::NaiveTime::try_from(params[1].value)?,
+                chrono::NaiveTime::from_hms_micro(13, 14, 15, 16)
+            );
+            assert!(chrono::NaiveTime::try_from(params[2].value).is_err());
+            let took = msql_srv::Duration::try_from(params[2].value)?;
+            assert!(took.is_negative());
+
+            let mut w = w.start(&cols)?;
+            w.write_col(took)?;
+            w.finish()
+        },
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols2)
+    .test(|db| {
+        let took: mysql::Value = db
+            .exec_first(
+                "SELECT took FROM t WHERE on = ? AND at = ? AND took = ?",
+                (
+                    mysql::Value::Date(2024, 2, 29, 0, 0, 0, 0),
+                    mysql::Value::Time(false, 0, 13, 14, 15, 16),
+                    mysql::Value::Time(true, 1, 2, 3, 4, 0),
+                ),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(took, mysql::Value::Time(true, 1, 2, 3, 4, 0));
+    })
+}
+
+#[test]
+fn it_echoes_owned_params() {
+    let cols = vec![
+        Column::new("s", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+        Column::new("n", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+        Column::new("at", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+        Column::new("took", myc::constants::ColumnType::MYSQL_TYPE_TIME),
+    ];
+    let cols2 = cols.clone();
+    let params = cols.clone();
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let owned: Vec<msql_srv::OwnedValue> =
+                params.into_iter().map(|p| p.value.to_owned()).collect();
+            // the values no longer borrow from the packet, so they can go to another thread
+            let owned = thread::spawn(move || owned).join().unwrap();
+
+            let mut w = w.start(&cols)?;
+            for v in owned {
+                w.write_col(v)?;
+            }
+            w.finish()
         },
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols2)
+    .test(|db| {
+        let sent = (
+            mysql::Value::Bytes(b"hello".to_vec()),
+            mysql::Value::Int(-42),
+            mysql::Value::Date(2024, 2, 29, 13, 14, 15, 16),
+            mysql::Value::Time(true, 1, 2, 3, 4, 0),
+        );
+        let row: mysql::Row = db
+            .exec_first("SELECT ?, ?, ?, ?", sent.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.as_ref(0), Some(&sent.0));
+        assert_eq!(row.as_ref(1), Some(&sent.1));
+        assert_eq!(row.as_ref(2), Some(&sent.2));
+        assert_eq!(row.as_ref(3), Some(&sent.3));
+    })
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn it_deserializes_params() {
+    #[derive(serde::Deserialize)]
+    struct Login<'a> {
+        user: &'a str,
+        attempts: u8,
+        remember: bool,
+    }
+
+    // the testing shim hands out parameters that have already been iterated over
+    struct LoginShim;
+
+    impl<W: Read + Write> MysqlShim<W> for LoginShim {
+        type Error = io::Error;
+
+        fn on_prepare(&mut self, _: &str, info: StatementMetaWriter<'_, W>) -> io::Result<()> {
+            let params = [
+                Column::new("user", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+                Column::new("attempts", myc::constants::ColumnType::MYSQL_TYPE_LONG),
+                Column::new("remember", myc::constants::ColumnType::MYSQL_TYPE_TINY),
+            ];
+            let cols = [Column::new(
+                "ok",
+                myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+            )];
+            info.reply(1, &params, &cols)
+        }
+
+        fn on_execute(
+            &mut self,
+            _: u32,
+            params: ParamParser<'_>,
+            results: QueryResultWriter<'_, W>,
+        ) -> io::Result<()> {
+            match params.deserialize::<Login<'_>>() {
+                Ok(login) => {
+                    let cols = [Column::new(
+                        "ok",
+                        myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+                    )];
+                    let mut w = results.start(&cols)?;
+                    w.write_col(format!(
+                        "{} {} {}",
+                        login.user, login.attempts, login.remember
+                    ))?;
+                    w.finish()
+                }
+                Err(e) => results.error(ErrorKind::ER_WRONG_ARGUMENTS, e.to_string().as_bytes()),
+            }
+        }
+
+        fn on_close(&mut self, _: u32) {}
+
+        fn on_query(&mut self, _: &str, _: QueryResultWriter<'_, W>) -> io::Result<()> {
+            unreachable!()
+        }
+    }
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(LoginShim, s)
+    });
+
+    let mut db = mysql::Conn::new(
+        OptsBuilder::default()
+            .ip_or_hostname(Some("localhost"))
+            .tcp_port(port),
+    )
+    .unwrap();
+    let query = "SELECT ok FROM t WHERE u = ? AND a = ? AND r = ?";
+    let ok: String = db.exec_first(query, ("jane", "3", 1)).unwrap().unwrap();
+    assert_eq!(ok, "jane 3 true");
+
+    match db.exec_first::<String, _, _>(query, ("jane", 1000, 1)) {
+        Err(mysql::Error::MySqlError(e)) => assert_eq!(
+            e.message,
+            "parameter 1: cannot convert Int(1000) to u8: out of range"
+        ),
+        r => panic!("unexpected result {:?}", r),
+    }
+    drop(db);
+    jh.join().unwrap().unwrap();
+}
+
+#[test]
+fn insert_exec() {
+    use std::convert::TryFrom;
+
+    let params = vec![
+        Column::new("username", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("email", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("pw", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("created", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+        Column::new("session", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("rss", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
+        Column::new("mail", myc::constants::ColumnType::MYSQL_TYPE_VARCHAR),
     ];
 
     TestingShim::new(
@@ -990,19 +1357,22 @@ fn insert_exec() {
                 params[6].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING
             );
-            assert_eq!(Into::<&str>::into(params[0].value), "user199");
-            assert_eq!(Into::<&str>::into(params[1].value), "user199@example.com");
+            assert_eq!(<&str>::try_from(params[0].value).unwrap(), "user199");
+            assert_eq!(
+                <&str>::try_from(params[1].value).unwrap(),
+                "user199@example.com"
+            );
             assert_eq!(
-                Into::<&str>::into(params[2].value),
+                <&str>::try_from(params[2].value).unwrap(),
                 "$2a$10$Tq3wrGeC0xtgzuxqOlc3v.07VTUvxvwI70kuoVihoO2cE5qj7ooka"
             );
             assert_eq!(
-                Into::<chrono::NaiveDateTime>::into(params[3].value),
+                chrono::NaiveDateTime::try_from(params[3].value).unwrap(),
                 chrono::NaiveDate::from_ymd(2018, 4, 6).and_hms(13, 0, 56)
             );
-            assert_eq!(Into::<&str>::into(params[4].value), "token199");
-            assert_eq!(Into::<&str>::into(params[5].value), "rsstoken199");
-            assert_eq!(Into::<&str>::into(params[6].value), "mtok199");
+            assert_eq!(<&str>::try_from(params[4].value).unwrap(), "token199");
+            assert_eq!(<&str>::try_from(params[5].value).unwrap(), "rsstoken199");
+            assert_eq!(<&str>::try_from(params[6].value).unwrap(), "mtok199");
 
             w.completed(42, 1)
         },
@@ -1035,19 +1405,17 @@ fn insert_exec() {
 
 #[test]
 fn send_long() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_BLOB,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_BLOB,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -1063,7 +1431,7 @@ fn send_long() {
                 params[0].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING
             );
-            assert_eq!(Into::<&[u8]>::into(params[0].value), b"Hello world");
+            assert_eq!(<&[u8]>::try_from(params[0].value).unwrap(), b"Hello world");
 
             let mut w = w.start(&cols)?;
             w.write_col(1024i16)?;
@@ -1088,18 +1456,8 @@ fn send_long() {
 #[test]
 fn it_prepares_many() {
     let cols = vec![
-        Column {
-            table: String::new(),
-            column: "a".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "b".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
     let cols2 = cols.clone();
 
@@ -1139,19 +1497,15 @@ fn it_prepares_many() {
 
 #[test]
 fn prepared_empty() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols;
-    let params = vec![Column {
-        table: String::new(),
-        column: "c".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let params = vec![Column::new(
+        "c",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
 
     TestingShim::new(
         |_, _| unreachable!(),
@@ -1177,12 +1531,10 @@ fn prepared_empty() {
 
 #[test]
 fn prepared_no_params() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
     let params = vec![];
 
@@ -1208,34 +1560,16 @@ fn prepared_no_params() {
 
 #[test]
 fn prepared_nulls() {
+    use std::convert::TryFrom;
+
     let cols = vec![
-        Column {
-            table: String::new(),
-            column: "a".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "b".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
     let cols2 = cols.clone();
     let params = vec![
-        Column {
-            table: String::new(),
-            column: "c".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
-        Column {
-            table: String::new(),
-            column: "d".to_owned(),
-            coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-            colflags: myc::constants::ColumnFlags::empty(),
-        },
+        Column::new("c", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
+        Column::new("d", myc::constants::ColumnType::MYSQL_TYPE_SHORT),
     ];
 
     TestingShim::new(
@@ -1254,7 +1588,7 @@ fn prepared_nulls() {
                 params[1].coltype,
                 myc::constants::ColumnType::MYSQL_TYPE_LONGLONG
             );
-            assert_eq!(Into::<i8>::into(params[1].value), 42i8);
+            assert_eq!(i8::try_from(params[1].value).unwrap(), 42i8);
 
             let mut w = w.start(&cols)?;
             w.write_row(vec![None::<i16>, Some(42)])?;
@@ -1282,12 +1616,10 @@ fn prepared_nulls() {
 
 #[test]
 fn prepared_no_rows() {
-    let cols = vec![Column {
-        table: String::new(),
-        column: "a".to_owned(),
-        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-        colflags: myc::constants::ColumnFlags::empty(),
-    }];
+    let cols = vec![Column::new(
+        "a",
+        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+    )];
     let cols2 = cols.clone();
     TestingShim::new(
         |_, _| unreachable!(),
@@ -1347,3 +1679,1167 @@ fn really_long_query() {
         db.query_iter(long).unwrap();
     })
 }
+
+#[test]
+fn it_selects_system_variables() {
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        let row: (u64, i64, String) = db
+            .query_first(
+                "SELECT @@max_allowed_packet, @@session.auto_increment_increment AS inc, \
+                 @@GLOBAL.tx_isolation",
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(row, (67108864, 1, "REPEATABLE-READ".to_owned()));
+
+        let result = db.query_iter("SELECT @@version_comment LIMIT 1").unwrap();
+        let columns = result.columns();
+        assert_eq!(columns.as_ref()[0].name_str(), "@@version_comment");
+        assert_eq!(result.count(), 1);
+    })
+}
+
+#[test]
+fn it_passes_unknown_system_variables_to_shim() {
+    TestingShim::new(
+        |q, w| {
+            assert_eq!(q, "SELECT @@no_such_variable");
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        assert_eq!(
+            db.query_iter("SELECT @@no_such_variable").unwrap().count(),
+            0
+        );
+    })
+}
+
+#[test]
+fn it_shows_variables() {
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        let rows: Vec<(String, String)> = db.query("SHOW VARIABLES LIKE '%_timeout'").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("connect_timeout".to_owned(), "10".to_owned()),
+                ("interactive_timeout".to_owned(), "28800".to_owned()),
+                ("net_read_timeout".to_owned(), "30".to_owned()),
+                ("net_write_timeout".to_owned(), "60".to_owned()),
+                ("wait_timeout".to_owned(), "28800".to_owned()),
+            ]
+        );
+    })
+}
+
+#[test]
+fn it_sets_system_variables() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen1 = Arc::clone(&seen);
+    TestingShim::new(
+        move |q, w| {
+            seen1.lock().unwrap().push(q.to_owned());
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        db.query_drop("SET autocommit = OFF, NAMES latin1").unwrap();
+        let row: (i64, String) = db
+            .query_first("SELECT @@autocommit, @@character_set_client")
+            .unwrap()
+            .unwrap();
+        assert_eq!(row, (0, "latin1".to_owned()));
+
+        let rows: Vec<(String, String)> = db
+            .query("SHOW SESSION VARIABLES LIKE 'autocommit'")
+            .unwrap();
+        assert_eq!(rows, vec![("autocommit".to_owned(), "OFF".to_owned())]);
+    });
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec!["SET autocommit = OFF, NAMES latin1".to_owned()]
+    );
+}
+
+#[test]
+fn it_only_sets_global_variables_if_the_shim_allows_it() {
+    for &allow in &[false, true] {
+        let vars = SystemVariables::new();
+        let server = MysqlServer::bind("127.0.0.1:0", move |_| {
+            let shim = TestingShim::new(
+                |_, w| w.completed(0, 0),
+                |_| unreachable!(),
+                |_, _, _| unreachable!(),
+                |_, _| unreachable!(),
+                |_| Ok(()),
+            )
+            .with_system_variables(vars.new_session());
+            if allow {
+                shim.with_set_global()
+            } else {
+                shim
+            }
+        })
+        .unwrap();
+        let port = serve(server);
+
+        let mut a = connect(port).unwrap();
+        let mut b = connect(port).unwrap();
+        a.query_drop("SET GLOBAL sql_select_limit = 10").unwrap();
+        let global = if allow { 10 } else { u64::MAX };
+        for db in &mut [&mut a, &mut b] {
+            let row: (u64, u64) = db
+                .query_first("SELECT @@sql_select_limit, @@global.sql_select_limit")
+                .unwrap()
+                .unwrap();
+            // like in MySQL, open connections keep their session values
+            assert_eq!(row, (u64::MAX, global));
+        }
+
+        let mut c = connect(port).unwrap();
+        let limit: u64 = c.query_first("SELECT @@sql_select_limit").unwrap().unwrap();
+        assert_eq!(limit, global);
+    }
+}
+
+#[test]
+fn it_writes_timestamps_in_the_session_time_zone() {
+    use chrono::{DateTime, TimeZone, Utc};
+    use std::convert::TryFrom;
+
+    let cols = vec![Column::new(
+        "at",
+        myc::constants::ColumnType::MYSQL_TYPE_TIMESTAMP,
+    )];
+    let cols2 = cols.clone();
+    let cols3 = cols.clone();
+    let params = cols.clone();
+    let at = Utc.ymd(2024, 2, 29).and_hms(23, 30, 0);
+
+    TestingShim::new(
+        move |q, w| {
+            if q.starts_with("SET") {
+                return w.completed(0, 0);
+            }
+            let mut w = w.start(&cols)?;
+            w.write_col(at)?;
+            w.finish()
+        },
+        |_| 0,
+        move |_, params, w| {
+            let param = DateTime::<Utc>::try_from(params[0].value)?;
+            assert_eq!(param, at);
+
+            let mut w = w.start(&cols2)?;
+            w.write_col(param)?;
+            w.finish()
+        },
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_params(params)
+    .with_columns(cols3)
+    .test(|db| {
+        let utc: String = db.query_first("SELECT at FROM t").unwrap().unwrap();
+        assert_eq!(utc, "2024-02-29 23:30:00");
+
+        db.query_drop("SET time_zone = '+02:00'").unwrap();
+        let local: String = db.query_first("SELECT at FROM t").unwrap().unwrap();
+        assert_eq!(local, "2024-03-01 01:30:00");
+
+        let local = mysql::Value::Date(2024, 3, 1, 1, 30, 0, 0);
+        let row: mysql::Row = db
+            .exec_first("SELECT at FROM t WHERE at = ?", (local.clone(),))
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.as_ref(0), Some(&local));
+    })
+}
+
+#[test]
+fn it_answers_bootstrap_queries() {
+    TestingShim::new(
+        |q, _| panic!("unexpected query {:?}", q),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, w| w.ok(),
+        |_| Ok(()),
+    )
+    .with_bootstrap()
+    .test(|db| {
+        db.query_drop("SET NAMES utf8mb4").unwrap();
+        db.query_drop("set autocommit=1").unwrap();
+        assert_eq!(db.query_iter("SHOW WARNINGS").unwrap().count(), 0);
+
+        let schema: Option<Option<String>> = db.query_first("SELECT DATABASE()").unwrap();
+        assert_eq!(schema, Some(None));
+        db.select_db("foo");
+        let schema: Option<String> = db
+            .query_first("/* hi */ select database() LIMIT 1")
+            .unwrap();
+        assert_eq!(schema, Some("foo".to_owned()));
+
+        let id: Option<u32> = db.query_first("SELECT CONNECTION_ID()").unwrap();
+        assert_eq!(id, Some(db.connection_id()));
+    })
+}
+
+#[test]
+fn it_passes_bootstrap_queries_by_default() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen1 = Arc::clone(&seen);
+    TestingShim::new(
+        move |q, w| {
+            seen1.lock().unwrap().push(q.to_owned());
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        db.query_drop("SET NAMES utf8mb4").unwrap();
+        db.query_drop("SELECT DATABASE()").unwrap();
+    });
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "SET NAMES utf8mb4".to_owned(),
+            "SELECT DATABASE()".to_owned()
+        ]
+    );
+}
+
+#[test]
+fn it_transcodes_text_to_the_session_charset() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen1 = Arc::clone(&seen);
+    TestingShim::new(
+        move |q, w| {
+            seen1.lock().unwrap().push(q.to_owned());
+            let cols = [Column::new(
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+            )];
+            let mut w = w.start(&cols)?;
+            w.write_col("caf\u{e9}")?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .test(|db| {
+        let client: String = db
+            .query_first("SELECT @@character_set_client")
+            .unwrap()
+            .unwrap();
+        assert!(client.starts_with("utf8"));
+        let row: Vec<u8> = db.query_first("SELECT 'x'").unwrap().unwrap();
+        assert_eq!(row, "caf\u{e9}".as_bytes());
+
+        db.query_drop("SET NAMES latin1").unwrap();
+        let row: Vec<u8> = db.query_first("SELECT '\u{e9}'").unwrap().unwrap();
+        assert_eq!(row, b"caf\xe9");
+    });
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "SELECT 'x'".to_owned(),
+            "SET NAMES latin1".to_owned(),
+            "SELECT '\u{c3}\u{a9}'".to_owned(),
+        ]
+    );
+}
+
+fn read_raw_packet(s: &mut net::TcpStream) -> Vec<u8> {
+    let mut header = [0; 4];
+    s.read_exact(&mut header).unwrap();
+    let len = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+    let mut body = vec![0; len];
+    s.read_exact(&mut body).unwrap();
+    body
+}
+
+fn write_raw_packet(s: &mut net::TcpStream, seq: u8, body: &[u8]) {
+    let len = body.len() as u32;
+    s.write_all(&len.to_le_bytes()[..3]).unwrap();
+    s.write_all(&[seq]).unwrap();
+    s.write_all(body).unwrap();
+}
+
+/// Connect with a hand-rolled client that advertises `capabilities` (on top of
+/// `CLIENT_PROTOCOL_41`) and the MariaDB `mariadb_capabilities`.
+fn raw_connect<S>(shim: S, capabilities: u32, mariadb_capabilities: u32) -> net::TcpStream
+where
+    S: 'static + Send + MysqlShim<net::TcpStream>,
+{
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        let _ = MysqlIntermediary::run_on_tcp(shim, s);
+    });
+    raw_handshake(port, capabilities, mariadb_capabilities)
+}
+
+/// Log in to the server on `port` with a hand-rolled client.
+fn raw_handshake(port: u16, capabilities: u32, mariadb_capabilities: u32) -> net::TcpStream {
+    let mut s = net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let greeting = read_raw_packet(&mut s);
+    assert_eq!(greeting[0], 10);
+
+    let mut handshake = Vec::new();
+    // CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION, but not CLIENT_LONG_PASSWORD since that
+    // would make MariaDB capabilities go unnoticed
+    handshake.extend(&(0x0000_8200u32 | capabilities).to_le_bytes());
+    handshake.extend(&16_777_216u32.to_le_bytes());
+    handshake.push(0x21);
+    handshake.extend(&[0; 19]);
+    handshake.extend(&mariadb_capabilities.to_le_bytes());
+    handshake.extend(b"jon\0\0");
+    write_raw_packet(&mut s, 1, &handshake);
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+    s
+}
+
+#[test]
+fn it_sends_mariadb_extended_type_info() {
+    let shim = TestingShim::new(
+        |_, w| {
+            let cols = [Column {
+                extended_type_name: Some("uuid".to_owned()),
+                extended_format: Some("json".to_owned()),
+                ..Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            }];
+            let w = w.start(&cols)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    // MARIADB_CLIENT_EXTENDED_TYPE_INFO
+    let mut s = raw_connect(shim, 0, 1 << 3);
+    write_raw_packet(&mut s, 0, b"\x03SELECT a");
+    assert_eq!(read_raw_packet(&mut s), [0x01]);
+    let def = read_raw_packet(&mut s);
+    assert!(def.starts_with(b"\x03def\x00\x00\x00\x01a\x00\x0c\x00\x04uuid\x01\x04json\x0c"));
+}
+
+#[test]
+fn it_omits_extended_type_info_for_clients_without_it() {
+    let shim = TestingShim::new(
+        |_, w| {
+            let cols = [Column {
+                extended_type_name: Some("uuid".to_owned()),
+                ..Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            }];
+            let w = w.start(&cols)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    let mut s = raw_connect(shim, 0, 0);
+    write_raw_packet(&mut s, 0, b"\x03SELECT a");
+    assert_eq!(read_raw_packet(&mut s), [0x01]);
+    let def = read_raw_packet(&mut s);
+    assert!(def.starts_with(b"\x03def\x00\x00\x00\x01a\x00\x0c"));
+}
+
+#[test]
+fn it_reports_progress_to_mariadb_clients() {
+    let shim = TestingShim::new(
+        |_, mut w| {
+            w.report_progress(1, 2, 12.5, "copy to tmp table")?;
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    // MARIADB_CLIENT_PROGRESS
+    let mut s = raw_connect(shim, 0, 1);
+    write_raw_packet(&mut s, 0, b"\x03ALTER TABLE t ADD c INT");
+    assert_eq!(
+        read_raw_packet(&mut s),
+        &b"\xff\xff\xff\x01\x01\x02\xd4\x30\x00\x11copy to tmp table"[..]
+    );
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+}
+
+#[test]
+fn it_skips_progress_reports_for_other_clients() {
+    let shim = TestingShim::new(
+        |_, mut w| {
+            w.report_progress(1, 1, 50.0, "")?;
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    let mut s = raw_connect(shim, 0, 0);
+    write_raw_packet(&mut s, 0, b"\x03ALTER TABLE t ADD c INT");
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+}
+
+#[test]
+fn it_exposes_query_attributes() {
+    let shim = TestingShim::new(
+        |q, w| {
+            assert_eq!(q, "SELECT 1");
+            let attributes: Vec<_> = w
+                .attributes()
+                .iter()
+                .map(|(n, v)| (n.to_owned(), v.into_inner()))
+                .collect();
+            assert_eq!(
+                attributes,
+                vec![(
+                    "traceparent".to_owned(),
+                    msql_srv::ValueInner::Bytes(b"00-abc")
+                )]
+            );
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    // CLIENT_QUERY_ATTRIBUTES
+    let mut s = raw_connect(shim, 0x0800_0000, 0);
+    write_raw_packet(
+        &mut s,
+        0,
+        b"\x03\x01\x01\x00\x01\xfe\x00\x0btraceparent\x0600-abcSELECT 1",
+    );
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+}
+
+#[test]
+fn it_rejects_packets_larger_than_max_allowed_packet() {
+    let vars = SystemVariables::new();
+    vars.set(VariableScope::Global, "max_allowed_packet", 1024i64);
+    let shim = TestingShim::new(
+        |_, w| w.completed(0, 0),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_system_variables(vars);
+
+    let mut s = raw_connect(shim, 0, 0);
+    let mut query = b"\x03SELECT ".to_vec();
+    query.resize(1024, b'1');
+    write_raw_packet(&mut s, 0, &query);
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+
+    // the session cannot change the limit
+    write_raw_packet(&mut s, 0, b"\x03SET max_allowed_packet = 1");
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+    write_raw_packet(&mut s, 0, &query);
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+
+    query.push(b'1');
+    write_raw_packet(&mut s, 0, &query);
+    let err = read_raw_packet(&mut s);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(
+        u16::from_le_bytes([err[1], err[2]]),
+        ErrorKind::ER_NET_PACKET_TOO_LARGE as u16
+    );
+}
+
+#[test]
+fn it_clamps_max_allowed_packet() {
+    for &(configured, effective) in &[(1i64, 1024u64), (1 << 40, 1 << 30)] {
+        let vars = SystemVariables::new();
+        vars.set(VariableScope::Global, "max_allowed_packet", configured);
+        TestingShim::new(
+            |_, _| unreachable!(),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+        .with_system_variables(vars)
+        .test(|db| {
+            let max: u64 = db
+                .query_first("SELECT @@max_allowed_packet")
+                .unwrap()
+                .unwrap();
+            assert_eq!(max, effective);
+        });
+    }
+}
+
+#[test]
+fn it_rejects_packets_out_of_order() {
+    let shim = TestingShim::new(
+        |_, _| unreachable!(),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    );
+
+    let mut s = raw_connect(shim, 0, 0);
+    write_raw_packet(&mut s, 3, b"\x03SELECT 1");
+    let err = read_raw_packet(&mut s);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(
+        u16::from_le_bytes([err[1], err[2]]),
+        ErrorKind::ER_NET_PACKETS_OUT_OF_ORDER as u16
+    );
+}
+
+/// A shim that completes every query, with the given global system variables.
+fn with_timeouts(
+    vars: &[(&str, i64)],
+) -> impl MysqlShim<net::TcpStream, Error = io::Error> + Send + 'static {
+    let system_vars = SystemVariables::default();
+    for &(name, value) in vars {
+        system_vars.set(VariableScope::Global, name, value);
+    }
+    TestingShim::new(
+        |_, w| w.completed(0, 0),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_system_variables(system_vars)
+}
+
+fn assert_timed_out(s: &mut net::TcpStream, kind: ErrorKind) {
+    let err = read_raw_packet(s);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(u16::from_le_bytes([err[1], err[2]]), kind as u16);
+    // and then the server hangs up
+    assert_eq!(s.read(&mut [0]).unwrap(), 0);
+}
+
+#[test]
+fn it_times_out_idle_clients() {
+    let mut s = raw_connect(with_timeouts(&[("wait_timeout", 1)]), 0, 0);
+    write_raw_packet(&mut s, 0, b"\x03SELECT 1");
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+
+    let idle = std::time::Instant::now();
+    assert_timed_out(&mut s, ErrorKind::ER_CLIENT_INTERACTION_TIMEOUT);
+    assert!(idle.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn it_keeps_the_idle_timeout_of_open_connections_when_the_global_changes() {
+    let vars = SystemVariables::new();
+    let server = MysqlServer::bind("127.0.0.1:0", move |_| {
+        TestingShim::new(
+            |_, w| w.completed(0, 0),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+        .with_system_variables(vars.new_session())
+        .with_set_global()
+    })
+    .unwrap();
+    let port = serve(server);
+
+    let mut open = connect(port).unwrap();
+    let mut admin = connect(port).unwrap();
+    admin
+        .query_drop("SET GLOBAL wait_timeout = 1, GLOBAL interactive_timeout = 1")
+        .unwrap();
+    let mut later = connect(port).unwrap();
+
+    // the open connection picks its idle timeout anew for every command
+    open.query_drop("SELECT 1").unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    open.query_drop("SELECT 1").unwrap();
+    assert!(later.query_drop("SELECT 1").is_err());
+}
+
+#[test]
+fn it_gives_interactive_clients_interactive_timeout() {
+    let shim = with_timeouts(&[("wait_timeout", 1), ("interactive_timeout", 3)]);
+    // CLIENT_INTERACTIVE
+    let mut s = raw_connect(shim, 0x400, 0);
+
+    // we are still around after `wait_timeout`
+    thread::sleep(Duration::from_millis(1500));
+    write_raw_packet(&mut s, 0, b"\x03SELECT 1");
+    assert_eq!(read_raw_packet(&mut s)[0], 0x00);
+
+    let idle = std::time::Instant::now();
+    assert_timed_out(&mut s, ErrorKind::ER_CLIENT_INTERACTION_TIMEOUT);
+    assert!(idle.elapsed() >= Duration::from_secs(3));
+}
+
+#[test]
+fn it_times_out_slow_handshakes() {
+    let shim = with_timeouts(&[("connect_timeout", 1)]);
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(shim, s)
+    });
+
+    let mut s = net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    assert_eq!(read_raw_packet(&mut s)[0], 10);
+    // trickle in the header of a handshake response, a byte at a time, each well within the
+    // timeout
+    for &b in &[32u8, 0, 0] {
+        s.write_all(&[b]).unwrap();
+        thread::sleep(Duration::from_millis(400));
+    }
+    assert_timed_out(&mut s, ErrorKind::ER_NET_READ_INTERRUPTED);
+    let e = jh.join().unwrap().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
+
+fn serve<F: ShimFactory>(server: MysqlServer<F>) -> u16 {
+    let port = server.local_addr().unwrap().port();
+    thread::spawn(move || server.run().unwrap());
+    port
+}
+
+fn connect(port: u16) -> mysql::Result<mysql::Conn> {
+    mysql::Conn::new(OptsBuilder::default().tcp_port(port))
+}
+
+#[test]
+fn it_serves_clients_from_a_shim_factory() {
+    let peers = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&peers);
+    let server = MysqlServer::bind("127.0.0.1:0", move |peer| {
+        seen.lock().unwrap().push(peer);
+        TestingShim::new(
+            |_, w| w.completed(1, 0),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .unwrap();
+    let port = serve(server);
+
+    let mut a = connect(port).unwrap();
+    let mut b = connect(port).unwrap();
+    a.query_drop("INSERT INTO foo VALUES (1)").unwrap();
+    b.query_drop("INSERT INTO foo VALUES (2)").unwrap();
+    assert_eq!(a.affected_rows(), 1);
+    assert_eq!(b.affected_rows(), 1);
+
+    let peers = peers.lock().unwrap();
+    assert_eq!(peers.len(), 2);
+    assert_ne!(peers[0], peers[1]);
+}
+
+#[test]
+fn it_keeps_accepting_after_accept_errors() {
+    /// A TCP listener whose first accepts fail.
+    struct FlakyListener {
+        inner: net::TcpListener,
+        failures: Mutex<Vec<io::Error>>,
+    }
+
+    impl msql_srv::Listener for FlakyListener {
+        type Stream = net::TcpStream;
+        type Addr = net::SocketAddr;
+        const TRANSPORT: Transport = Transport::Tcp;
+
+        fn accept(&self) -> io::Result<(net::TcpStream, net::SocketAddr)> {
+            match self.failures.lock().unwrap().pop() {
+                Some(e) => Err(e),
+                None => self.inner.accept(),
+            }
+        }
+
+        fn set_read_timeout(s: &net::TcpStream, t: Option<Duration>) -> io::Result<()> {
+            s.set_read_timeout(t)
+        }
+
+        fn try_clone(s: &net::TcpStream) -> io::Result<net::TcpStream> {
+            s.try_clone()
+        }
+
+        fn shutdown(s: &net::TcpStream, how: net::Shutdown) -> io::Result<()> {
+            s.shutdown(how)
+        }
+
+        fn wake(&self) -> io::Result<()> {
+            net::TcpStream::connect(self.inner.local_addr()?).map(drop)
+        }
+    }
+
+    let inner = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = inner.local_addr().unwrap().port();
+    let listener = FlakyListener {
+        inner,
+        failures: Mutex::new(vec![
+            io::Error::from(io::ErrorKind::ConnectionAborted),
+            // EMFILE on Linux; anything that is not about a single client will do
+            io::Error::from_raw_os_error(24),
+            io::Error::from_raw_os_error(24),
+        ]),
+    };
+    let errors = Arc::new(Mutex::new(0));
+    let seen = Arc::clone(&errors);
+    let server = MysqlServer::new(listener, |_| {
+        TestingShim::new(
+            |_, w| w.completed(1, 0),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .on_accept_error(move |_| *seen.lock().unwrap() += 1);
+    thread::spawn(move || server.run().unwrap());
+
+    let mut db = connect(port).unwrap();
+    db.query_drop("INSERT INTO foo VALUES (1)").unwrap();
+    assert_eq!(*errors.lock().unwrap(), 3);
+}
+
+#[test]
+fn it_turns_away_clients_over_max_connections() {
+    let server = MysqlServer::bind("127.0.0.1:0", |_| {
+        TestingShim::new(
+            |_, _| unreachable!(),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .unwrap()
+    .max_connections(1);
+    let port = serve(server);
+
+    let mut a = connect(port).unwrap();
+    assert!(a.ping());
+    match connect(port) {
+        Err(mysql::Error::MySqlError(e)) => {
+            assert_eq!(e.code, ErrorKind::ER_CON_COUNT_ERROR as u16)
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("connected despite the limit"),
+    }
+
+    // the slot frees up once the first client has left
+    drop(a);
+    let mut retries = 0;
+    let mut b = loop {
+        match connect(port) {
+            Ok(b) => break b,
+            Err(_) if retries < 50 => {
+                retries += 1;
+                thread::sleep(Duration::from_millis(20));
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    };
+    assert!(b.ping());
+}
+
+#[test]
+fn it_reports_connection_errors() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server = MysqlServer::bind("127.0.0.1:0", |_| {
+        TestingShim::new(
+            |_, _| Err(io::Error::other("backend went away")),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .unwrap()
+    .on_error(move |peer, e| tx.lock().unwrap().send((peer, e)).unwrap());
+    let port = serve(server);
+
+    let mut db = connect(port).unwrap();
+    db.query_drop("SELECT 1").unwrap_err();
+    let (peer, e) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(peer.ip(), std::net::Ipv4Addr::LOCALHOST);
+    assert_eq!(e.to_string(), "backend went away");
+}
+
+/// Reads the error that a server that is shutting down sends, and then the end of the stream.
+fn assert_shut_down(s: &mut net::TcpStream) {
+    let err = read_raw_packet(s);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(
+        u16::from_le_bytes([err[1], err[2]]),
+        ErrorKind::ER_SERVER_SHUTDOWN as u16
+    );
+    assert_eq!(s.read(&mut [0]).unwrap(), 0);
+}
+
+#[test]
+fn it_shuts_down_gracefully() {
+    let server = MysqlServer::bind("127.0.0.1:0", |_| {
+        TestingShim::new(
+            |q, w| {
+                if q == "SELECT SLEEP(1)" {
+                    thread::sleep(Duration::from_secs(1));
+                }
+                w.completed(1, 0)
+            },
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .unwrap();
+    let port = server.local_addr().unwrap().port();
+    let handle = server.shutdown_handle();
+    let jh = thread::spawn(move || server.run());
+
+    let mut idle = raw_handshake(port, 0, 0);
+    let mut busy = raw_handshake(port, 0, 0);
+    write_raw_packet(&mut busy, 0, b"\x03SELECT SLEEP(1)");
+    thread::sleep(Duration::from_millis(200));
+
+    let started = std::time::Instant::now();
+    handle.shutdown(Duration::from_secs(10));
+    // we waited for the running command, but not for the grace period
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert!(started.elapsed() < Duration::from_secs(10));
+    jh.join().unwrap().unwrap();
+
+    assert_shut_down(&mut idle);
+    // the command that was running when we shut down completed
+    assert_eq!(read_raw_packet(&mut busy)[0], 0x00);
+    assert_shut_down(&mut busy);
+
+    // and nobody is listening anymore
+    assert!(net::TcpStream::connect(("127.0.0.1", port)).is_err());
+}
+
+#[test]
+fn it_drops_connections_that_outlive_the_grace_period() {
+    let server = MysqlServer::bind("127.0.0.1:0", |_| {
+        TestingShim::new(
+            |_, w| {
+                thread::sleep(Duration::from_secs(2));
+                w.completed(1, 0)
+            },
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+    })
+    .unwrap();
+    let port = server.local_addr().unwrap().port();
+    let handle = server.shutdown_handle();
+    thread::spawn(move || server.run());
+
+    let mut s = raw_handshake(port, 0, 0);
+    write_raw_packet(&mut s, 0, b"\x03SELECT SLEEP(2)");
+    thread::sleep(Duration::from_millis(200));
+
+    let started = std::time::Instant::now();
+    handle.shutdown(Duration::from_millis(200));
+    assert!(started.elapsed() < Duration::from_secs(1));
+    // the connection was closed from under the running command
+    assert_eq!(s.read(&mut [0]).unwrap(), 0);
+}
+
+#[test]
+fn it_shuts_down_intermediaries_through_the_shims_handle() {
+    let handle = ShutdownHandle::new();
+    let shim = || {
+        TestingShim::new(
+            |_, w| w.completed(1, 0),
+            |_| unreachable!(),
+            |_, _, _| unreachable!(),
+            |_, _| unreachable!(),
+            |_| Ok(()),
+        )
+        .with_shutdown_handle(handle.clone())
+    };
+    let mut idle = raw_connect(shim(), 0, 0);
+
+    let started = std::time::Instant::now();
+    handle.shutdown(Duration::from_secs(10));
+    // the idle client was woken up rather than waited for
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_shut_down(&mut idle);
+
+    // clients that connect afterwards are sent away as well
+    let mut late = raw_connect(shim(), 0, 0);
+    assert_shut_down(&mut late);
+}
+
+#[test]
+fn it_reports_tcp_as_an_insecure_transport() {
+    TestingShim::new(
+        |_, w| w.completed(0, 0),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+        |a| {
+            assert_eq!(a.transport, Transport::Tcp);
+            assert!(!a.tls);
+            assert!(!a.is_secure());
+            Ok(())
+        },
+    )
+    .test(|db| db.query_drop("SELECT 1").unwrap());
+}
+
+/// A shim that only reports how its client connected.
+#[cfg(unix)]
+struct TransportShim(std::sync::mpsc::Sender<(Transport, bool)>);
+
+#[cfg(unix)]
+impl<W: io::Read + io::Write> MysqlShim<W> for TransportShim {
+    type Error = io::Error;
+
+    fn on_prepare(&mut self, _: &str, _: StatementMetaWriter<W>) -> io::Result<()> {
+        unreachable!()
+    }
+
+    fn on_execute(&mut self, _: u32, _: ParamParser, _: QueryResultWriter<W>) -> io::Result<()> {
+        unreachable!()
+    }
+
+    fn on_close(&mut self, _: u32) {}
+
+    fn on_query(&mut self, _: &str, results: QueryResultWriter<W>) -> io::Result<()> {
+        results.completed(1, 0)
+    }
+
+    fn after_authentication(&mut self, auth_context: &AuthenticationContext) -> io::Result<()> {
+        self.0
+            .send((auth_context.transport, auth_context.is_secure()))
+            .unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn it_runs_on_unix_sockets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mysqld.sock");
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_unix(TransportShim(tx), s)
+    });
+
+    let opts = OptsBuilder::default().socket(Some(path.to_str().unwrap()));
+    let mut db = mysql::Conn::new(opts).unwrap();
+    db.query_drop("INSERT INTO foo VALUES (1)").unwrap();
+    assert_eq!(db.affected_rows(), 1);
+    drop(db);
+    jh.join().unwrap().unwrap();
+    assert_eq!(rx.recv().unwrap(), (Transport::Unix, true));
+}
+
+#[test]
+#[cfg(unix)]
+fn it_serves_clients_on_a_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mysqld.sock");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server =
+        MysqlServer::bind_unix(&path, move |_| TransportShim(tx.lock().unwrap().clone())).unwrap();
+    thread::spawn(move || server.run().unwrap());
+
+    for _ in 0..2 {
+        let opts = OptsBuilder::default().socket(Some(path.to_str().unwrap()));
+        let mut db = mysql::Conn::new(opts).unwrap();
+        db.query_drop("INSERT INTO foo VALUES (1)").unwrap();
+        assert_eq!(rx.recv().unwrap(), (Transport::Unix, true));
+    }
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn it_serializes_rows() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    enum Role {
+        Admin,
+        Member,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct User<'a> {
+        id: u32,
+        name: &'a str,
+        score: f64,
+        active: bool,
+        email: Option<String>,
+        role: Role,
+    }
+
+    fn users() -> Vec<User<'static>> {
+        vec![
+            User {
+                id: 1,
+                name: "jane",
+                score: 1.5,
+                active: true,
+                email: Some("jane@example.com".to_owned()),
+                role: Role::Admin,
+            },
+            User {
+                id: 2,
+                name: "joe",
+                score: -2.0,
+                active: false,
+                email: None,
+                role: Role::Member,
+            },
+        ]
+    }
+
+    fn write_users<W: Read + Write>(w: QueryResultWriter<'_, W>) -> io::Result<()> {
+        let cols = msql_srv::columns_for::<User<'_>>()?;
+        let mut w = w.start(&cols)?;
+        for user in users() {
+            w.serialize(&user)?;
+        }
+        w.finish()
+    }
+
+    let check = |rows: Vec<mysql::Row>| {
+        assert_eq!(rows.len(), 2);
+        let names: Vec<_> = rows[0]
+            .columns_ref()
+            .iter()
+            .map(|c| c.name_str().into_owned())
+            .collect();
+        assert_eq!(names, ["id", "name", "score", "active", "email", "role"]);
+        type Row = (u32, String, f64, bool, Option<String>, String);
+        let rows: Vec<Row> = rows.into_iter().map(mysql::from_row).collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    1,
+                    "jane".to_owned(),
+                    1.5,
+                    true,
+                    Some("jane@example.com".to_owned()),
+                    "Admin".to_owned()
+                ),
+                (2, "joe".to_owned(), -2.0, false, None, "Member".to_owned()),
+            ]
+        );
+    };
+
+    TestingShim::new(
+        |_, w| write_users(w),
+        |_| 0,
+        |_, _, w| write_users(w),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_columns(msql_srv::columns_for::<User<'_>>().unwrap())
+    .test(|db| {
+        check(db.query("SELECT * FROM users").unwrap());
+        check(db.exec("SELECT * FROM users", ()).unwrap());
+    })
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn it_rejects_serialized_rows_that_do_not_match_the_columns() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Login {
+        id: u32,
+        #[serde(skip_serializing)]
+        password: String,
+    }
+
+    fn write_login<W: Read + Write>(w: QueryResultWriter<'_, W>) -> io::Result<()> {
+        let cols = msql_srv::columns_for::<Login>()?;
+        let mut w = w.start(&cols)?;
+        let login = Login {
+            id: 1,
+            password: "hunter2".to_owned(),
+        };
+        let e = w.serialize(&login).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            e.to_string(),
+            "row has 1 fields, but 2 columns are left to write"
+        );
+        assert!(w.serialize(&(1u32, 2u32, 3u32)).is_err());
+
+        // nothing was written, so the resultset can still be completed
+        w.serialize(&(2u32, "secret"))?;
+        w.finish()
+    }
+
+    TestingShim::new(
+        |_, w| write_login(w),
+        |_| 0,
+        |_, _, w| write_login(w),
+        |_, _| unreachable!(),
+        |_| Ok(()),
+    )
+    .with_columns(msql_srv::columns_for::<Login>().unwrap())
+    .test(|db| {
+        let rows: Vec<(u32, String)> = db.query("SELECT * FROM logins").unwrap();
+        assert_eq!(rows, [(2, "secret".to_owned())]);
+        let rows: Vec<(u32, String)> = db.exec("SELECT * FROM logins", ()).unwrap();
+        assert_eq!(rows, [(2, "secret".to_owned())]);
+    })
+}
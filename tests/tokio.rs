@@ -0,0 +1,249 @@
+#![cfg(feature = "tokio")]
+
+extern crate async_trait;
+extern crate msql_srv;
+extern crate mysql_async;
+extern crate mysql_common as myc;
+extern crate tokio;
+
+use async_trait::async_trait;
+use mysql_async::prelude::*;
+use mysql_async::Opts;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::time::{Duration, Instant};
+
+use msql_srv::{
+    AsyncMysqlIntermediary, AsyncMysqlShim, AsyncStream, Column, ErrorKind, InitWriter,
+    ParamParser, QueryResultWriter, ShutdownHandle, StatementMetaWriter, SystemVariables,
+    VariableScope,
+};
+
+#[derive(Default)]
+struct Backend {
+    rows: usize,
+    wait_timeout: Option<i64>,
+    shutdown: Option<ShutdownHandle>,
+}
+
+#[async_trait]
+impl AsyncMysqlShim for Backend {
+    type Error = io::Error;
+
+    async fn on_prepare(
+        &mut self,
+        _: &str,
+        info: StatementMetaWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        let params = [Column::new(
+            "?",
+            myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+        )];
+        let cols = [Column::new(
+            "a",
+            myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+        )];
+        info.reply(1, &params, &cols)
+    }
+
+    async fn on_execute(
+        &mut self,
+        _: u32,
+        params: ParamParser<'_>,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        let n = i64::try_from(params.into_iter().next().unwrap().value)?;
+        tokio::task::yield_now().await;
+        let cols = [Column::new(
+            "a",
+            myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+        )];
+        let mut w = results.start(&cols)?;
+        w.write_row([n * 2])?;
+        w.finish()
+    }
+
+    async fn on_close(&mut self, _: u32) {}
+
+    async fn on_query(
+        &mut self,
+        query: &str,
+        results: QueryResultWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        if query.starts_with("DELETE") {
+            return results.error(ErrorKind::ER_TABLEACCESS_DENIED_ERROR, b"no");
+        }
+
+        let cols = [
+            Column::new("a", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+            Column::new("b", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+        ];
+        let mut w = results.start(&cols)?;
+        for i in 0..self.rows {
+            w.write_col(i as i64)?;
+            w.write_col("x".repeat(100))?;
+            w.end_row()?;
+            if i % 100 == 99 {
+                // send rows as we go rather than buffering the whole resultset
+                w.flush().await?;
+            }
+        }
+        w.finish()
+    }
+
+    async fn on_init(
+        &mut self,
+        schema: &str,
+        writer: InitWriter<'_, AsyncStream>,
+    ) -> io::Result<()> {
+        if schema == "test" {
+            writer.ok()
+        } else {
+            writer.error(ErrorKind::ER_BAD_DB_ERROR, b"no such database")
+        }
+    }
+
+    fn system_variables(&mut self) -> SystemVariables {
+        let vars = SystemVariables::default();
+        if let Some(timeout) = self.wait_timeout {
+            vars.set(VariableScope::Global, "wait_timeout", timeout);
+        }
+        vars
+    }
+
+    fn shutdown_handle(&self) -> Option<ShutdownHandle> {
+        self.shutdown.clone()
+    }
+}
+
+impl Backend {
+    fn test<C, F>(self, c: C)
+    where
+        F: Future<Output = Result<(), Box<dyn Error>>>,
+        C: FnOnce(mysql_async::Conn) -> F,
+    {
+        self.test_with_result(c).unwrap()
+    }
+
+    fn test_with_result<C, F>(self, c: C) -> io::Result<()>
+    where
+        F: Future<Output = Result<(), Box<dyn Error>>>,
+        C: FnOnce(mysql_async::Conn) -> F,
+    {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async move {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let jh = tokio::spawn(async move {
+                let (s, _) = listener.accept().await.unwrap();
+                AsyncMysqlIntermediary::run_on_tokio(self, s).await
+            });
+
+            let conn = mysql_async::Conn::new(
+                Opts::from_url(&format!("mysql://127.0.0.1:{}", port)).unwrap(),
+            )
+            .await
+            .unwrap();
+            c(conn).await.unwrap();
+            jh.await.unwrap()
+        })
+    }
+}
+
+#[test]
+fn it_pings() {
+    Backend::default().test(|mut db| async move {
+        db.ping().await?;
+        db.disconnect().await?;
+        Ok(())
+    })
+}
+
+#[test]
+fn it_queries() {
+    Backend {
+        rows: 1000,
+        ..Backend::default()
+    }
+    .test(|mut db| async move {
+        let rows: Vec<(i64, String)> = db.query("SELECT a, b FROM foo").await?;
+        assert_eq!(rows.len(), 1000);
+        assert_eq!(rows[999].0, 999);
+        assert_eq!(rows[999].1.len(), 100);
+
+        let err = db.query_drop("DELETE FROM foo").await.unwrap_err();
+        match err {
+            mysql_async::Error::Server(e) => {
+                assert_eq!(e.code, ErrorKind::ER_TABLEACCESS_DENIED_ERROR as u16)
+            }
+            e => return Err(e.into()),
+        }
+        db.disconnect().await?;
+        Ok(())
+    })
+}
+
+#[test]
+fn it_executes() {
+    Backend::default().test(|mut db| async move {
+        let doubled: Option<i64> = db
+            .exec_first("SELECT a FROM foo WHERE a = ?", (21,))
+            .await?;
+        assert_eq!(doubled, Some(42));
+        db.disconnect().await?;
+        Ok(())
+    })
+}
+
+#[test]
+fn it_switches_schema() {
+    Backend::default().test(|mut db| async move {
+        db.query_drop("USE test").await?;
+        assert!(db.query_drop("USE nope").await.is_err());
+        db.disconnect().await?;
+        Ok(())
+    })
+}
+
+#[test]
+fn it_times_out_idle_clients() {
+    let backend = Backend {
+        wait_timeout: Some(1),
+        ..Backend::default()
+    };
+    let e = backend
+        .test_with_result(|mut db| async move {
+            db.ping().await?;
+            tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+            assert!(db.ping().await.is_err());
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn it_shuts_down_idle_clients() {
+    let handle = ShutdownHandle::new();
+    let backend = Backend {
+        shutdown: Some(handle.clone()),
+        ..Backend::default()
+    };
+    backend.test(|mut db| async move {
+        db.ping().await?;
+
+        let started = Instant::now();
+        // shutting down blocks until the connection is closed
+        tokio::task::spawn_blocking(move || handle.shutdown(Duration::from_secs(10))).await?;
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // the client sees our goodbye as an out-of-order reply to its ping
+        assert!(db.ping().await.is_err());
+        Ok(())
+    })
+}